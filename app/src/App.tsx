@@ -6,6 +6,7 @@ import { ImportView } from './features/import/ImportView'
 import { SettingsView } from './features/settings/SettingsView'
 import { LockScreen } from './features/lock/LockScreen'
 import { useKeyboardShortcuts } from './hooks/useKeyboardShortcuts'
+import { useVaultLockEvents } from './hooks/useVaultLockEvents'
 import { resolveTheme, useAppShellStore, type AppView } from './stores/appShell'
 
 const ViewRenderer = ({ view }: { view: AppView }) => {
@@ -36,6 +37,7 @@ const App = () => {
   const theme = useAppShellStore((state) => state.theme)
 
   useKeyboardShortcuts()
+  useVaultLockEvents()
 
   useEffect(() => {
     const applyTheme = () => {
@@ -1,4 +1,5 @@
-import { useMemo } from 'react'
+import { useCallback, useMemo } from 'react'
+import { apiClient } from '../lib/apiClient'
 import { useAppShellStore, type AppView } from '../stores/appShell'
 import { useQuickAddStore } from '../stores/quickAdd'
 
@@ -20,6 +21,11 @@ export const Sidebar = () => {
   const lock = useAppShellStore((state) => state.lock)
   const openQuickAdd = useQuickAddStore((state) => state.open)
 
+  const lockVault = useCallback(async () => {
+    await apiClient.lockVault()
+    lock()
+  }, [lock])
+
   const navButtons = useMemo(
     () =>
       NAV_ITEMS.map((item) => {
@@ -67,7 +73,7 @@ export const Sidebar = () => {
         </button>
         <button
           type="button"
-          onClick={lock}
+          onClick={lockVault}
           className="w-full rounded-lg border border-slate-800 px-3 py-2 text-xs font-medium text-slate-400 hover:border-slate-700 hover:text-slate-100"
         >
           Lock Vault
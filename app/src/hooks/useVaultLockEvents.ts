@@ -0,0 +1,26 @@
+import { useEffect } from 'react'
+import { listenNative } from '../lib/nativeBridge'
+import { useAppShellStore } from '../stores/appShell'
+
+/** Drops to the lock screen when the backend locks the vault on its own. */
+export const useVaultLockEvents = () => {
+  const lock = useAppShellStore((state) => state.lock)
+
+  useEffect(() => {
+    let unlisten: (() => void) | undefined
+    let disposed = false
+
+    listenNative<string>('vault-locked', () => lock()).then((stop) => {
+      if (disposed) {
+        stop()
+      } else {
+        unlisten = stop
+      }
+    })
+
+    return () => {
+      disposed = true
+      unlisten?.()
+    }
+  }, [lock])
+}
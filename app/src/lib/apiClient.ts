@@ -55,6 +55,7 @@ export interface ApiClient {
   updateSetting: (key: string, value: string) => Promise<void>
   getBudgets: () => Promise<BudgetConfig[]>
   setBudget: (category: string, cap: number) => Promise<void>
+  lockVault: () => Promise<void>
 }
 
 type ApiImplementation = ApiClient
@@ -195,6 +196,9 @@ const localTransport: ApiImplementation = {
   async setBudget(category, cap) {
     await invokeNative<void>('set_budget_config', { category, cap })
   },
+  async lockVault() {
+    await invokeNative<void>('lock_vault')
+  },
 }
 
 const remoteTransport: ApiImplementation = {
@@ -238,6 +242,9 @@ const remoteTransport: ApiImplementation = {
   async setBudget(category, cap) {
     console.warn('[apiClient] remote transport not implemented, falling back to mock data.')
   },
+  async lockVault() {
+    console.warn('[apiClient] remote transport not implemented, locking locally only.')
+  },
 }
 
 const transports: Record<TransportMode, ApiImplementation> = {
@@ -257,4 +264,5 @@ export const apiClient: ApiClient = {
   updateSetting: (key, value) => transports[transportMode].updateSetting(key, value),
   getBudgets: () => transports[transportMode].getBudgets(),
   setBudget: (category, cap) => transports[transportMode].setBudget(category, cap),
+  lockVault: () => transports[transportMode].lockVault(),
 }
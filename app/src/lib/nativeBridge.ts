@@ -1,3 +1,15 @@
+import { useAppShellStore } from '../stores/appShell'
+
+type BridgeWindow<T> = typeof window & {
+  __TAURI__?: {
+    core?: { invoke: (cmd: string, args?: Record<string, unknown>) => Promise<T> }
+    tauri?: { invoke: (cmd: string, args?: Record<string, unknown>) => Promise<T> }
+    event?: {
+      listen: (event: string, handler: (event: { payload: T }) => void) => Promise<() => void>
+    }
+  }
+}
+
 export const invokeNative = async <T,>(
   command: string,
   payload?: Record<string, unknown>,
@@ -6,12 +18,7 @@ export const invokeNative = async <T,>(
     return null
   }
 
-  const bridgeWindow = window as typeof window & {
-    __TAURI__?: {
-      core?: { invoke: (cmd: string, args?: Record<string, unknown>) => Promise<T> }
-      tauri?: { invoke: (cmd: string, args?: Record<string, unknown>) => Promise<T> }
-    }
-  }
+  const bridgeWindow = window as BridgeWindow<T>
 
   const invoke =
     bridgeWindow.__TAURI__?.core?.invoke ?? bridgeWindow.__TAURI__?.tauri?.invoke
@@ -23,7 +30,27 @@ export const invokeNative = async <T,>(
   try {
     return await invoke(command, payload)
   } catch (error) {
+    if ((error as { kind?: string } | null)?.kind === 'locked') {
+      useAppShellStore.getState().lock()
+    }
     console.warn(`[nativeBridge] invoke for ${command} failed`, error)
     return null
   }
 }
+
+/** Subscribes to a backend event. Resolves to a no-op unsubscribe outside Tauri. */
+export const listenNative = async <T,>(
+  event: string,
+  handler: (payload: T) => void,
+): Promise<() => void> => {
+  if (typeof window === 'undefined') {
+    return () => undefined
+  }
+
+  const listen = (window as BridgeWindow<T>).__TAURI__?.event?.listen
+  if (!listen) {
+    return () => undefined
+  }
+
+  return listen(event, (message) => handler(message.payload))
+}
@@ -0,0 +1,327 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::dto::{AuditEntry, AuditQuery, AuditVerification};
+
+/// `prev_hash` of the first entry.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+/// Settings key holding `<entries>:<head hash>` as of the last append, so
+/// `verify` can tell when entries were cut from the end.
+pub const ANCHOR: &str = "audit_anchor";
+const DEFAULT_PAGE: usize = 100;
+const MAX_PAGE: usize = 1000;
+
+/// Appends an entry chained to the anchored head and moves the anchor. Call it on
+/// the connection (or transaction) that made the change, so the entry and anchor
+/// commit or roll back with it. `prev_hash` is unique, so two racing writers
+/// cannot fork the chain: the loser's insert fails.
+///
+/// Chaining from the anchor rather than the newest row means entries appended
+/// after a truncation do not hide it: the first of them points at a missing hash.
+pub fn record(
+    conn: &Connection,
+    operation: &str,
+    entity: &str,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<()> {
+    let (entries, prev_hash) = match anchor(conn)? {
+        Some(anchor) => anchor,
+        // Vaults from before the anchor existed pick up from their newest entry.
+        None => (
+            conn.query_row("SELECT COUNT(*) FROM audit_log", [], |row| row.get(0))?,
+            head(conn)?.unwrap_or_else(|| GENESIS.to_string()),
+        ),
+    };
+    let occurred_at: String =
+        conn.query_row("SELECT strftime('%Y-%m-%dT%H:%M:%fZ', 'now')", [], |row| {
+            row.get(0)
+        })?;
+    let before = before.map(|value| value.to_string());
+    let after = after.map(|value| value.to_string());
+    let hash = entry_hash(
+        &prev_hash,
+        &occurred_at,
+        operation,
+        entity,
+        before.as_deref(),
+        after.as_deref(),
+    );
+
+    conn.execute(
+        "INSERT INTO audit_log (occurred_at, operation, entity, before_json, after_json, prev_hash, hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![occurred_at, operation, entity, before, after, prev_hash, hash],
+    )
+    .context("Failed to append audit log entry")?;
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value=excluded.value",
+        params![ANCHOR, format!("{}:{hash}", entries + 1)],
+    )
+    .context("Failed to move audit log anchor")?;
+    Ok(())
+}
+
+pub fn list(conn: &Connection, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE).min(MAX_PAGE);
+    let (entity, entity_prefix) = match query.entity.as_deref() {
+        Some(prefix) if prefix.ends_with(':') => (None, Some(format!("{}%", escape_like(prefix)))),
+        entity => (entity, None),
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT id, occurred_at, operation, entity, before_json, after_json, hash FROM audit_log
+         WHERE (?1 IS NULL OR operation = ?1)
+           AND (?2 IS NULL OR entity = ?2)
+           AND (?3 IS NULL OR entity LIKE ?3 ESCAPE '\\')
+           AND (?4 IS NULL OR id < ?4)
+         ORDER BY id DESC LIMIT ?5",
+    )?;
+    let rows = stmt
+        .query_map(
+            params![
+                query.operation,
+                entity,
+                entity_prefix,
+                query.before_id,
+                limit as i64
+            ],
+            |row| {
+                Ok((
+                    AuditEntry {
+                        id: row.get(0)?,
+                        occurred_at: row.get(1)?,
+                        operation: row.get(2)?,
+                        entity: row.get(3)?,
+                        before: None,
+                        after: None,
+                        hash: row.get(6)?,
+                    },
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch audit log")?;
+
+    rows.into_iter()
+        .map(|(mut entry, before, after)| {
+            entry.before = before.as_deref().map(serde_json::from_str).transpose()?;
+            entry.after = after.as_deref().map(serde_json::from_str).transpose()?;
+            Ok(entry)
+        })
+        .collect()
+}
+
+/// Walks the chain from the first entry, recomputing every hash and checking each
+/// entry points at its predecessor. Editing, inserting or deleting an entry breaks
+/// the chain from that point on; deleting the newest entries leaves the chain short
+/// of the anchored head.
+pub fn verify(conn: &Connection) -> Result<AuditVerification> {
+    let mut stmt = conn.prepare(
+        "SELECT id, occurred_at, operation, entity, before_json, after_json, prev_hash, hash
+         FROM audit_log ORDER BY id",
+    )?;
+    let mut rows = stmt.query([])?;
+
+    let mut expected_prev = GENESIS.to_string();
+    let mut entries = 0;
+    let mut first_invalid_id = None;
+    let mut head_hash = None;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let prev_hash: String = row.get(6)?;
+        let hash: String = row.get(7)?;
+        let recomputed = entry_hash(
+            &prev_hash,
+            &row.get::<_, String>(1)?,
+            &row.get::<_, String>(2)?,
+            &row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?.as_deref(),
+            row.get::<_, Option<String>>(5)?.as_deref(),
+        );
+        if first_invalid_id.is_none() && (prev_hash != expected_prev || recomputed != hash) {
+            first_invalid_id = Some(id);
+        }
+        entries += 1;
+        expected_prev = hash.clone();
+        head_hash = Some(hash);
+    }
+
+    let truncated = match anchor(conn)? {
+        Some((anchored_entries, anchored_head)) => {
+            anchored_entries != entries || head_hash.as_ref() != Some(&anchored_head)
+        }
+        None => false,
+    };
+
+    Ok(AuditVerification {
+        entries,
+        valid: first_invalid_id.is_none() && !truncated,
+        first_invalid_id,
+        truncated,
+        head_hash,
+    })
+}
+
+fn anchor(conn: &Connection) -> Result<Option<(usize, String)>> {
+    let Some(value) = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key=?1",
+            params![ANCHOR],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .context("Failed to read audit log anchor")?
+    else {
+        return Ok(None);
+    };
+    let (entries, hash) = value
+        .split_once(':')
+        .and_then(|(entries, hash)| Some((entries.parse().ok()?, hash.to_string())))
+        .with_context(|| format!("Malformed audit log anchor: {value}"))?;
+    Ok(Some((entries, hash)))
+}
+
+fn head(conn: &Connection) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT hash FROM audit_log ORDER BY id DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+    .context("Failed to read audit log head")
+}
+
+/// SHA-256 over the length-prefixed fields, so no two different entries can
+/// serialise to the same input. A missing `before`/`after` hashes differently from
+/// any JSON text.
+fn entry_hash(
+    prev_hash: &str,
+    occurred_at: &str,
+    operation: &str,
+    entity: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> String {
+    let mut hasher = Sha256::new();
+    for field in [
+        Some(prev_hash),
+        Some(occurred_at),
+        Some(operation),
+        Some(entity),
+        before,
+        after,
+    ] {
+        match field {
+            Some(text) => {
+                hasher.update([1]);
+                hasher.update((text.len() as u64).to_le_bytes());
+                hasher.update(text.as_bytes());
+            }
+            None => hasher.update([0]),
+        }
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+    use serde_json::json;
+
+    #[test]
+    fn detects_edits_to_the_chain() {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        record(
+            &conn,
+            "set_budget_config",
+            "budget:Food",
+            None,
+            Some(json!({ "cap": 5000.0 })),
+        )
+        .unwrap();
+        record(
+            &conn,
+            "set_budget_config",
+            "budget:Food",
+            Some(json!({ "cap": 5000.0 })),
+            Some(json!({ "cap": 6000.0 })),
+        )
+        .unwrap();
+        record(
+            &conn,
+            "update_setting",
+            "setting:theme",
+            None,
+            Some(json!({ "value": "dark" })),
+        )
+        .unwrap();
+
+        let verification = verify(&conn).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.entries, 3);
+        // The anchor cannot be forged through the settings API.
+        assert!(crate::settings::set_setting(&conn, ANCHOR, "3:forged").is_err());
+
+        let budgets = list(
+            &conn,
+            &AuditQuery {
+                entity: Some("budget:".into()),
+                ..AuditQuery::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(budgets.len(), 2);
+        assert_eq!(budgets[0].after, Some(json!({ "cap": 6000.0 })));
+
+        // The log refuses edits through SQL...
+        assert!(conn
+            .execute("UPDATE audit_log SET after_json = '{}' WHERE id = 2", [])
+            .is_err());
+        assert!(conn
+            .execute("DELETE FROM audit_log WHERE id = 2", [])
+            .is_err());
+
+        // ...and an edit that gets around the triggers is caught by verification.
+        conn.execute_batch("DROP TRIGGER audit_log_no_update;")
+            .unwrap();
+        conn.execute(
+            "UPDATE audit_log SET after_json = '{\"cap\":60000.0}' WHERE id = 2",
+            [],
+        )
+        .unwrap();
+        let verification = verify(&conn).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_id, Some(2));
+        assert!(!verification.truncated);
+
+        // Cutting the newest entry leaves the chain short of the anchored head...
+        conn.execute_batch(
+            "DROP TRIGGER audit_log_no_delete;
+             DELETE FROM audit_log WHERE id = 3;",
+        )
+        .unwrap();
+        let verification = verify(&conn).unwrap();
+        assert!(verification.truncated);
+        assert_eq!(verification.entries, 2);
+
+        // ...and a later entry chains from the anchor, so it cannot paper over the gap.
+        record(&conn, "update_setting", "setting:theme", None, None).unwrap();
+        let verification = verify(&conn).unwrap();
+        assert!(verification.truncated);
+        assert_eq!(verification.entries, 3);
+    }
+}
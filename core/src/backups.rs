@@ -0,0 +1,364 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use rusqlite::{backup::Backup, Connection};
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+use crate::{
+    dto::{BackupPolicy, BackupSnapshot},
+    key_provider::VaultKey,
+    storage,
+};
+
+const PREFIX: &str = "vault-";
+const SUFFIX: &str = ".db.enc";
+const PARTIAL_SUFFIX: &str = ".partial";
+const PAGES_PER_STEP: std::os::raw::c_int = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+/// Default location for snapshots: a `backups` directory next to the vault file.
+pub fn directory_for(vault: &Path) -> PathBuf {
+    vault
+        .parent()
+        .map_or_else(|| PathBuf::from("backups"), |parent| parent.join("backups"))
+}
+
+/// Copies the live vault into `dir` with SQLite's online backup API. The copy is
+/// written under a temporary name and only renamed into place once it passes
+/// `PRAGMA integrity_check`.
+pub fn create(
+    conn: &Connection,
+    dir: &Path,
+    key: &VaultKey,
+    now: PrimitiveDateTime,
+) -> Result<BackupSnapshot> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create backup directory: {}", dir.display()))?;
+
+    let name = snapshot_name(now);
+    let path = dir.join(&name);
+    if path.exists() {
+        bail!("Snapshot {name} already exists");
+    }
+
+    let partial = dir.join(format!("{name}{PARTIAL_SUFFIX}"));
+    let _ = fs::remove_file(&partial);
+    let copied = storage::open_keyed(&partial, key).and_then(|mut target| {
+        Backup::new(conn, &mut target)?
+            .run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)
+            .context("Online backup failed")
+    });
+    if let Err(error) = copied.and_then(|_| check_integrity(&partial, key)) {
+        let _ = fs::remove_file(&partial);
+        return Err(error);
+    }
+
+    fs::rename(&partial, &path).context("Failed to finalise snapshot")?;
+    describe(&path, &name, now)
+}
+
+/// Snapshots in `dir`, newest first. Files that don't follow the naming scheme are
+/// ignored.
+pub fn list(dir: &Path) -> Result<Vec<BackupSnapshot>> {
+    let mut snapshots = Vec::new();
+    for (name, taken_at) in snapshot_times(dir)? {
+        snapshots.push(describe(&dir.join(&name), &name, taken_at)?);
+    }
+    Ok(snapshots)
+}
+
+/// Whether the newest snapshot is older than `interval` (or there is none).
+pub fn is_due(dir: &Path, now: PrimitiveDateTime, interval: Duration) -> Result<bool> {
+    Ok(match snapshot_times(dir)?.first() {
+        Some((_, newest)) => (now - *newest).unsigned_abs() >= interval,
+        None => true,
+    })
+}
+
+/// Deletes snapshots the policy no longer retains, returning their names.
+pub fn prune(dir: &Path, policy: &BackupPolicy) -> Result<Vec<String>> {
+    let snapshots = snapshot_times(dir)?;
+    let keep = retained(&snapshots, policy);
+
+    let mut removed = Vec::new();
+    for (name, _) in snapshots {
+        if !keep.contains(&name) {
+            fs::remove_file(dir.join(&name))
+                .with_context(|| format!("Failed to remove snapshot {name}"))?;
+            removed.push(name);
+        }
+    }
+    Ok(removed)
+}
+
+/// Overwrites the live vault with a verified snapshot, then re-runs migrations in
+/// case the snapshot predates the current schema.
+pub fn restore(conn: &mut Connection, dir: &Path, name: &str, key: &VaultKey) -> Result<()> {
+    let path = snapshot_path(dir, name)?;
+    check_integrity(&path, key)?;
+
+    let source = storage::open_keyed(&path, key)?;
+    Backup::new(&source, conn)?
+        .run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)
+        .context("Snapshot restore failed")?;
+    storage::migrate(conn)
+}
+
+/// Replaces the vault file itself with a verified snapshot, for a vault too damaged
+/// to open. The replaced file and its WAL are kept beside it with a `.pre-restore`
+/// suffix. Nothing may have the vault open.
+pub fn restore_file(vault: &Path, name: &str, key: &VaultKey) -> Result<()> {
+    let path = snapshot_path(&directory_for(vault), name)?;
+    check_integrity(&path, key)?;
+
+    let staged = with_suffix(vault, ".restoring");
+    let copied = fs::copy(&path, &staged)
+        .context("Failed to stage snapshot")
+        .and_then(|_| check_integrity(&staged, key));
+    if let Err(error) = copied {
+        let _ = fs::remove_file(&staged);
+        return Err(error);
+    }
+
+    let set_aside = with_suffix(vault, ".pre-restore");
+    for suffix in ["", "-wal", "-shm"] {
+        let file = with_suffix(vault, suffix);
+        if file.exists() {
+            fs::rename(&file, with_suffix(&set_aside, suffix))
+                .with_context(|| format!("Failed to set aside {}", file.display()))?;
+        }
+    }
+    fs::rename(&staged, vault).context("Failed to move snapshot into place")
+}
+
+/// Re-encrypts every snapshot after the vault key changes, so they stay
+/// restorable. Snapshots that won't open with `old_key` are left alone.
+pub fn rekey_all(dir: &Path, old_key: &VaultKey, new_key: &VaultKey) -> Result<()> {
+    for (name, _) in snapshot_times(dir)? {
+        match storage::open_keyed(&dir.join(&name), old_key) {
+            Ok(conn) => storage::rekey(&conn, new_key)
+                .with_context(|| format!("Failed to rekey snapshot {name}"))?,
+            Err(error) => log::warn!("Skipping snapshot {name} during rekey: {error}"),
+        }
+    }
+    Ok(())
+}
+
+/// The path of snapshot `name` in `dir`, refusing names outside the naming scheme.
+fn snapshot_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    parse_snapshot_name(name).ok_or_else(|| anyhow!("Unknown snapshot: {name}"))?;
+    let path = dir.join(name);
+    if !path.exists() {
+        bail!("Unknown snapshot: {name}");
+    }
+    Ok(path)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file = path.as_os_str().to_owned();
+    file.push(suffix);
+    PathBuf::from(file)
+}
+
+fn check_integrity(path: &Path, key: &VaultKey) -> Result<()> {
+    let conn = storage::open_keyed(path, key)?;
+    let result: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .context("Integrity check could not run")?;
+    if result != "ok" {
+        bail!("Snapshot failed integrity check: {result}");
+    }
+    Ok(())
+}
+
+fn describe(path: &Path, name: &str, taken_at: PrimitiveDateTime) -> Result<BackupSnapshot> {
+    let size_bytes = fs::metadata(path)
+        .with_context(|| format!("Failed to read snapshot {name}"))?
+        .len();
+    Ok(BackupSnapshot {
+        name: name.to_string(),
+        taken_at: format!(
+            "{} {:02}:{:02}:{:02}",
+            taken_at.date(),
+            taken_at.hour(),
+            taken_at.minute(),
+            taken_at.second()
+        ),
+        size_bytes,
+    })
+}
+
+/// Names and timestamps of the snapshots in `dir`, newest first.
+fn snapshot_times(dir: &Path) -> Result<Vec<(String, PrimitiveDateTime)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir).context("Failed to list backups")? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if let Some(taken_at) = parse_snapshot_name(&name) {
+            snapshots.push((name, taken_at));
+        }
+    }
+    snapshots.sort_by_key(|(_, taken_at)| Reverse(*taken_at));
+    Ok(snapshots)
+}
+
+/// Maps a snapshot date to its retention bucket (day, ISO week or month).
+type BucketOf = fn(Date) -> (i32, u32);
+
+/// Grandfather-father-son retention: the newest snapshot of each of the last
+/// `daily` days, `weekly` ISO weeks and `monthly` months that have one. The newest
+/// snapshot overall is always kept.
+fn retained(snapshots: &[(String, PrimitiveDateTime)], policy: &BackupPolicy) -> HashSet<String> {
+    let mut keep = HashSet::new();
+    keep.extend(snapshots.first().map(|(name, _)| name.clone()));
+
+    let buckets: [(usize, BucketOf); 3] = [
+        (policy.daily, |date| {
+            (date.year(), u32::from(date.ordinal()))
+        }),
+        (policy.weekly, |date| {
+            let (year, week, _) = date.to_iso_week_date();
+            (year, u32::from(week))
+        }),
+        (policy.monthly, |date| {
+            (date.year(), u32::from(u8::from(date.month())))
+        }),
+    ];
+    for (limit, bucket_of) in buckets {
+        let mut seen = HashSet::new();
+        for (name, taken_at) in snapshots {
+            if seen.len() >= limit {
+                break;
+            }
+            if seen.insert(bucket_of(taken_at.date())) {
+                keep.insert(name.clone());
+            }
+        }
+    }
+    keep
+}
+
+fn snapshot_name(at: PrimitiveDateTime) -> String {
+    format!(
+        "{PREFIX}{:04}{:02}{:02}-{:02}{:02}{:02}{SUFFIX}",
+        at.year(),
+        u8::from(at.month()),
+        at.day(),
+        at.hour(),
+        at.minute(),
+        at.second()
+    )
+}
+
+fn parse_snapshot_name(name: &str) -> Option<PrimitiveDateTime> {
+    let stamp = name.strip_prefix(PREFIX)?.strip_suffix(SUFFIX)?;
+    let (date, time) = stamp.split_once('-')?;
+    if date.len() != 8
+        || time.len() != 6
+        || !date.bytes().chain(time.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let date = Date::from_calendar_date(
+        date[..4].parse().ok()?,
+        Month::try_from(date[4..6].parse::<u8>().ok()?).ok()?,
+        date[6..].parse().ok()?,
+    )
+    .ok()?;
+    let time = Time::from_hms(
+        time[..2].parse().ok()?,
+        time[2..4].parse().ok()?,
+        time[4..].parse().ok()?,
+    )
+    .ok()?;
+    Some(PrimitiveDateTime::new(date, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{date, time};
+
+    #[test]
+    fn retains_daily_weekly_and_monthly_snapshots() {
+        let mut snapshots = (0..60)
+            .map(|offset| {
+                let at = PrimitiveDateTime::new(date!(2024 - 01 - 01), time!(02:00))
+                    + time::Duration::days(offset);
+                (snapshot_name(at), at)
+            })
+            .collect::<Vec<_>>();
+        snapshots.reverse();
+        assert_eq!(
+            parse_snapshot_name(&snapshots[0].0),
+            Some(snapshots[0].1),
+            "names should round-trip"
+        );
+
+        let policy = BackupPolicy {
+            daily: 7,
+            weekly: 4,
+            monthly: 3,
+        };
+        let keep = retained(&snapshots, &policy);
+
+        let mut expected = (23..=29)
+            .map(|day| format!("vault-202402{day:02}-020000.db.enc"))
+            .collect::<HashSet<_>>();
+        // Sundays closing the previous three ISO weeks, and the end of January.
+        expected.extend(
+            ["20240218", "20240211", "20240131"].map(|day| format!("vault-{day}-020000.db.enc")),
+        );
+        assert_eq!(keep, expected);
+
+        assert_eq!(parse_snapshot_name("vault-20240230-020000.db.enc"), None);
+        assert_eq!(parse_snapshot_name("../vault.db.enc"), None);
+    }
+
+    #[test]
+    fn restores_a_snapshot_over_a_vault_that_no_longer_opens() {
+        let dir = std::env::temp_dir().join(format!("artha-backups-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let vault = dir.join("vault.db.enc");
+        let key = VaultKey::from([3; 32]);
+
+        let snapshot = {
+            let pool = storage::connection_pool(&vault, &key).unwrap();
+            let conn = pool.get().unwrap();
+            conn.execute(
+                "INSERT INTO settings (key, value) VALUES ('marker', 'kept')",
+                [],
+            )
+            .unwrap();
+            let at = PrimitiveDateTime::new(date!(2024 - 05 - 01), time!(02:00));
+            create(&conn, &directory_for(&vault), &key, at).unwrap()
+        };
+        check_integrity(&directory_for(&vault).join(&snapshot.name), &key).unwrap();
+
+        fs::write(&vault, b"not a database any more").unwrap();
+        assert!(storage::open_keyed(&vault, &key).is_err());
+
+        assert!(restore_file(&vault, "vault-20990101-000000.db.enc", &key).is_err());
+        restore_file(&vault, &snapshot.name, &key).unwrap();
+        let conn = storage::open_keyed(&vault, &key).unwrap();
+        let marker: String = conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'marker'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(marker, "kept");
+        assert!(with_suffix(&vault, ".pre-restore").exists());
+    }
+}
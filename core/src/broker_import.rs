@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use csv::{ReaderBuilder, StringRecord};
+use rusqlite::{params, Connection};
+
+use crate::{
+    csv_import,
+    dto::{
+        AssetClass, Broker, BrokerFunding, BrokerImportSummary, HoldingLot, LotKind, NewHolding,
+    },
+    holdings,
+};
+
+/// Columns whose values are summed into a trade's charges when a tradebook or
+/// contract note carries them. STT is left out: it is not a cost of transfer
+/// under s.48, so it is also taken back out of a "total charges" column.
+const CHARGE_COLUMNS: &[&str] = &[
+    "brokerage",
+    "exchange transaction charges",
+    "exchange txn charges",
+    "clearing charges",
+    "gst",
+    "sebi fees",
+    "sebi turnover fees",
+    "stamp duty",
+];
+
+const STT_COLUMNS: &[&str] = &["stt", "securities transaction tax"];
+
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub trade_ref: String,
+    pub symbol: String,
+    pub isin: Option<String>,
+    pub name: String,
+    pub date: String,
+    pub kind: LotKind,
+    pub quantity: f64,
+    pub price: f64,
+    pub charges: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tradebook {
+    pub broker: Broker,
+    pub trades: Vec<Trade>,
+    /// Rows left out: derivative, currency or commodity trades, and rows whose date,
+    /// side or amounts could not be read.
+    pub skipped: usize,
+}
+
+/// Parses a Zerodha Console or Groww tradebook export, detecting the broker from its
+/// header row. Only equity cash trades are kept.
+pub fn parse_tradebook(bytes: &[u8]) -> Result<Tradebook> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(true)
+        .from_reader(bytes);
+
+    let headers = reader
+        .headers()
+        .context("Tradebook missing header row")?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect::<Vec<_>>();
+
+    let broker = if headers.iter().any(|h| h == "trade_id") {
+        Broker::Zerodha
+    } else if headers
+        .iter()
+        .any(|h| h == "execution date and time" || h == "exchange order id")
+    {
+        Broker::Groww
+    } else {
+        bail!("Unrecognised tradebook format");
+    };
+
+    let column = |candidates: &[&str]| csv_import::find_index(&headers, candidates);
+    let charge_columns = CHARGE_COLUMNS
+        .iter()
+        .filter_map(|name| headers.iter().position(|header| header == name))
+        .collect::<Vec<_>>();
+    let total_charges_idx = headers
+        .iter()
+        .position(|h| h == "total charges" || h == "charges");
+    let stt_idx = csv_import::find_index(&headers, STT_COLUMNS);
+    let segment_idx = column(&["segment"]);
+    let charges =
+        |record: &StringRecord| charges(record, &charge_columns, total_charges_idx, stt_idx);
+
+    let rows = match broker {
+        Broker::Zerodha => {
+            let symbol_idx = column(&["symbol", "tradingsymbol"]).context("Missing symbol")?;
+            let isin_idx = column(&["isin"]);
+            let date_idx = column(&["trade_date", "trade date"]).context("Missing trade date")?;
+            let type_idx = column(&["trade_type", "type"]).context("Missing trade type")?;
+            let qty_idx = column(&["quantity", "qty"]).context("Missing quantity")?;
+            let price_idx = column(&["price"]).context("Missing price")?;
+            let id_idx = column(&["trade_id"]).context("Missing trade id")?;
+
+            reader
+                .records()
+                .filter_map(skip_malformed)
+                .map(|record| {
+                    let symbol = field(&record, Some(symbol_idx))?;
+                    if !is_equity_cash(&record, segment_idx, &symbol) {
+                        return None;
+                    }
+                    let quantity = number(&record, Some(qty_idx))?;
+                    Some(Trade {
+                        trade_ref: format!("zerodha:{}", field(&record, Some(id_idx))?),
+                        isin: field(&record, isin_idx),
+                        name: symbol.clone(),
+                        date: date(&record, date_idx)?,
+                        kind: side(&field(&record, Some(type_idx))?)?,
+                        quantity,
+                        price: number(&record, Some(price_idx))?,
+                        charges: charges(&record),
+                        symbol,
+                    })
+                })
+                .collect::<Vec<_>>()
+        }
+        Broker::Groww => {
+            let name_idx = column(&["stock name", "name"]).context("Missing stock name")?;
+            let symbol_idx = column(&["symbol"]);
+            let isin_idx = column(&["isin"]);
+            let type_idx = column(&["type"]).context("Missing trade type")?;
+            let qty_idx = column(&["quantity"]).context("Missing quantity")?;
+            let value_idx = column(&["value"]).context("Missing value")?;
+            let date_idx =
+                column(&["execution date and time", "date"]).context("Missing execution date")?;
+            let id_idx = column(&["exchange order id", "order id"]).context("Missing order id")?;
+            let status_idx = column(&["order status", "status"]);
+
+            reader
+                .records()
+                .filter_map(skip_malformed)
+                .filter(|record| {
+                    field(record, status_idx)
+                        .map(|status| status.eq_ignore_ascii_case("executed"))
+                        .unwrap_or(true)
+                })
+                .map(|record| {
+                    let name = field(&record, Some(name_idx))?;
+                    let symbol = field(&record, symbol_idx).unwrap_or_else(|| name.clone());
+                    if !is_equity_cash(&record, segment_idx, &symbol) {
+                        return None;
+                    }
+                    let quantity = number(&record, Some(qty_idx))?;
+                    let value = number(&record, Some(value_idx))?;
+                    if quantity <= 0.0 {
+                        return None;
+                    }
+                    Some(Trade {
+                        trade_ref: format!("groww:{}", field(&record, Some(id_idx))?),
+                        symbol,
+                        isin: field(&record, isin_idx),
+                        name,
+                        date: date(&record, date_idx)?,
+                        kind: side(&field(&record, Some(type_idx))?)?,
+                        quantity,
+                        price: value / quantity,
+                        charges: charges(&record),
+                    })
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let skipped = rows.iter().filter(|row| row.is_none()).count();
+    let trades = rows.into_iter().flatten().collect::<Vec<_>>();
+    if skipped > 0 {
+        log::warn!("Tradebook import left out {skipped} rows");
+    }
+
+    if trades.is_empty() {
+        Err(anyhow!("Tradebook produced zero trades"))
+    } else {
+        Ok(Tradebook {
+            broker,
+            trades,
+            skipped,
+        })
+    }
+}
+
+/// Records trades as lots on per-broker holdings (FIFO runs per demat account) and
+/// links the broker funding transfers found in the bank ledger.
+pub fn apply(conn: &Connection, tradebook: &Tradebook) -> Result<BrokerImportSummary> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for tradebook import")?;
+
+    let broker = tradebook.broker;
+    let mut summary = BrokerImportSummary {
+        broker,
+        trades: tradebook.trades.len(),
+        rows_skipped: tradebook.skipped,
+        holdings: 0,
+        lots_added: 0,
+        lots_skipped: 0,
+        fundings_linked: 0,
+    };
+    let mut holding_ids: HashMap<String, i64> = HashMap::new();
+
+    for trade in &tradebook.trades {
+        let symbol = trade.isin.clone().unwrap_or_else(|| trade.symbol.clone());
+        let holding_id = match holding_ids.get(&symbol) {
+            Some(id) => *id,
+            None => {
+                let holding = holdings::upsert_holding(
+                    &tx,
+                    &NewHolding {
+                        name: trade.name.clone(),
+                        asset_class: asset_class(&trade.symbol),
+                        symbol: symbol.clone(),
+                        folio: Some(broker.as_str().to_string()),
+                        equity_oriented: holdings::is_equity_etf(&trade.symbol),
+                    },
+                )?;
+                holding_ids.insert(symbol, holding.id);
+                holding.id
+            }
+        };
+
+        let inserted = holdings::add_lot(
+            &tx,
+            &HoldingLot {
+                id: None,
+                holding_id,
+                kind: trade.kind,
+                date: trade.date.clone(),
+                units: trade.quantity,
+                price: trade.price,
+                charges: trade.charges,
+                external_ref: Some(trade.trade_ref.clone()),
+            },
+        )?;
+
+        match inserted {
+            Some(_) => summary.lots_added += 1,
+            None => summary.lots_skipped += 1,
+        }
+    }
+    summary.holdings = holding_ids.len();
+    summary.fundings_linked = link_fundings(&tx, broker)?;
+
+    tx.commit().context("Commit tradebook import failed")?;
+    Ok(summary)
+}
+
+/// Spreads the day totals of a contract note (`date` plus charge columns) across that
+/// day's lots for `broker` in proportion to traded value. Returns lots updated.
+pub fn apply_contract_note(conn: &Connection, broker: Broker, bytes: &[u8]) -> Result<usize> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(true)
+        .from_reader(bytes);
+
+    let headers = reader
+        .headers()
+        .context("Contract note missing header row")?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect::<Vec<_>>();
+
+    let date_idx = csv_import::find_index(&headers, &["trade date", "date"])
+        .context("Contract note missing date column")?;
+    let charge_columns = CHARGE_COLUMNS
+        .iter()
+        .filter_map(|name| headers.iter().position(|header| header == name))
+        .collect::<Vec<_>>();
+    let total_idx = headers
+        .iter()
+        .position(|h| h == "total charges" || h == "charges");
+    let stt_idx = csv_import::find_index(&headers, STT_COLUMNS);
+    let segment_idx = csv_import::find_index(&headers, &["segment"]);
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for contract note")?;
+    let prefix = format!("{}:%", broker.as_str());
+    let mut updated = 0;
+
+    for record in reader.records().filter_map(skip_malformed) {
+        if !is_equity_cash(&record, segment_idx, "") {
+            continue;
+        }
+        let Some(date) = date(&record, date_idx) else {
+            continue;
+        };
+        let total = charges(&record, &charge_columns, total_idx, stt_idx);
+        if total <= 0.0 {
+            continue;
+        }
+
+        let mut stmt = tx.prepare(
+            "SELECT id, units * price FROM holding_lots WHERE date=?1 AND external_ref LIKE ?2",
+        )?;
+        let lots = stmt
+            .query_map(params![date, prefix], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let turnover: f64 = lots.iter().map(|(_, value)| value).sum();
+        if turnover <= 0.0 {
+            log::warn!("Contract note for {date} has no matching trades");
+            continue;
+        }
+
+        for (id, value) in lots {
+            tx.execute(
+                "UPDATE holding_lots SET charges=?1 WHERE id=?2",
+                params![total * value / turnover, id],
+            )?;
+            updated += 1;
+        }
+    }
+
+    tx.commit().context("Commit contract note failed")?;
+    Ok(updated)
+}
+
+/// Marks bank transactions naming the broker as funding transfers. Returns how many
+/// new links were created.
+pub fn link_fundings(conn: &Connection, broker: Broker) -> Result<usize> {
+    let mut linked = 0;
+    for pattern in broker.ledger_patterns() {
+        linked += conn
+            .execute(
+                r#"
+                INSERT OR IGNORE INTO broker_fundings (transaction_id, broker)
+                SELECT id, ?1 FROM transactions WHERE UPPER(description) LIKE ?2
+                "#,
+                params![broker.as_str(), format!("%{pattern}%")],
+            )
+            .context("Failed to link broker fundings")?;
+    }
+    Ok(linked)
+}
+
+pub fn get_fundings(conn: &Connection) -> Result<Vec<BrokerFunding>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, bf.broker, t.date, t.description, t.amount FROM broker_fundings bf JOIN transactions t ON t.id = bf.transaction_id ORDER BY t.date",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(BrokerFunding {
+                transaction_id: row.get(0)?,
+                broker: Broker::parse(&row.get::<_, String>(1)?).unwrap_or(Broker::Zerodha),
+                date: row.get(2)?,
+                description: row.get(3)?,
+                amount: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch broker fundings")?;
+    Ok(rows)
+}
+
+fn skip_malformed(record: csv::Result<StringRecord>) -> Option<StringRecord> {
+    match record {
+        Ok(record) => Some(record),
+        Err(error) => {
+            log::warn!("Skipping malformed trade row: {error}");
+            None
+        }
+    }
+}
+
+fn field(record: &StringRecord, idx: Option<usize>) -> Option<String> {
+    record
+        .get(idx?)
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn number(record: &StringRecord, idx: Option<usize>) -> Option<f64> {
+    csv_import::parse_amount(record.get(idx?)?).ok()
+}
+
+/// Trade dates may carry a time component (`05-01-2024 10:15 AM`); only the date is kept.
+fn date(record: &StringRecord, idx: usize) -> Option<String> {
+    let raw = field(record, Some(idx))?;
+    let day = raw.split_whitespace().next()?;
+    let date = csv_import::normalize_date(day);
+    if date.is_none() {
+        log::warn!("Skipping trade row with unreadable date {raw:?}");
+    }
+    date
+}
+
+/// Whether a row is an equity cash trade, going by its segment column or, without
+/// one, by the futures/options shape of its symbol (`NIFTY24JAN21500CE`).
+fn is_equity_cash(record: &StringRecord, segment_idx: Option<usize>, symbol: &str) -> bool {
+    if let Some(segment) = field(record, segment_idx) {
+        return matches!(
+            segment.to_lowercase().as_str(),
+            "eq" | "equity" | "cash" | "nse_eq" | "bse_eq"
+        );
+    }
+    let upper = symbol.to_uppercase();
+    let has_digit = upper.chars().any(|c| c.is_ascii_digit());
+    let option_strike = upper
+        .strip_suffix("CE")
+        .or_else(|| upper.strip_suffix("PE"))
+        .is_some_and(|rest| rest.ends_with(|c: char| c.is_ascii_digit()));
+    !(option_strike || (has_digit && upper.ends_with("FUT")))
+}
+
+fn side(raw: &str) -> Option<LotKind> {
+    match raw.trim().to_lowercase().as_str() {
+        "buy" | "b" => Some(LotKind::Buy),
+        "sell" | "s" => Some(LotKind::Sell),
+        _ => None,
+    }
+}
+
+fn charges(
+    record: &StringRecord,
+    columns: &[usize],
+    total_idx: Option<usize>,
+    stt_idx: Option<usize>,
+) -> f64 {
+    if let Some(total) = number(record, total_idx) {
+        let stt = number(record, stt_idx).map_or(0.0, f64::abs);
+        return (total.abs() - stt).max(0.0);
+    }
+    columns
+        .iter()
+        .filter_map(|idx| number(record, Some(*idx)))
+        .map(f64::abs)
+        .sum()
+}
+
+fn asset_class(symbol: &str) -> AssetClass {
+    let upper = symbol.to_uppercase();
+    if upper.ends_with("BEES") || upper.contains("ETF") {
+        AssetClass::Etf
+    } else {
+        AssetClass::Stock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_zerodha_tradebook() {
+        let csv_data = "symbol,isin,trade_date,exchange,segment,series,trade_type,auction,quantity,price,trade_id,order_id,order_execution_time
+INFY,INE009A01021,2024-01-05,NSE,EQ,EQ,buy,false,10.000000,1520.500000,57211,1100000012345,2024-01-05T10:15:02
+NIFTYBEES,INF204KB14I2,2024-02-01,NSE,EQ,EQ,buy,false,100,235.10,57999,1100000019999,2024-02-01T09:20:11
+INFY,INE009A01021,2024-03-11,NSE,EQ,EQ,sell,false,4,1610.00,60001,1100000030000,2024-03-11T14:01:00";
+
+        let Tradebook { broker, trades, .. } =
+            parse_tradebook(csv_data.as_bytes()).expect("Failed to parse");
+        assert_eq!(broker, Broker::Zerodha);
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades[0].trade_ref, "zerodha:57211");
+        assert_eq!(trades[0].date, "2024-01-05");
+        assert_eq!(trades[0].kind, LotKind::Buy);
+        assert_eq!(trades[0].quantity, 10.0);
+        assert_eq!(asset_class(&trades[1].symbol), AssetClass::Etf);
+        assert_eq!(trades[2].kind, LotKind::Sell);
+    }
+
+    #[test]
+    fn test_parse_groww_order_history() {
+        let csv_data = "Stock name,Symbol,ISIN,Type,Quantity,Value,Exchange,Exchange Order Id,Execution date and time,Order status
+TATA MOTORS LIMITED,TATAMOTORS,INE155A01022,BUY,5,\"4,250.00\",NSE,1300000011,05-01-2024 10:15 AM,Executed
+TATA MOTORS LIMITED,TATAMOTORS,INE155A01022,SELL,5,\"4,600.00\",NSE,1300000012,12-02-2024 01:05 PM,Cancelled";
+
+        let Tradebook { broker, trades, .. } =
+            parse_tradebook(csv_data.as_bytes()).expect("Failed to parse");
+        assert_eq!(broker, Broker::Groww);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].date, "2024-01-05");
+        assert_eq!(trades[0].price, 850.0);
+    }
+
+    #[test]
+    fn leaves_out_derivatives_stt_and_unreadable_dates() {
+        let csv_data = "symbol,trade_date,segment,trade_type,quantity,price,trade_id,brokerage,stt,total charges
+INFY,2024-01-05,EQ,buy,10,1520.50,1,20,15.2,40
+NIFTY24JAN21500CE,2024-01-05,FO,buy,50,110.00,2,20,5,30
+INFY,someday,EQ,sell,4,1610.00,3,20,6.4,30";
+
+        let tradebook = parse_tradebook(csv_data.as_bytes()).expect("Failed to parse");
+        assert_eq!(tradebook.trades.len(), 1);
+        assert_eq!(tradebook.skipped, 2);
+        assert!((tradebook.trades[0].charges - 24.8).abs() < 1e-9);
+
+        assert!(!is_equity_cash(&StringRecord::new(), None, "NIFTY24JANFUT"));
+        assert!(is_equity_cash(&StringRecord::new(), None, "RELIANCE"));
+    }
+}
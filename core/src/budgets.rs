@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection};
+use time::Date;
+
+use crate::{
+    dto::{BudgetAlert, BudgetConfig, BudgetProposal, BudgetUsage},
+    periods,
+};
+
+/// Thresholds (percent of cap) applied to budgets without explicit configuration.
+pub const DEFAULT_THRESHOLDS: [f64; 3] = [50.0, 80.0, 100.0];
+
+pub fn get_thresholds(conn: &Connection, category: &str) -> Result<Vec<f64>> {
+    let mut stmt = conn
+        .prepare("SELECT threshold FROM budget_thresholds WHERE category=?1 ORDER BY threshold")?;
+    let thresholds = stmt
+        .query_map(params![category], |row| row.get::<_, f64>(0))?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch budget thresholds")?;
+
+    if thresholds.is_empty() {
+        Ok(DEFAULT_THRESHOLDS.to_vec())
+    } else {
+        Ok(thresholds)
+    }
+}
+
+/// Replaces the alert thresholds of `category`. Run it on a transaction so the
+/// category is never left without its thresholds.
+pub fn set_thresholds(conn: &Connection, category: &str, thresholds: &[f64]) -> Result<()> {
+    if let Some(invalid) = thresholds
+        .iter()
+        .find(|value| !value.is_finite() || **value <= 0.0)
+    {
+        bail!("Invalid budget threshold {invalid} for {category}");
+    }
+
+    conn.execute(
+        "DELETE FROM budget_thresholds WHERE category=?1",
+        params![category],
+    )?;
+    let mut stmt = conn
+        .prepare("INSERT OR IGNORE INTO budget_thresholds (category, threshold) VALUES (?1, ?2)")?;
+    for threshold in thresholds {
+        stmt.execute(params![category, threshold])
+            .with_context(|| format!("Threshold insert failed for {category}"))?;
+    }
+    Ok(())
+}
+
+/// Proposes starting caps from the median monthly spend per category across the
+/// first `months` months that contain debits. Months without spend in a category
+/// count as zero so one-off purchases do not become recurring budgets.
+pub fn propose_budgets(conn: &Connection, months: usize) -> Result<Vec<BudgetProposal>> {
+    let mut month_stmt = conn.prepare(
+        "SELECT DISTINCT substr(date, 1, 7) AS month FROM transactions WHERE amount < 0 AND id NOT IN (SELECT transaction_id FROM card_payments) ORDER BY month LIMIT ?1",
+    )?;
+    let observed = month_stmt
+        .query_map(params![months as i64], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch observed months")?;
+
+    let Some(last) = observed.last() else {
+        return Ok(Vec::new());
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT category, substr(date, 1, 7) AS month, SUM(ABS(amount)) FROM transactions WHERE amount < 0 AND category IS NOT NULL AND substr(date, 1, 7) <= ?1 AND id NOT IN (SELECT transaction_id FROM card_payments) GROUP BY category, month",
+    )?;
+    let rows = stmt
+        .query_map(params![last], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch monthly category spend")?;
+
+    let mut per_category: BTreeMap<String, BTreeMap<String, f64>> = BTreeMap::new();
+    for (category, month, total) in rows {
+        per_category
+            .entry(category)
+            .or_default()
+            .insert(month, total);
+    }
+
+    let proposals = per_category
+        .into_iter()
+        .map(|(category, totals)| {
+            let mut series = observed
+                .iter()
+                .map(|month| totals.get(month).copied().unwrap_or(0.0))
+                .collect::<Vec<_>>();
+            let median_spend = median(&mut series);
+
+            BudgetProposal {
+                category,
+                suggested_cap: median_spend.ceil(),
+                median_spend,
+                months_observed: observed.len() as u32,
+            }
+        })
+        .filter(|proposal| proposal.suggested_cap > 0.0)
+        .collect();
+
+    Ok(proposals)
+}
+
+/// Inserts the given caps, leaving categories that already have a budget untouched.
+/// Returns the number of budgets created.
+pub fn seed_budgets(conn: &Connection, budgets: &[BudgetConfig]) -> Result<usize> {
+    let mut created = 0;
+    let mut stmt = conn.prepare(
+        "INSERT INTO budgets (category, cap) VALUES (?1, ?2) ON CONFLICT(category) DO NOTHING",
+    )?;
+    for budget in budgets {
+        created += stmt
+            .execute(params![budget.category, budget.cap])
+            .with_context(|| format!("Seed insert failed for {}", budget.category))?;
+    }
+    Ok(created)
+}
+
+/// Budget usage for `month` (`YYYY-MM`), with pace measured against `today`.
+pub fn budget_usage(conn: &Connection, month: &str, today: Date) -> Result<Vec<BudgetUsage>> {
+    let like = format!("{month}-%");
+    let elapsed = periods::elapsed_fraction(periods::parse_month(month)?, today);
+
+    let mut stmt = conn.prepare(
+        "SELECT b.category, b.cap, COALESCE(spent.total, 0) FROM budgets b LEFT JOIN (SELECT category, SUM(ABS(amount)) AS total FROM transactions WHERE amount < 0 AND date LIKE ?1 AND id NOT IN (SELECT transaction_id FROM card_payments) GROUP BY category) spent ON spent.category = b.category ORDER BY b.category",
+    )?;
+
+    let rows = stmt
+        .query_map(params![&like], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch budget usage")?;
+
+    rows.into_iter()
+        .map(|(category, cap, spent)| {
+            let thresholds = get_thresholds(conn, &category)?;
+            let percent_used = if cap > 0.0 { spent / cap * 100.0 } else { 0.0 };
+            let expected_spend = cap * elapsed;
+            let projected_spend = if elapsed > 0.0 {
+                spent / elapsed
+            } else {
+                spent
+            };
+
+            Ok(BudgetUsage {
+                category,
+                cap,
+                spent,
+                thresholds,
+                percent_used,
+                expected_spend,
+                projected_spend,
+            })
+        })
+        .collect()
+}
+
+/// Returns thresholds crossed in `month` that have not been reported before and
+/// records them so each crossing is only surfaced once per period.
+pub fn evaluate_alerts(conn: &Connection, month: &str, today: Date) -> Result<Vec<BudgetAlert>> {
+    let usage = budget_usage(conn, month, today)?;
+    let mut alerts = Vec::new();
+
+    for budget in usage {
+        if budget.cap <= 0.0 {
+            continue;
+        }
+
+        for threshold in &budget.thresholds {
+            if budget.percent_used < *threshold {
+                continue;
+            }
+
+            let inserted = conn
+                .execute(
+                    "INSERT OR IGNORE INTO budget_alert_log (category, period, threshold, spent) VALUES (?1, ?2, ?3, ?4)",
+                    params![budget.category, month, threshold, budget.spent],
+                )
+                .context("Failed to record budget alert")?;
+
+            if inserted > 0 {
+                alerts.push(BudgetAlert {
+                    category: budget.category.clone(),
+                    month: month.to_string(),
+                    threshold: *threshold,
+                    percent_used: budget.percent_used,
+                    spent: budget.spent,
+                    cap: budget.cap,
+                });
+            }
+        }
+    }
+
+    Ok(alerts)
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    match values.len() % 2 {
+        0 => (values[mid - 1] + values[mid]) / 2.0,
+        _ => values[mid],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+    use time::macros::date;
+
+    fn vault() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        conn
+    }
+
+    fn spend(conn: &Connection, date: &str, category: &str, amount: f64) {
+        conn.execute(
+            "INSERT INTO transactions (date, description, amount, flow, category)
+             VALUES (?1, 'SPEND', ?2, 'debit', ?3)",
+            params![date, -amount, category],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn alerts_fire_once_per_threshold_and_track_pace() {
+        let conn = vault();
+        conn.execute(
+            "INSERT INTO budgets (category, cap) VALUES ('Food', 1000)",
+            [],
+        )
+        .unwrap();
+        set_thresholds(&conn, "Food", &[50.0, 90.0]).unwrap();
+        spend(&conn, "2025-04-03", "Food", 600.0);
+
+        let today = date!(2025 - 04 - 15);
+        let usage = budget_usage(&conn, "2025-04", today).unwrap();
+        assert_eq!(usage[0].percent_used, 60.0);
+        assert_eq!(usage[0].expected_spend, 500.0);
+        assert_eq!(usage[0].projected_spend, 1200.0);
+
+        let alerts = evaluate_alerts(&conn, "2025-04", today).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].threshold, 50.0);
+        assert!(evaluate_alerts(&conn, "2025-04", today).unwrap().is_empty());
+
+        spend(&conn, "2025-04-10", "Food", 350.0);
+        let alerts = evaluate_alerts(&conn, "2025-04", today).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].threshold, 90.0);
+
+        // Each month starts over.
+        spend(&conn, "2025-05-02", "Food", 700.0);
+        let alerts = evaluate_alerts(&conn, "2025-05", date!(2025 - 05 - 02)).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].month, "2025-05");
+    }
+
+    #[test]
+    fn proposals_use_the_median_of_observed_months() {
+        let conn = vault();
+        spend(&conn, "2025-01-05", "Food", 300.0);
+        spend(&conn, "2025-02-05", "Food", 500.0);
+        spend(&conn, "2025-03-05", "Food", 400.5);
+        // A one-off purchase counts as zero in the other months.
+        spend(&conn, "2025-02-20", "Electronics", 40000.0);
+        // Outside the first three months.
+        spend(&conn, "2025-04-05", "Food", 9000.0);
+
+        let proposals = propose_budgets(&conn, 3).unwrap();
+        assert_eq!(proposals.len(), 1);
+        assert_eq!(proposals[0].category, "Food");
+        assert_eq!(proposals[0].median_spend, 400.5);
+        assert_eq!(proposals[0].suggested_cap, 401.0);
+        assert_eq!(proposals[0].months_observed, 3);
+
+        let created = seed_budgets(
+            &conn,
+            &[BudgetConfig {
+                category: "Food".into(),
+                cap: 401.0,
+            }],
+        )
+        .unwrap();
+        assert_eq!(created, 1);
+        assert_eq!(
+            seed_budgets(
+                &conn,
+                &[BudgetConfig {
+                    category: "Food".into(),
+                    cap: 1.0,
+                }],
+            )
+            .unwrap(),
+            0
+        );
+    }
+}
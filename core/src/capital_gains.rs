@@ -0,0 +1,592 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use time::{Date, Month};
+
+use crate::{
+    dto::{
+        AssetClass, CapitalGainsLine, CapitalGainsReport, GainCategory, GainTerm, Holding,
+        RealizedGain, UnrealizedGain,
+    },
+    holdings::{self, Disposal, OpenLot},
+    periods,
+};
+
+/// Cost Inflation Index by financial year start, from FY2001-02 (base year).
+const COST_INFLATION_INDEX: &[(i32, f64)] = &[
+    (2001, 100.0),
+    (2002, 105.0),
+    (2003, 109.0),
+    (2004, 113.0),
+    (2005, 117.0),
+    (2006, 122.0),
+    (2007, 129.0),
+    (2008, 137.0),
+    (2009, 148.0),
+    (2010, 167.0),
+    (2011, 184.0),
+    (2012, 200.0),
+    (2013, 220.0),
+    (2014, 240.0),
+    (2015, 254.0),
+    (2016, 264.0),
+    (2017, 272.0),
+    (2018, 280.0),
+    (2019, 289.0),
+    (2020, 301.0),
+    (2021, 317.0),
+    (2022, 331.0),
+    (2023, 348.0),
+    (2024, 363.0),
+    (2025, 376.0),
+];
+
+/// How a disposal is taxed: its term, bucket, flat rate and whether cost is indexed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Treatment {
+    term: GainTerm,
+    category: GainCategory,
+    rate: Option<f64>,
+    indexed: bool,
+}
+
+/// Realized gains for sells within `financial_year` and unrealized gains on lots still
+/// open at the earlier of the year end and `today`.
+pub fn build_report(
+    conn: &Connection,
+    financial_year: &str,
+    today: Date,
+) -> Result<CapitalGainsReport> {
+    let (start, end) = periods::parse_financial_year(financial_year)?;
+    let as_of = periods::format_date(end.min(today));
+    let (start_str, end_str) = (periods::format_date(start), periods::format_date(end));
+
+    let mut realized = Vec::new();
+    let mut unrealized = Vec::new();
+
+    for holding in holdings::get_holdings(conn)? {
+        if !is_capital_asset(holding.asset_class) {
+            continue;
+        }
+
+        let lots = holdings::get_lots(conn, holding.id)?;
+        let (_, disposals) = holdings::match_fifo(&lots, &end_str);
+        let fmv_2018 = fair_value_2018(conn, &holding)?;
+
+        for disposal in disposals
+            .iter()
+            .filter(|disposal| disposal.sell_date >= start_str)
+        {
+            realized.push(realize(&holding, disposal, fmv_2018)?);
+        }
+
+        let (open, _) = holdings::match_fifo(&lots, &as_of);
+        if open.is_empty() {
+            continue;
+        }
+        let price = holdings::price_on(conn, holding.id, &lots, &as_of)?;
+        unrealized.push(unrealize(
+            &holding,
+            &open,
+            price.map(|point| point.price),
+            &as_of,
+        )?);
+    }
+
+    realized.sort_by(|a, b| a.sell_date.cmp(&b.sell_date));
+    let schedule = schedule(&realized, start.year());
+
+    Ok(CapitalGainsReport {
+        financial_year: periods::financial_year_label(start),
+        start: start_str,
+        end: end_str,
+        total_realized: realized.iter().map(|gain| gain.gain).sum(),
+        total_unrealized: unrealized
+            .iter()
+            .map(|gain| gain.short_term_gain + gain.long_term_gain)
+            .sum(),
+        realized,
+        schedule,
+        unrealized,
+    })
+}
+
+/// One CSV row per realized disposal followed by the schedule lines.
+pub fn report_csv(report: &CapitalGainsReport) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "financial_year",
+        "category",
+        "holding",
+        "buy_date",
+        "sell_date",
+        "units",
+        "sale_value",
+        "cost_used",
+        "gain",
+    ])?;
+
+    for gain in &report.realized {
+        writer.write_record([
+            report.financial_year.as_str(),
+            gain.category.as_str(),
+            gain.holding_name.as_str(),
+            gain.buy_date.as_str(),
+            gain.sell_date.as_str(),
+            &format!("{:.4}", gain.units),
+            &format!("{:.2}", gain.sale_value),
+            &format!("{:.2}", gain.cost_used),
+            &format!("{:.2}", gain.gain),
+        ])?;
+    }
+    for line in &report.schedule {
+        writer.write_record([
+            report.financial_year.as_str(),
+            line.category.as_str(),
+            &format!("{} (taxable)", line.label),
+            "",
+            "",
+            "",
+            &format!("{:.2}", line.sale_value),
+            &format!("{:.2}", line.cost),
+            &format!("{:.2}", line.taxable),
+        ])?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|error| anyhow::anyhow!("Failed to flush capital gains CSV: {error}"))?;
+    String::from_utf8(bytes).context("Capital gains CSV was not valid UTF-8")
+}
+
+/// Fixed deposits, PPF and EPF earn interest rather than capital gains.
+fn is_capital_asset(asset_class: AssetClass) -> bool {
+    !matches!(
+        asset_class,
+        AssetClass::FixedDeposit | AssetClass::Ppf | AssetClass::Epf
+    )
+}
+
+fn is_equity(asset_class: AssetClass) -> bool {
+    matches!(asset_class, AssetClass::EquityFund | AssetClass::Stock)
+}
+
+/// The class a holding is taxed as. ETFs follow their underlying: equity ETFs like
+/// stocks, the rest (gold, debt, foreign indices) like debt funds. Equity-oriented
+/// hybrid funds are taxed as equity.
+fn taxed_as(holding: &Holding) -> AssetClass {
+    match holding.asset_class {
+        AssetClass::Etf if holding.equity_oriented => AssetClass::Stock,
+        AssetClass::HybridFund if holding.equity_oriented => AssetClass::Stock,
+        AssetClass::Etf => AssetClass::DebtFund,
+        asset_class => asset_class,
+    }
+}
+
+fn date(year: i32, month: Month, day: u8) -> Date {
+    Date::from_calendar_date(year, month, day).unwrap_or(Date::MIN)
+}
+
+/// Held for more than `months` months when sold on `sell`.
+fn held_over(buy: Date, sell: Date, months: i32) -> bool {
+    sell > periods::add_months(buy, months)
+}
+
+/// Applies the holding-period thresholds and rates in force on the sale date. The
+/// Finance (No. 2) Act 2024 changed rates and periods for transfers from 23-Jul-2024;
+/// debt funds bought from 1-Apr-2023 are always taxed at slab rates (section 50AA).
+fn treatment(asset_class: AssetClass, buy: Date, sell: Date) -> Treatment {
+    let post_budget_2024 = sell >= date(2024, Month::July, 23);
+
+    if is_equity(asset_class) {
+        return if held_over(buy, sell, 12) {
+            let rate = if post_budget_2024 {
+                12.5
+            } else if sell >= date(2018, Month::April, 1) {
+                10.0
+            } else {
+                0.0
+            };
+            Treatment {
+                term: GainTerm::LongTerm,
+                category: GainCategory::EquityLtcg,
+                rate: Some(rate),
+                indexed: false,
+            }
+        } else {
+            Treatment {
+                term: GainTerm::ShortTerm,
+                category: GainCategory::EquityStcg,
+                rate: Some(if post_budget_2024 { 20.0 } else { 15.0 }),
+                indexed: false,
+            }
+        };
+    }
+
+    let slab = Treatment {
+        term: GainTerm::ShortTerm,
+        category: GainCategory::Slab,
+        rate: None,
+        indexed: false,
+    };
+
+    if asset_class == AssetClass::DebtFund && buy >= date(2023, Month::April, 1) {
+        return slab;
+    }
+
+    if post_budget_2024 {
+        if held_over(buy, sell, 24) {
+            Treatment {
+                term: GainTerm::LongTerm,
+                category: GainCategory::Ltcg,
+                rate: Some(12.5),
+                indexed: false,
+            }
+        } else {
+            slab
+        }
+    } else if held_over(buy, sell, 36) {
+        Treatment {
+            term: GainTerm::LongTerm,
+            category: GainCategory::LtcgIndexed,
+            rate: Some(20.0),
+            indexed: true,
+        }
+    } else {
+        slab
+    }
+}
+
+/// CII for the financial year containing `date`; purchases before FY2001-02 use the
+/// base year and years beyond the table use its latest entry.
+fn cost_inflation_index(date: Date) -> f64 {
+    let year = periods::financial_year_start(date).year();
+    COST_INFLATION_INDEX
+        .iter()
+        .rev()
+        .find(|(start, _)| *start <= year)
+        .map(|(_, index)| *index)
+        .unwrap_or(COST_INFLATION_INDEX[0].1)
+}
+
+/// Price on or just before 31-Jan-2018 from the holding's price history, used as the
+/// fair market value for equity grandfathering.
+fn fair_value_2018(conn: &Connection, holding: &Holding) -> Result<Option<f64>> {
+    if !is_equity(taxed_as(holding)) {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT price FROM holding_prices WHERE holding_id=?1 AND date >= '2018-01-01' AND date <= '2018-01-31' ORDER BY date DESC LIMIT 1",
+        params![holding.id],
+        |row| row.get::<_, f64>(0),
+    )
+    .optional()
+    .context("Failed to look up 31-Jan-2018 price")
+}
+
+fn realize(holding: &Holding, disposal: &Disposal, fmv_2018: Option<f64>) -> Result<RealizedGain> {
+    let buy = periods::parse_date(&disposal.buy_date)?;
+    let sell = periods::parse_date(&disposal.sell_date)?;
+    let treatment = treatment(taxed_as(holding), buy, sell);
+
+    let sale_value = disposal.units * disposal.unit_proceeds;
+    let cost = disposal.units * disposal.unit_cost;
+
+    // Section 112A: cost of equity acquired before 1-Feb-2018 is the higher of actual
+    // cost and the lower of the 31-Jan-2018 FMV and the sale value.
+    let fmv_2018 = fmv_2018
+        .filter(|_| {
+            treatment.category == GainCategory::EquityLtcg && buy < date(2018, Month::February, 1)
+        })
+        .map(|fmv| fmv * disposal.units);
+    let indexed_cost = treatment
+        .indexed
+        .then(|| cost * cost_inflation_index(sell) / cost_inflation_index(buy));
+
+    let cost_used = match (fmv_2018, indexed_cost) {
+        (Some(fmv), _) => cost.max(fmv.min(sale_value)),
+        (None, Some(indexed)) => indexed,
+        (None, None) => cost,
+    };
+
+    Ok(RealizedGain {
+        holding_id: holding.id,
+        holding_name: holding.name.clone(),
+        asset_class: holding.asset_class,
+        symbol: holding.symbol.clone(),
+        buy_date: disposal.buy_date.clone(),
+        sell_date: disposal.sell_date.clone(),
+        units: disposal.units,
+        sale_value,
+        cost,
+        fmv_2018,
+        indexed_cost,
+        cost_used,
+        gain: sale_value - cost_used,
+        term: treatment.term,
+        category: treatment.category,
+        rate: treatment.rate,
+    })
+}
+
+fn unrealize(
+    holding: &Holding,
+    open: &[OpenLot],
+    price: Option<f64>,
+    as_of: &str,
+) -> Result<UnrealizedGain> {
+    let as_of_date = periods::parse_date(as_of)?;
+    let mut gain = UnrealizedGain {
+        holding_id: holding.id,
+        holding_name: holding.name.clone(),
+        asset_class: holding.asset_class,
+        units: 0.0,
+        cost: 0.0,
+        market_value: 0.0,
+        short_term_gain: 0.0,
+        long_term_gain: 0.0,
+    };
+
+    for lot in open {
+        let cost = lot.units * lot.unit_cost;
+        let value = lot.units * price.unwrap_or(lot.unit_cost);
+        gain.units += lot.units;
+        gain.cost += cost;
+        gain.market_value += value;
+
+        let buy = periods::parse_date(&lot.date)?;
+        match treatment(taxed_as(holding), buy, as_of_date).term {
+            GainTerm::ShortTerm => gain.short_term_gain += value - cost,
+            GainTerm::LongTerm => gain.long_term_gain += value - cost,
+        }
+    }
+
+    Ok(gain)
+}
+
+/// Annual section 112A exemption for the financial year starting in `year`.
+fn equity_ltcg_exemption(year: i32) -> f64 {
+    if year >= 2024 {
+        125_000.0
+    } else {
+        100_000.0
+    }
+}
+
+/// Groups realized gains by category and rate. The 112A exemption is applied to the
+/// highest-rate equity LTCG line first; losses are netted within a line but not set
+/// off across lines.
+fn schedule(realized: &[RealizedGain], year: i32) -> Vec<CapitalGainsLine> {
+    let mut lines: Vec<CapitalGainsLine> = Vec::new();
+
+    for gain in realized {
+        let line = match lines
+            .iter_mut()
+            .position(|line| line.category == gain.category && line.rate == gain.rate)
+        {
+            Some(index) => &mut lines[index],
+            None => {
+                lines.push(CapitalGainsLine {
+                    category: gain.category,
+                    label: gain.category.label().to_string(),
+                    term: gain.term,
+                    rate: gain.rate,
+                    sale_value: 0.0,
+                    cost: 0.0,
+                    gain: 0.0,
+                    exemption: 0.0,
+                    taxable: 0.0,
+                    estimated_tax: None,
+                });
+                lines.last_mut().expect("line just pushed")
+            }
+        };
+        line.sale_value += gain.sale_value;
+        line.cost += gain.cost_used;
+        line.gain += gain.gain;
+    }
+
+    lines.sort_by(|a, b| {
+        a.category
+            .as_str()
+            .cmp(b.category.as_str())
+            .then(b.rate.unwrap_or(0.0).total_cmp(&a.rate.unwrap_or(0.0)))
+    });
+
+    let mut exemption_left = if year >= 2018 {
+        equity_ltcg_exemption(year)
+    } else {
+        0.0
+    };
+    for line in &mut lines {
+        if line.category == GainCategory::EquityLtcg && line.gain > 0.0 {
+            line.exemption = line.gain.min(exemption_left);
+            exemption_left -= line.exemption;
+        }
+        line.taxable = (line.gain - line.exemption).max(0.0);
+        line.estimated_tax = line.rate.map(|rate| line.taxable * rate / 100.0);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::{HoldingLot, LotKind};
+
+    fn lot(kind: LotKind, date: &str, units: f64, price: f64) -> HoldingLot {
+        HoldingLot {
+            id: None,
+            holding_id: 1,
+            kind,
+            date: date.into(),
+            units,
+            price,
+            charges: 0.0,
+            external_ref: None,
+        }
+    }
+
+    fn holding(asset_class: AssetClass) -> Holding {
+        Holding {
+            id: 1,
+            name: "Test".into(),
+            asset_class,
+            symbol: "INE000000000".into(),
+            folio: None,
+            equity_oriented: false,
+        }
+    }
+
+    #[test]
+    fn equity_is_long_term_after_twelve_months_and_grandfathered() {
+        let lots = vec![
+            lot(LotKind::Buy, "2017-06-01", 10.0, 100.0),
+            lot(LotKind::Buy, "2023-09-01", 10.0, 300.0),
+            lot(LotKind::Sell, "2024-03-15", 15.0, 320.0),
+        ];
+        let (open, disposals) = holdings::match_fifo(&lots, "2024-03-31");
+        assert_eq!(open.len(), 1);
+        assert_eq!(disposals.len(), 2);
+
+        let equity = holding(AssetClass::Stock);
+        let first = realize(&equity, &disposals[0], Some(150.0)).unwrap();
+        assert_eq!(first.term, GainTerm::LongTerm);
+        assert_eq!(first.rate, Some(10.0));
+        // FMV 150 beats the actual cost of 100.
+        assert!((first.cost_used - 1500.0).abs() < 1e-6);
+        assert!((first.gain - 1700.0).abs() < 1e-6);
+
+        let second = realize(&equity, &disposals[1], Some(150.0)).unwrap();
+        assert_eq!(second.category, GainCategory::EquityStcg);
+        assert_eq!(second.fmv_2018, None);
+        assert!((second.gain - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn debt_funds_follow_purchase_and_sale_date_rules() {
+        let indexed = treatment(
+            AssetClass::DebtFund,
+            date(2019, Month::May, 10),
+            date(2023, Month::June, 1),
+        );
+        assert_eq!(indexed.category, GainCategory::LtcgIndexed);
+        assert!(indexed.indexed);
+        assert!((cost_inflation_index(date(2019, Month::May, 10)) - 289.0).abs() < 1e-6);
+
+        let after_budget = treatment(
+            AssetClass::DebtFund,
+            date(2021, Month::May, 10),
+            date(2024, Month::August, 1),
+        );
+        assert_eq!(after_budget.category, GainCategory::Ltcg);
+        assert_eq!(after_budget.rate, Some(12.5));
+
+        let specified = treatment(
+            AssetClass::DebtFund,
+            date(2023, Month::April, 1),
+            date(2026, Month::June, 1),
+        );
+        assert_eq!(specified.category, GainCategory::Slab);
+    }
+
+    #[test]
+    fn etfs_are_taxed_by_their_underlying() {
+        let mut index_etf = holding(AssetClass::Etf);
+        index_etf.equity_oriented = holdings::is_equity_etf("NIFTYBEES");
+        assert_eq!(taxed_as(&index_etf), AssetClass::Stock);
+
+        let mut gold_etf = holding(AssetClass::Etf);
+        gold_etf.equity_oriented = holdings::is_equity_etf("GOLDBEES");
+        assert_eq!(taxed_as(&gold_etf), AssetClass::DebtFund);
+
+        let sold = holdings::match_fifo(
+            &[
+                lot(LotKind::Buy, "2023-06-01", 10.0, 50.0),
+                lot(LotKind::Sell, "2024-09-01", 10.0, 60.0),
+            ],
+            "2024-12-31",
+        )
+        .1;
+        let gold = realize(&gold_etf, &sold[0], None).unwrap();
+        assert_eq!(gold.category, GainCategory::Slab);
+        let index = realize(&index_etf, &sold[0], None).unwrap();
+        assert_eq!(index.category, GainCategory::EquityLtcg);
+    }
+
+    #[test]
+    fn equity_oriented_hybrids_are_taxed_as_equity() {
+        let mut balanced = holding(AssetClass::HybridFund);
+        balanced.equity_oriented =
+            holdings::is_equity_hybrid("HDFC Balanced Advantage Fund - Growth");
+        let mut conservative = holding(AssetClass::HybridFund);
+        conservative.equity_oriented =
+            holdings::is_equity_hybrid("SBI Conservative Hybrid Fund - Growth");
+        assert_eq!(taxed_as(&conservative), AssetClass::HybridFund);
+
+        let sold = holdings::match_fifo(
+            &[
+                lot(LotKind::Buy, "2023-06-01", 10.0, 50.0),
+                lot(LotKind::Buy, "2024-06-01", 10.0, 55.0),
+                lot(LotKind::Sell, "2024-09-01", 20.0, 60.0),
+            ],
+            "2024-12-31",
+        )
+        .1;
+        let long = realize(&balanced, &sold[0], None).unwrap();
+        assert_eq!(long.category, GainCategory::EquityLtcg);
+        let short = realize(&balanced, &sold[1], None).unwrap();
+        assert_eq!(short.category, GainCategory::EquityStcg);
+        let debt_like = realize(&conservative, &sold[0], None).unwrap();
+        assert_eq!(debt_like.category, GainCategory::Slab);
+    }
+
+    #[test]
+    fn equity_exemption_applies_once_per_year() {
+        let gain = |rate: f64, amount: f64| RealizedGain {
+            holding_id: 1,
+            holding_name: "Test".into(),
+            asset_class: AssetClass::EquityFund,
+            symbol: "X".into(),
+            buy_date: "2020-01-01".into(),
+            sell_date: "2024-09-01".into(),
+            units: 1.0,
+            sale_value: amount,
+            cost: 0.0,
+            fmv_2018: None,
+            indexed_cost: None,
+            cost_used: 0.0,
+            gain: amount,
+            term: GainTerm::LongTerm,
+            category: GainCategory::EquityLtcg,
+            rate: Some(rate),
+        };
+
+        let lines = schedule(&[gain(10.0, 50_000.0), gain(12.5, 100_000.0)], 2024);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].rate, Some(12.5));
+        assert!((lines[0].exemption - 100_000.0).abs() < 1e-6);
+        assert!((lines[1].exemption - 25_000.0).abs() < 1e-6);
+        assert!((lines[1].taxable - 25_000.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,450 @@
+use anyhow::{anyhow, Context, Result};
+use rusqlite::Connection;
+use time::{macros::format_description, Date};
+
+use crate::{
+    csv_import,
+    dto::{AssetClass, CasImportSummary, CasTxKind, HoldingLot, LotKind, NewHolding},
+    holdings, periods,
+};
+
+#[derive(Debug, Clone)]
+pub struct CasScheme {
+    pub folio: String,
+    pub name: String,
+    pub isin: String,
+    pub asset_class: AssetClass,
+    pub transactions: Vec<CasTransaction>,
+    pub closing_nav: Option<(String, f64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CasTransaction {
+    pub date: String,
+    pub kind: CasTxKind,
+    pub amount: f64,
+    pub units: Option<f64>,
+    pub nav: Option<f64>,
+}
+
+/// Parses the text export of a CAMS/KFintech consolidated account statement.
+pub fn parse_cas(text: &str) -> Result<Vec<CasScheme>> {
+    let mut schemes: Vec<CasScheme> = Vec::new();
+    let mut folio = String::new();
+
+    for raw in text.lines() {
+        let line = raw.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(rest) = strip_prefix_ignore_case(line, "folio no:") {
+            folio = rest
+                .split("PAN")
+                .next()
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .trim()
+                .to_string();
+            continue;
+        }
+
+        if let Some(isin_at) = line.find("ISIN:") {
+            let name = scheme_name(&line[..isin_at]);
+            let isin = line[isin_at + 5..]
+                .trim_start()
+                .split(|c: char| c.is_whitespace() || c == '(')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+
+            schemes.push(CasScheme {
+                folio: folio.clone(),
+                asset_class: guess_asset_class(&name),
+                name,
+                isin,
+                transactions: Vec::new(),
+                closing_nav: None,
+            });
+            continue;
+        }
+
+        let Some(scheme) = schemes.last_mut() else {
+            continue;
+        };
+
+        if line.to_lowercase().starts_with("closing unit balance") {
+            scheme.closing_nav = parse_closing_nav(line);
+            continue;
+        }
+
+        if let Some(transaction) = parse_transaction(line) {
+            scheme.transactions.push(transaction);
+        }
+    }
+
+    if schemes.is_empty() {
+        Err(anyhow!("CAS text contained no schemes"))
+    } else {
+        Ok(schemes)
+    }
+}
+
+/// Creates or updates holdings and lots from parsed schemes. Stamp duty is folded
+/// into the charges of the matching purchase on the same day; dividend payouts, STT
+/// and TDS are recorded as cash flows of the holding instead.
+pub fn apply(conn: &Connection, schemes: &[CasScheme]) -> Result<CasImportSummary> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for CAS import")?;
+    let mut summary = CasImportSummary::default();
+
+    for scheme in schemes {
+        let holding = holdings::upsert_holding(
+            &tx,
+            &NewHolding {
+                name: scheme.name.clone(),
+                asset_class: scheme.asset_class,
+                symbol: scheme.isin.clone(),
+                folio: Some(scheme.folio.clone()).filter(|folio| !folio.is_empty()),
+                equity_oriented: match scheme.asset_class {
+                    AssetClass::HybridFund => holdings::is_equity_hybrid(&scheme.name),
+                    _ => holdings::is_equity_etf(&scheme.name),
+                },
+            },
+        )?;
+        summary.schemes += 1;
+
+        for (index, entry) in scheme.transactions.iter().enumerate() {
+            let lot_kind = match entry.kind {
+                CasTxKind::Purchase
+                | CasTxKind::Sip
+                | CasTxKind::SwitchIn
+                | CasTxKind::DividendReinvest => LotKind::Buy,
+                CasTxKind::Redemption | CasTxKind::SwitchOut => LotKind::Sell,
+                CasTxKind::Dividend | CasTxKind::Stt | CasTxKind::Tds => {
+                    let amount = entry.amount.abs();
+                    let external_ref = format!(
+                        "cas:{}:{}:{}:{}:{:.2}",
+                        scheme.folio,
+                        scheme.isin,
+                        entry.date,
+                        entry.kind.as_str(),
+                        amount
+                    );
+                    let inserted = holdings::add_cash_flow(
+                        &tx,
+                        holding.id,
+                        entry.kind.as_str(),
+                        &entry.date,
+                        amount,
+                        &external_ref,
+                    )?;
+                    if !inserted {
+                        continue;
+                    }
+                    match entry.kind {
+                        CasTxKind::Dividend => summary.dividends += amount,
+                        CasTxKind::Stt => summary.stt += amount,
+                        _ => summary.tds += amount,
+                    }
+                    continue;
+                }
+                CasTxKind::StampDuty => continue,
+            };
+
+            let (Some(units), Some(nav)) = (entry.units, entry.nav) else {
+                continue;
+            };
+
+            let charges = scheme
+                .transactions
+                .iter()
+                .skip(index + 1)
+                .take_while(|next| {
+                    next.date == entry.date
+                        && matches!(
+                            next.kind,
+                            CasTxKind::StampDuty | CasTxKind::Stt | CasTxKind::Tds
+                        )
+                })
+                .filter(|next| next.kind == CasTxKind::StampDuty)
+                .map(|next| next.amount.abs())
+                .sum();
+
+            let external_ref = format!(
+                "cas:{}:{}:{}:{}:{:.4}",
+                scheme.folio,
+                scheme.isin,
+                entry.date,
+                entry.kind.as_str(),
+                units.abs()
+            );
+
+            let inserted = holdings::add_lot(
+                &tx,
+                &HoldingLot {
+                    id: None,
+                    holding_id: holding.id,
+                    kind: lot_kind,
+                    date: entry.date.clone(),
+                    units: units.abs(),
+                    price: nav,
+                    charges,
+                    external_ref: Some(external_ref),
+                },
+            )?;
+
+            match inserted {
+                Some(_) => summary.lots_added += 1,
+                None => summary.lots_skipped += 1,
+            }
+        }
+
+        if let Some((date, nav)) = &scheme.closing_nav {
+            holdings::set_price(&tx, holding.id, date, *nav)?;
+            summary.prices += 1;
+        }
+    }
+
+    tx.commit().context("Commit CAS import failed")?;
+    Ok(summary)
+}
+
+fn strip_prefix_ignore_case<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = line.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| line[prefix.len()..].trim())
+}
+
+/// Drops the registrar's scheme code prefix (e.g. `B92Z-`) and trailing separators.
+fn scheme_name(raw: &str) -> String {
+    let trimmed = raw.trim().trim_end_matches('-').trim();
+    match trimmed.split_once('-') {
+        Some((code, rest))
+            if !code.contains(' ')
+                && code.len() <= 8
+                && code.chars().any(|c| c.is_ascii_digit()) =>
+        {
+            rest.trim().to_string()
+        }
+        _ => trimmed.to_string(),
+    }
+}
+
+fn guess_asset_class(name: &str) -> AssetClass {
+    let lower = name.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
+
+    if has(&["gold"]) {
+        AssetClass::Gold
+    } else if has(&["etf"]) {
+        AssetClass::Etf
+    } else if has(&[
+        "liquid",
+        "debt",
+        "bond",
+        "gilt",
+        "money market",
+        "overnight",
+        "duration",
+        "credit risk",
+        "corporate",
+        "banking and psu",
+        "floater",
+    ]) {
+        AssetClass::DebtFund
+    } else if has(&["hybrid", "balanced", "asset allocation", "multi asset"]) {
+        AssetClass::HybridFund
+    } else {
+        AssetClass::EquityFund
+    }
+}
+
+fn parse_cas_date(raw: &str) -> Option<Date> {
+    let format = format_description!("[day]-[month repr:short case_sensitive:false]-[year]");
+    Date::parse(raw, &format).ok()
+}
+
+fn parse_transaction(line: &str) -> Option<CasTransaction> {
+    let (raw_date, rest) = line.split_once(char::is_whitespace)?;
+    let date = parse_cas_date(raw_date)?;
+
+    let tokens = rest.split_whitespace().collect::<Vec<_>>();
+    let numeric_tail = tokens
+        .iter()
+        .rev()
+        .take_while(|token| parse_number(token).is_some())
+        .count();
+    let (words, numbers) = tokens.split_at(tokens.len() - numeric_tail);
+    let numbers = numbers
+        .iter()
+        .filter_map(|token| parse_number(token))
+        .collect::<Vec<_>>();
+
+    let amount = *numbers.first()?;
+    let (units, nav) = match numbers.len() {
+        n if n >= 3 => (Some(numbers[1]), Some(numbers[2])),
+        _ => (None, None),
+    };
+
+    let kind = classify(&words.join(" "), units);
+
+    Some(CasTransaction {
+        date: periods::format_date(date),
+        kind,
+        amount,
+        units,
+        nav,
+    })
+}
+
+fn classify(description: &str, units: Option<f64>) -> CasTxKind {
+    let lower = description.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
+
+    if has(&["stamp duty"]) {
+        CasTxKind::StampDuty
+    } else if has(&["stt", "securities transaction tax"]) {
+        CasTxKind::Stt
+    } else if has(&["tds", "tax deducted"]) {
+        CasTxKind::Tds
+    } else if has(&["switch-out", "switch out", "switch over out"]) {
+        CasTxKind::SwitchOut
+    } else if has(&["switch-in", "switch in", "switch over in"]) {
+        CasTxKind::SwitchIn
+    } else if has(&["idcw", "dividend"]) {
+        match units {
+            Some(_) => CasTxKind::DividendReinvest,
+            None => CasTxKind::Dividend,
+        }
+    } else if has(&["redemption", "redeem", "withdrawal", "swp"]) {
+        CasTxKind::Redemption
+    } else if has(&["sip", "systematic"]) {
+        CasTxKind::Sip
+    } else if units.is_some_and(|units| units < 0.0) {
+        CasTxKind::Redemption
+    } else {
+        CasTxKind::Purchase
+    }
+}
+
+fn parse_closing_nav(line: &str) -> Option<(String, f64)> {
+    let lower = line.to_lowercase();
+    let at = lower.find("nav on ")?;
+    let rest = &line[at + 7..];
+    let (raw_date, rest) = rest.split_once(':')?;
+    let date = parse_cas_date(raw_date.trim())?;
+    let nav = rest.split_whitespace().find_map(parse_number)?;
+    Some((periods::format_date(date), nav))
+}
+
+fn parse_number(token: &str) -> Option<f64> {
+    let token = token.trim_start_matches("INR").trim();
+    if token.is_empty()
+        || !token
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, ',' | '.' | '(' | ')' | '-'))
+        || !token.chars().any(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    csv_import::parse_amount(token).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "
+Consolidated Account Statement
+Folio No: 1234567 / 89     PAN: ABCDE1234F
+B205RG-Axis Long Term Equity Fund - Direct Growth - ISIN: INF846K01EW2(Advisor: DIRECT) Registrar : KFINTECH
+Opening Unit Balance: 0.000
+05-Jan-2023 Purchase - Via Internet 5,000.00 68.514 72.9766 68.514
+05-Jan-2023 *** Stamp Duty *** 0.25
+06-Feb-2023 SIP Purchase - Instalment 2/12 5,000.00 69.010 72.4533 137.524
+10-Mar-2023 Redemption (2,000.00) (26.000) 76.9231 111.524
+15-Mar-2023 IDCW Paid 150.00
+Closing Unit Balance: 111.524 NAV on 31-Mar-2023: INR 78.1200 Valuation on 31-Mar-2023: INR 8,712.25
+Folio No: 998877     PAN: ABCDE1234F
+P8013-Parag Parikh Liquid Fund - Direct Growth - ISIN: INF879O01100 Registrar : CAMS
+01-Apr-2023 Purchase 10,000.00 8.012 1248.1278 8.012
+";
+
+    #[test]
+    fn test_parse_cas_text() {
+        let schemes = parse_cas(SAMPLE).expect("Failed to parse CAS");
+        assert_eq!(schemes.len(), 2);
+
+        let elss = &schemes[0];
+        assert_eq!(elss.folio, "1234567 / 89");
+        assert_eq!(elss.isin, "INF846K01EW2");
+        assert_eq!(elss.name, "Axis Long Term Equity Fund - Direct Growth");
+        assert_eq!(elss.asset_class, AssetClass::EquityFund);
+        assert_eq!(elss.transactions.len(), 5);
+
+        let purchase = &elss.transactions[0];
+        assert_eq!(purchase.date, "2023-01-05");
+        assert_eq!(purchase.kind, CasTxKind::Purchase);
+        assert_eq!(purchase.units, Some(68.514));
+        assert_eq!(purchase.nav, Some(72.9766));
+
+        assert_eq!(elss.transactions[1].kind, CasTxKind::StampDuty);
+        assert_eq!(elss.transactions[2].kind, CasTxKind::Sip);
+
+        let redemption = &elss.transactions[3];
+        assert_eq!(redemption.kind, CasTxKind::Redemption);
+        assert_eq!(redemption.units, Some(-26.0));
+
+        assert_eq!(elss.transactions[4].kind, CasTxKind::Dividend);
+        assert_eq!(elss.closing_nav, Some(("2023-03-31".to_string(), 78.12)));
+
+        assert_eq!(schemes[1].asset_class, AssetClass::DebtFund);
+        assert_eq!(schemes[1].folio, "998877");
+    }
+
+    fn cash_flows(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM holding_cash_flows", [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn records_dividends_and_withheld_tax_apart_from_lots() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::migrate(&conn).unwrap();
+        let text = SAMPLE.replace(
+            "15-Mar-2023 IDCW Paid 150.00",
+            "10-Mar-2023 *** STT Paid *** 0.02\n15-Mar-2023 IDCW Paid 150.00\n15-Mar-2023 TDS on IDCW 15.00",
+        );
+        let schemes = parse_cas(&text).unwrap();
+        assert_eq!(schemes[0].transactions[4].kind, CasTxKind::Stt);
+        assert_eq!(schemes[0].transactions[6].kind, CasTxKind::Tds);
+
+        let summary = apply(&conn, &schemes).unwrap();
+        assert_eq!(summary.lots_added, 4);
+        assert_eq!(summary.dividends, 150.0);
+        assert_eq!(summary.stt, 0.02);
+        assert_eq!(summary.tds, 15.0);
+
+        // Only stamp duty becomes a lot charge; STT stays a separate cash flow.
+        let charges: f64 = conn
+            .query_row(
+                "SELECT SUM(charges) FROM holding_lots WHERE kind = 'sell'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(charges, 0.0);
+        assert_eq!(cash_flows(&conn), 3);
+
+        let again = apply(&conn, &schemes).unwrap();
+        assert_eq!(again.lots_skipped, 4);
+        assert_eq!((again.dividends, again.stt, again.tds), (0.0, 0.0, 0.0));
+        assert_eq!(cash_flows(&conn), 3);
+    }
+}
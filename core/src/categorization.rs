@@ -5,6 +5,8 @@ use parking_lot::RwLock;
 use std::sync::Arc;
 use rusqlite::{params, Connection};
 
+use crate::dto::{LearnedToken, TokenProvenance};
+
 #[derive(Default)]
 pub struct CategorizationMemory {
     tokens: HashMap<String, String>,
@@ -30,35 +32,150 @@ impl CategorizationMemory {
             .find_map(|token| self.tokens.get(&token).cloned())
     }
 
-    pub fn learn(&mut self, conn: &Connection, description: &str, category: &str) -> Result<()> {
-        let tokens = tokenize(description);
-
-        let tx = conn
-            .transaction()
-            .context("Categorization learn transaction failed")?;
-
-        for token in tokens {
+    /// Learns `category` for the tokens of a committed transaction's description and
+    /// records the transaction as their provenance. A token already mapped to another
+    /// category is reassigned and its hit count starts over.
+    ///
+    /// Writes through `conn` without a transaction of its own, so callers can make
+    /// learning part of the change that taught it.
+    pub fn learn(
+        &mut self,
+        conn: &Connection,
+        description: &str,
+        category: &str,
+        transaction_id: i64,
+    ) -> Result<()> {
+        for token in tokenize(description) {
             self.tokens.insert(token.clone(), category.to_string());
-            tx.execute(
+            conn.execute(
                 r#"
                 INSERT INTO categorization_memory (token, category, hit_count, updated_at)
                 VALUES (?1, ?2, 1, datetime('now'))
                 ON CONFLICT(token) DO UPDATE SET
                     category=excluded.category,
-                    hit_count=hit_count + 1,
+                    hit_count=CASE WHEN category = excluded.category THEN hit_count + 1 ELSE 1 END,
                     updated_at=datetime('now')
                 "#,
                 params![token, category],
             )
             .context("Upsert categorization token failed")?;
+            conn.execute(
+                "INSERT OR REPLACE INTO categorization_provenance (token, transaction_id, category) VALUES (?1, ?2, ?3)",
+                params![token, transaction_id, category],
+            )
+            .context("Record categorization provenance failed")?;
+        }
+        Ok(())
+    }
+
+    /// Takes back one hit from each token of `description` still mapped to
+    /// `category`, after the user moved an item away from that suggestion. A token
+    /// left without hits is forgotten, along with its provenance.
+    pub fn unlearn(&mut self, conn: &Connection, description: &str, category: &str) -> Result<()> {
+        for token in tokenize(description) {
+            let weakened = conn
+                .execute(
+                    "UPDATE categorization_memory SET hit_count=hit_count - 1, updated_at=datetime('now')
+                     WHERE token=?1 AND category=?2",
+                    params![token, category],
+                )
+                .context("Weaken categorization token failed")?;
+            if weakened > 0 {
+                let exhausted = conn.execute(
+                    "DELETE FROM categorization_memory WHERE token=?1 AND hit_count <= 0",
+                    params![token],
+                )?;
+                if exhausted > 0 {
+                    self.forget_token(conn, &token)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Points `token` at `category`, keeping its hit count. Returns `false` if the
+    /// token was never learned.
+    pub fn relabel(&mut self, conn: &Connection, token: &str, category: &str) -> Result<bool> {
+        let updated = conn
+            .execute(
+                "UPDATE categorization_memory SET category=?2, updated_at=datetime('now') WHERE token=?1",
+                params![token, category],
+            )
+            .context("Relabel categorization token failed")?;
+        if updated > 0 {
+            self.tokens.insert(token.to_string(), category.to_string());
         }
+        Ok(updated > 0)
+    }
+
+    /// Forgets `token` and its provenance. Returns `false` if it was never learned.
+    pub fn forget(&mut self, conn: &Connection, token: &str) -> Result<bool> {
+        let deleted = conn
+            .execute(
+                "DELETE FROM categorization_memory WHERE token=?1",
+                params![token],
+            )
+            .context("Delete categorization token failed")?;
+        self.forget_token(conn, token)?;
+        Ok(deleted > 0)
+    }
 
-        tx.commit().context("Commit categorization learn failed")?;
+    fn forget_token(&mut self, conn: &Connection, token: &str) -> Result<()> {
+        conn.execute(
+            "DELETE FROM categorization_provenance WHERE token=?1",
+            params![token],
+        )
+        .context("Delete categorization provenance failed")?;
+        self.tokens.remove(token);
         Ok(())
     }
 }
 
-fn tokenize(input: &str) -> Vec<String> {
+pub fn learned_tokens(conn: &Connection) -> Result<Vec<LearnedToken>> {
+    let mut stmt = conn.prepare(
+        "SELECT m.token, m.category, m.hit_count, m.updated_at,
+                (SELECT COUNT(*) FROM categorization_provenance p WHERE p.token = m.token)
+         FROM categorization_memory m ORDER BY m.token",
+    )?;
+    let tokens = stmt
+        .query_map([], |row| {
+            Ok(LearnedToken {
+                token: row.get(0)?,
+                category: row.get(1)?,
+                hit_count: row.get(2)?,
+                updated_at: row.get(3)?,
+                transaction_count: row.get::<_, i64>(4)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch learned tokens")?;
+    Ok(tokens)
+}
+
+/// Transactions that taught `token`, newest lesson first.
+pub fn provenance(conn: &Connection, token: &str) -> Result<Vec<TokenProvenance>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.transaction_id, t.date, t.description, t.amount, p.category, p.taught_at
+         FROM categorization_provenance p JOIN transactions t ON t.id = p.transaction_id
+         WHERE p.token = ?1 ORDER BY p.taught_at DESC, p.transaction_id DESC",
+    )?;
+    let rows = stmt
+        .query_map(params![token], |row| {
+            Ok(TokenProvenance {
+                transaction_id: row.get(0)?,
+                date: row.get(1)?,
+                description: row.get(2)?,
+                amount: row.get(3)?,
+                category: row.get(4)?,
+                taught_at: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch token provenance")?;
+    Ok(rows)
+}
+
+pub fn tokenize(input: &str) -> Vec<String> {
     input
         .split(|c: char| !c.is_alphanumeric())
         .filter(|token| token.len() > 2)
@@ -67,3 +184,64 @@ fn tokenize(input: &str) -> Vec<String> {
 }
 
 pub type SharedMemory = Arc<RwLock<CategorizationMemory>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+
+    fn hits(conn: &Connection, token: &str) -> Option<(String, i64)> {
+        learned_tokens(conn)
+            .unwrap()
+            .into_iter()
+            .find(|learned| learned.token == token)
+            .map(|learned| (learned.category, learned.hit_count))
+    }
+
+    #[test]
+    fn corrections_weaken_and_eventually_forget_tokens() {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        for id in 1..=3 {
+            conn.execute(
+                "INSERT INTO transactions (id, date, description, amount, flow)
+                 VALUES (?1, '2024-01-05', 'UBER TRIP', -250.0, 'debit')",
+                params![id],
+            )
+            .unwrap();
+        }
+
+        let mut memory = CategorizationMemory::default();
+        memory.learn(&conn, "UBER TRIP", "Transport", 1).unwrap();
+        memory.learn(&conn, "UBER TRIP", "Transport", 2).unwrap();
+        assert_eq!(hits(&conn, "uber"), Some(("Transport".into(), 2)));
+        assert_eq!(provenance(&conn, "uber").unwrap().len(), 2);
+
+        memory.unlearn(&conn, "UBER TRIP", "Transport").unwrap();
+        assert_eq!(hits(&conn, "uber"), Some(("Transport".into(), 1)));
+        // Only a token still pointing at the corrected category loses a hit.
+        memory.unlearn(&conn, "UBER TRIP", "Food").unwrap();
+        assert_eq!(hits(&conn, "uber"), Some(("Transport".into(), 1)));
+
+        memory.unlearn(&conn, "UBER TRIP", "Transport").unwrap();
+        assert_eq!(hits(&conn, "uber"), None);
+        assert!(provenance(&conn, "uber").unwrap().is_empty());
+        assert_eq!(memory.suggest("UBER RIDE"), None);
+
+        // Relearning under another category starts the count over.
+        memory.learn(&conn, "UBER TRIP", "Transport", 1).unwrap();
+        memory.learn(&conn, "UBER TRIP", "Commute", 3).unwrap();
+        assert_eq!(hits(&conn, "trip"), Some(("Commute".into(), 1)));
+        let taught = provenance(&conn, "trip").unwrap();
+        assert_eq!(taught.len(), 2);
+        assert!(taught
+            .iter()
+            .any(|row| row.transaction_id == 1 && row.category == "Transport"));
+
+        assert!(memory.relabel(&conn, "trip", "Travel").unwrap());
+        assert_eq!(memory.suggest("TRIP"), Some("Travel".into()));
+        assert!(memory.forget(&conn, "trip").unwrap());
+        assert!(!memory.forget(&conn, "trip").unwrap());
+        assert_eq!(memory.suggest("TRIP"), None);
+    }
+}
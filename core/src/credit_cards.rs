@@ -0,0 +1,517 @@
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection};
+use time::{Date, Month};
+
+use crate::{
+    dto::{
+        CardMatchSummary, CardOutstanding, CardStatement, CardStatementStatus, CreditCard,
+        NewCreditCard,
+    },
+    periods,
+};
+
+const DEFAULT_MIN_DUE_PERCENT: f64 = 5.0;
+/// Issuers ask for at least this much (or the full balance when smaller).
+const MIN_DUE_FLOOR: f64 = 200.0;
+/// Narration fragments that mark a bank debit as a card bill payment when no payment
+/// pattern is configured and the amount equals the statement balance.
+const PAYMENT_HINTS: &[&str] = &[
+    "CREDIT CARD",
+    "CARD PAYMENT",
+    "CC PAYMENT",
+    "CRED",
+    "BILLDESK",
+];
+/// Days after the due date a payment is still attributed to the statement.
+const PAYMENT_GRACE_DAYS: i64 = 3;
+
+pub fn add_card(conn: &Connection, card: &NewCreditCard) -> Result<CreditCard> {
+    validate(card)?;
+    conn.execute(
+        "INSERT INTO credit_cards (name, last_four, statement_day, due_day, credit_limit, spend_pattern, payment_pattern, min_due_percent) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            card.name.trim(),
+            card.last_four,
+            card.statement_day,
+            card.due_day,
+            card.credit_limit,
+            normalize_pattern(card.spend_pattern.as_deref()),
+            normalize_pattern(card.payment_pattern.as_deref()),
+            card.min_due_percent.unwrap_or(DEFAULT_MIN_DUE_PERCENT)
+        ],
+    )
+    .context("Failed to add credit card")?;
+    get_card(conn, conn.last_insert_rowid())
+}
+
+pub fn update_card(conn: &Connection, id: i64, card: &NewCreditCard) -> Result<CreditCard> {
+    validate(card)?;
+    conn.execute(
+        "UPDATE credit_cards SET name=?2, last_four=?3, statement_day=?4, due_day=?5, credit_limit=?6, spend_pattern=?7, payment_pattern=?8, min_due_percent=?9 WHERE id=?1",
+        params![
+            id,
+            card.name.trim(),
+            card.last_four,
+            card.statement_day,
+            card.due_day,
+            card.credit_limit,
+            normalize_pattern(card.spend_pattern.as_deref()),
+            normalize_pattern(card.payment_pattern.as_deref()),
+            card.min_due_percent.unwrap_or(DEFAULT_MIN_DUE_PERCENT)
+        ],
+    )
+    .context("Failed to update credit card")?;
+    get_card(conn, id)
+}
+
+pub fn delete_card(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for card delete")?;
+    tx.execute(
+        "DELETE FROM card_transactions WHERE card_id=?1",
+        params![id],
+    )?;
+    tx.execute("DELETE FROM card_payments WHERE card_id=?1", params![id])?;
+    tx.execute("DELETE FROM credit_cards WHERE id=?1", params![id])?;
+    tx.commit().context("Commit card delete failed")?;
+    Ok(())
+}
+
+pub fn get_card(conn: &Connection, id: i64) -> Result<CreditCard> {
+    conn.query_row(
+        "SELECT id, name, last_four, statement_day, due_day, credit_limit, spend_pattern, payment_pattern, min_due_percent FROM credit_cards WHERE id=?1",
+        params![id],
+        map_card,
+    )
+    .with_context(|| format!("Credit card {id} not found"))
+}
+
+pub fn get_cards(conn: &Connection) -> Result<Vec<CreditCard>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, last_four, statement_day, due_day, credit_limit, spend_pattern, payment_pattern, min_due_percent FROM credit_cards ORDER BY name, id",
+    )?;
+    let rows = stmt
+        .query_map([], map_card)?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch credit cards")?;
+    Ok(rows)
+}
+
+/// Marks transactions as swipes (or refunds) on `card_id`, e.g. after importing the
+/// card's own statement.
+pub fn assign_transactions(conn: &Connection, card_id: i64, transaction_ids: &[i64]) -> Result<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for card assignment")?;
+    for id in transaction_ids {
+        tx.execute(
+            "DELETE FROM card_payments WHERE transaction_id=?1",
+            params![id],
+        )?;
+        tx.execute(
+            "INSERT INTO card_transactions (transaction_id, card_id) VALUES (?1, ?2) ON CONFLICT(transaction_id) DO UPDATE SET card_id=excluded.card_id",
+            params![id, card_id],
+        )?;
+    }
+    tx.commit().context("Commit card assignment failed")?;
+    Ok(())
+}
+
+pub fn link_payment(conn: &Connection, card_id: i64, transaction_id: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO card_payments (transaction_id, card_id) VALUES (?1, ?2) ON CONFLICT(transaction_id) DO UPDATE SET card_id=excluded.card_id",
+        params![transaction_id, card_id],
+    )
+    .context("Failed to link card payment")?;
+    Ok(())
+}
+
+/// Detaches a transaction from any card, whether linked as a swipe or a payment.
+pub fn unlink_transaction(conn: &Connection, transaction_id: i64) -> Result<()> {
+    conn.execute(
+        "DELETE FROM card_transactions WHERE transaction_id=?1",
+        params![transaction_id],
+    )?;
+    conn.execute(
+        "DELETE FROM card_payments WHERE transaction_id=?1",
+        params![transaction_id],
+    )?;
+    Ok(())
+}
+
+/// Links swipes by each card's spend pattern and bill payments by its payment
+/// pattern, then falls back to matching bank debits equal to an unpaid statement
+/// balance.
+pub fn match_transactions(conn: &Connection, today: Date) -> Result<CardMatchSummary> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for card matching")?;
+    let mut summary = CardMatchSummary::default();
+    let cards = get_cards(&tx)?;
+
+    for card in &cards {
+        if let Some(pattern) = &card.spend_pattern {
+            summary.spends_linked += tx.execute(
+                "INSERT OR IGNORE INTO card_transactions (transaction_id, card_id) SELECT id, ?1 FROM transactions WHERE UPPER(description) LIKE ?2 AND id NOT IN (SELECT transaction_id FROM card_payments)",
+                params![card.id, format!("%{pattern}%")],
+            )?;
+        }
+        if let Some(pattern) = &card.payment_pattern {
+            summary.payments_linked += tx.execute(
+                "INSERT OR IGNORE INTO card_payments (transaction_id, card_id) SELECT id, ?1 FROM transactions WHERE amount < 0 AND UPPER(description) LIKE ?2 AND id NOT IN (SELECT transaction_id FROM card_transactions)",
+                params![card.id, format!("%{pattern}%")],
+            )?;
+        }
+    }
+
+    for card in &cards {
+        for statement in get_statements(&tx, card, today)? {
+            let remaining = statement.closing_balance - statement.paid_by_due;
+            if statement.status == CardStatementStatus::Paid || remaining < 1.0 {
+                continue;
+            }
+            let Some(transaction_id) = find_payment(&tx, card, &statement, remaining)? else {
+                continue;
+            };
+            link_payment(&tx, card.id, transaction_id)?;
+            summary.payments_linked += 1;
+        }
+    }
+
+    tx.commit().context("Commit card matching failed")?;
+    Ok(summary)
+}
+
+/// Billed statements for `card` up to `today`, oldest first.
+pub fn get_statements(
+    conn: &Connection,
+    card: &CreditCard,
+    today: Date,
+) -> Result<Vec<CardStatement>> {
+    let (spends, payments) = activity(conn, card.id)?;
+    Ok(build_statements(card, &spends, &payments, today).0)
+}
+
+pub fn outstanding(conn: &Connection, today: Date) -> Result<Vec<CardOutstanding>> {
+    get_cards(conn)?
+        .into_iter()
+        .map(|card| {
+            let (spends, payments) = activity(conn, card.id)?;
+            let (statements, unbilled, paid_since_statement) =
+                build_statements(&card, &spends, &payments, today);
+            let last_statement = statements.last().cloned();
+
+            let billed = last_statement
+                .as_ref()
+                .map(|statement| statement.closing_balance)
+                .unwrap_or(0.0);
+            let outstanding = billed - paid_since_statement + unbilled;
+            let (minimum_due, due_date) = match &last_statement {
+                Some(statement) if statement.status != CardStatementStatus::Paid => (
+                    (statement.minimum_due - paid_since_statement).max(0.0),
+                    Some(statement.due_date.clone()),
+                ),
+                _ => (0.0, None),
+            };
+
+            Ok(CardOutstanding {
+                available_credit: card.credit_limit.map(|limit| limit - outstanding.max(0.0)),
+                card,
+                last_statement,
+                unbilled,
+                paid_since_statement,
+                outstanding,
+                minimum_due,
+                due_date,
+            })
+        })
+        .collect()
+}
+
+/// Walks billing cycles from the first activity to `today`. Returns the billed
+/// statements, unbilled net spend and payments received since the last statement.
+fn build_statements(
+    card: &CreditCard,
+    spends: &[(Date, f64)],
+    payments: &[(Date, f64)],
+    today: Date,
+) -> (Vec<CardStatement>, f64, f64) {
+    let first_activity = spends.iter().chain(payments).map(|(date, _)| *date).min();
+    let Some(first_activity) = first_activity else {
+        return (Vec::new(), 0.0, 0.0);
+    };
+
+    let mut statement_date = statement_on(card, first_activity.year(), first_activity.month());
+    if statement_date < first_activity {
+        statement_date = next_statement(card, statement_date);
+    }
+    let mut previous = previous_statement(card, statement_date);
+    let mut balance = 0.0;
+    let mut statements = Vec::new();
+
+    while statement_date <= today {
+        let in_cycle = |date: &Date| *date > previous && *date <= statement_date;
+        let cycle_payments = sum_where(payments, in_cycle);
+        let (spend, credits) = split(spends, in_cycle);
+
+        let due_date = due_on(card, statement_date);
+        let opening_balance = balance - cycle_payments;
+        let closing_balance = opening_balance + spend - credits;
+        let minimum_due = minimum_due(card, closing_balance);
+        let paid_by_due = sum_where(payments, |date| {
+            *date > statement_date && periods::days_between(due_date, *date) <= 0
+        });
+
+        let status = if paid_by_due >= closing_balance - 0.5 {
+            CardStatementStatus::Paid
+        } else if today <= due_date {
+            CardStatementStatus::Due
+        } else if paid_by_due >= minimum_due - 0.5 {
+            CardStatementStatus::PartiallyPaid
+        } else {
+            CardStatementStatus::Overdue
+        };
+
+        statements.push(CardStatement {
+            card_id: card.id,
+            period_start: periods::format_date(periods::shift_days(previous, 1)),
+            statement_date: periods::format_date(statement_date),
+            due_date: periods::format_date(due_date),
+            opening_balance,
+            spend,
+            credits,
+            payments: cycle_payments,
+            closing_balance,
+            minimum_due,
+            paid_by_due,
+            status,
+        });
+
+        balance = closing_balance;
+        previous = statement_date;
+        statement_date = next_statement(card, statement_date);
+    }
+
+    let since_statement = |date: &Date| *date > previous && *date <= today;
+    let (spend, credits) = split(spends, since_statement);
+    let paid_since = sum_where(payments, since_statement);
+    (statements, spend - credits, paid_since)
+}
+
+/// Debit and credit totals of card activity within a window. Debits are stored as
+/// negative amounts in the ledger.
+fn split(activity: &[(Date, f64)], within: impl Fn(&Date) -> bool) -> (f64, f64) {
+    activity.iter().filter(|(date, _)| within(date)).fold(
+        (0.0, 0.0),
+        |(spend, credits), (_, amount)| {
+            if *amount < 0.0 {
+                (spend - amount, credits)
+            } else {
+                (spend, credits + amount)
+            }
+        },
+    )
+}
+
+fn sum_where(activity: &[(Date, f64)], within: impl Fn(&Date) -> bool) -> f64 {
+    activity
+        .iter()
+        .filter(|(date, _)| within(date))
+        .map(|(_, amount)| amount)
+        .sum()
+}
+
+fn minimum_due(card: &CreditCard, balance: f64) -> f64 {
+    if balance <= 0.0 {
+        return 0.0;
+    }
+    (balance * card.min_due_percent / 100.0)
+        .max(MIN_DUE_FLOOR)
+        .min(balance)
+}
+
+fn day_in(year: i32, month: Month, day: u8) -> Date {
+    let day = day.clamp(1, periods::days_in_month(year, month));
+    Date::from_calendar_date(year, month, day).unwrap_or(Date::MIN)
+}
+
+fn statement_on(card: &CreditCard, year: i32, month: Month) -> Date {
+    day_in(year, month, card.statement_day)
+}
+
+fn next_statement(card: &CreditCard, statement: Date) -> Date {
+    let next = periods::add_months(periods::month_start(statement), 1);
+    statement_on(card, next.year(), next.month())
+}
+
+fn previous_statement(card: &CreditCard, statement: Date) -> Date {
+    let previous = periods::add_months(periods::month_start(statement), -1);
+    statement_on(card, previous.year(), previous.month())
+}
+
+/// Due date for a statement: the due day later in the same month, otherwise in the
+/// following month.
+fn due_on(card: &CreditCard, statement: Date) -> Date {
+    if card.due_day > card.statement_day {
+        day_in(statement.year(), statement.month(), card.due_day)
+    } else {
+        let next = periods::add_months(periods::month_start(statement), 1);
+        day_in(next.year(), next.month(), card.due_day)
+    }
+}
+
+/// Swipes (signed ledger amounts) and payments (positive) linked to a card.
+type Activity = (Vec<(Date, f64)>, Vec<(Date, f64)>);
+
+fn activity(conn: &Connection, card_id: i64) -> Result<Activity> {
+    let load = |sql: &str| -> Result<Vec<(Date, f64)>> {
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt
+            .query_map(params![card_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to fetch card activity")?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(date, amount)| Some((periods::parse_date(&date).ok()?, amount)))
+            .collect())
+    };
+
+    let spends = load(
+        "SELECT t.date, t.amount FROM card_transactions ct JOIN transactions t ON t.id = ct.transaction_id WHERE ct.card_id=?1",
+    )?;
+    let payments = load(
+        "SELECT t.date, ABS(t.amount) FROM card_payments cp JOIN transactions t ON t.id = cp.transaction_id WHERE cp.card_id=?1",
+    )?;
+    Ok((spends, payments))
+}
+
+/// An unlinked bank debit paying `amount` of `statement` before its due date (plus a
+/// short grace period) whose narration looks like a card payment.
+fn find_payment(
+    conn: &Connection,
+    card: &CreditCard,
+    statement: &CardStatement,
+    amount: f64,
+) -> Result<Option<i64>> {
+    let due = periods::parse_date(&statement.due_date)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, description FROM transactions WHERE amount < 0 AND ABS(ABS(amount) - ?1) <= 1.0 AND date > ?2 AND date <= ?3 AND id NOT IN (SELECT transaction_id FROM card_transactions) AND id NOT IN (SELECT transaction_id FROM card_payments) ORDER BY date, id",
+    )?;
+    let rows = stmt
+        .query_map(
+            params![
+                amount,
+                statement.statement_date,
+                periods::format_date(periods::shift_days(due, PAYMENT_GRACE_DAYS))
+            ],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch card payment candidates")?;
+
+    Ok(rows
+        .into_iter()
+        .find(|(_, description)| {
+            let upper = description.to_uppercase();
+            PAYMENT_HINTS.iter().any(|hint| upper.contains(hint))
+                || card
+                    .last_four
+                    .as_deref()
+                    .is_some_and(|last_four| upper.contains(last_four))
+        })
+        .map(|(id, _)| id))
+}
+
+fn validate(card: &NewCreditCard) -> Result<()> {
+    if !(1..=31).contains(&card.statement_day) || !(1..=31).contains(&card.due_day) {
+        bail!("Statement and due days must be between 1 and 31");
+    }
+    if card.name.trim().is_empty() {
+        bail!("Card name is required");
+    }
+    Ok(())
+}
+
+fn normalize_pattern(pattern: Option<&str>) -> Option<String> {
+    pattern
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(str::to_uppercase)
+}
+
+fn map_card(row: &rusqlite::Row<'_>) -> rusqlite::Result<CreditCard> {
+    Ok(CreditCard {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        last_four: row.get(2)?,
+        statement_day: row.get(3)?,
+        due_day: row.get(4)?,
+        credit_limit: row.get(5)?,
+        spend_pattern: row.get(6)?,
+        payment_pattern: row.get(7)?,
+        min_due_percent: row.get(8)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn statements_follow_billing_cycle_and_payments() {
+        let card = CreditCard {
+            id: 1,
+            name: "Regalia".into(),
+            last_four: Some("1234".into()),
+            statement_day: 15,
+            due_day: 5,
+            credit_limit: Some(100_000.0),
+            spend_pattern: None,
+            payment_pattern: None,
+            min_due_percent: 5.0,
+        };
+        let spends = vec![
+            (day(2024, Month::January, 20), -3_000.0),
+            (day(2024, Month::February, 10), -2_000.0),
+            (day(2024, Month::February, 12), 500.0),
+            (day(2024, Month::February, 20), -1_000.0),
+            (day(2024, Month::March, 20), -4_000.0),
+        ];
+        let payments = vec![
+            (day(2024, Month::March, 1), 4_500.0),
+            (day(2024, Month::April, 10), 300.0),
+        ];
+
+        let (statements, unbilled, paid_since) =
+            build_statements(&card, &spends, &payments, day(2024, Month::April, 16));
+        assert_eq!(statements.len(), 3);
+
+        let february = &statements[0];
+        assert_eq!(february.statement_date, "2024-02-15");
+        assert_eq!(february.due_date, "2024-03-05");
+        assert_eq!(february.spend, 5_000.0);
+        assert_eq!(february.credits, 500.0);
+        assert_eq!(february.closing_balance, 4_500.0);
+        assert_eq!(february.status, CardStatementStatus::Paid);
+
+        let march = &statements[1];
+        assert_eq!(march.opening_balance, 0.0);
+        assert_eq!(march.closing_balance, 1_000.0);
+        assert_eq!(march.status, CardStatementStatus::Overdue);
+
+        let april = &statements[2];
+        assert_eq!(april.payments, 300.0);
+        assert_eq!(april.closing_balance, 4_700.0);
+        assert_eq!(april.minimum_due, 235.0);
+        assert_eq!(april.status, CardStatementStatus::Due);
+        assert_eq!(unbilled, 0.0);
+        assert_eq!(paid_since, 0.0);
+    }
+}
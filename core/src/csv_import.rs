@@ -94,7 +94,7 @@ pub fn parse_csv(bytes: &[u8]) -> Result<Vec<ParsedRow>> {
     }
 }
 
-fn find_index(headers: &[String], candidates: &[&str]) -> Option<usize> {
+pub(crate) fn find_index(headers: &[String], candidates: &[&str]) -> Option<usize> {
     // 1. Exact match first
     if let Some(idx) = candidates
         .iter()
@@ -110,7 +110,7 @@ fn find_index(headers: &[String], candidates: &[&str]) -> Option<usize> {
     })
 }
 
-fn parse_amount(raw: &str) -> Result<f64> {
+pub(crate) fn parse_amount(raw: &str) -> Result<f64> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
         return Ok(0.0);
@@ -138,7 +138,7 @@ fn parse_amount(raw: &str) -> Result<f64> {
     }
 }
 
-fn normalize_date(raw: &str) -> Option<String> {
+pub(crate) fn normalize_date(raw: &str) -> Option<String> {
     // Try formats: DD/MM/YY, DD/MM/YYYY, YYYY-MM-DD
     let formats = [
         format_description!("[day]/[month]/[year repr:last_two]"),
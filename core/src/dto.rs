@@ -35,6 +35,53 @@ pub struct InboxItem {
     pub suggested_category: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IncomeSource {
+    Salary,
+    Interest,
+    Dividend,
+    Refund,
+    Reimbursement,
+    Transfer,
+    Other,
+}
+
+impl IncomeSource {
+    /// Maps a user-assigned category name onto an income source, if it names one.
+    pub fn from_category(category: &str) -> Option<Self> {
+        match category.trim().to_lowercase().as_str() {
+            "salary" | "payroll" | "wages" => Some(IncomeSource::Salary),
+            "interest" => Some(IncomeSource::Interest),
+            "dividend" | "dividends" => Some(IncomeSource::Dividend),
+            "refund" | "refunds" | "cashback" => Some(IncomeSource::Refund),
+            "reimbursement" | "reimbursements" => Some(IncomeSource::Reimbursement),
+            "transfer" | "transfers" | "self transfer" => Some(IncomeSource::Transfer),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IncomeSource::Salary => "salary",
+            IncomeSource::Interest => "interest",
+            IncomeSource::Dividend => "dividend",
+            IncomeSource::Refund => "refund",
+            IncomeSource::Reimbursement => "reimbursement",
+            IncomeSource::Transfer => "transfer",
+            IncomeSource::Other => "other",
+        }
+    }
+
+    /// Refunds, reimbursements and transfers move money around without earning it.
+    pub fn is_income(&self) -> bool {
+        !matches!(
+            self,
+            IncomeSource::Refund | IncomeSource::Reimbursement | IncomeSource::Transfer
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SummaryResponse {
@@ -42,6 +89,19 @@ pub struct SummaryResponse {
     pub total_spend: f64,
     pub by_category: Vec<CategoryAmount>,
     pub budgets: Vec<BudgetUsage>,
+    pub total_income: f64,
+    pub income_by_source: Vec<IncomeAmount>,
+    pub refunds: f64,
+    pub transfers_in: f64,
+    pub free_cash_flow: f64,
+    pub savings_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncomeAmount {
+    pub source: IncomeSource,
+    pub amount: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +117,21 @@ pub struct BudgetUsage {
     pub category: String,
     pub cap: f64,
     pub spent: f64,
+    pub thresholds: Vec<f64>,
+    pub percent_used: f64,
+    pub expected_spend: f64,
+    pub projected_spend: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetAlert {
+    pub category: String,
+    pub month: String,
+    pub threshold: f64,
+    pub percent_used: f64,
+    pub spent: f64,
+    pub cap: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +154,8 @@ pub struct SetCategoryResponse {
 pub struct AppSettings {
     pub theme: String,
     pub accounts: Option<String>,
+    /// Idle minutes before the shell locks the vault; 0 disables auto-lock.
+    pub auto_lock_minutes: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,3 +164,1034 @@ pub struct BudgetConfig {
     pub category: String,
     pub cap: f64,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetProposal {
+    pub category: String,
+    pub suggested_cap: f64,
+    pub median_spend: f64,
+    pub months_observed: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportGrouping {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    #[serde(rename = "fy")]
+    FinancialYear,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportResponse {
+    pub grouping: ReportGrouping,
+    pub current: PeriodTotals,
+    pub previous_period: PeriodTotals,
+    pub same_period_last_year: PeriodTotals,
+    pub buckets: Vec<ReportBucket>,
+    pub categories: Vec<CategorySeries>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodTotals {
+    pub start: String,
+    pub end: String,
+    pub spend: f64,
+    pub income: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportBucket {
+    pub label: String,
+    pub start: String,
+    pub end: String,
+    pub spend: f64,
+    pub income: f64,
+}
+
+/// Spend per bucket for one category; `amounts` lines up with `ReportResponse::buckets`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategorySeries {
+    pub category: String,
+    pub amounts: Vec<f64>,
+    pub total: f64,
+    pub previous_total: f64,
+    pub last_year_total: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaxSection {
+    #[serde(rename = "80c")]
+    Section80C,
+    #[serde(rename = "80d")]
+    Section80D,
+    #[serde(rename = "80ccd1b")]
+    Section80Ccd1b,
+    #[serde(rename = "hra")]
+    Hra,
+    #[serde(rename = "24b")]
+    HomeLoanInterest,
+}
+
+impl TaxSection {
+    pub const ALL: [TaxSection; 5] = [
+        TaxSection::Section80C,
+        TaxSection::Section80D,
+        TaxSection::Section80Ccd1b,
+        TaxSection::Hra,
+        TaxSection::HomeLoanInterest,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|section| section.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaxSection::Section80C => "80c",
+            TaxSection::Section80D => "80d",
+            TaxSection::Section80Ccd1b => "80ccd1b",
+            TaxSection::Hra => "hra",
+            TaxSection::HomeLoanInterest => "24b",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TaxSection::Section80C => "Section 80C (ELSS, PPF, EPF, LIC)",
+            TaxSection::Section80D => "Section 80D (health insurance)",
+            TaxSection::Section80Ccd1b => "Section 80CCD(1B) (NPS)",
+            TaxSection::Hra => "HRA (rent paid)",
+            TaxSection::HomeLoanInterest => "Section 24(b) (home loan interest)",
+        }
+    }
+
+    /// Statutory ceiling under the old regime; HRA has no fixed cap.
+    pub fn limit(&self) -> Option<f64> {
+        match self {
+            TaxSection::Section80C => Some(150_000.0),
+            TaxSection::Section80D => Some(25_000.0),
+            TaxSection::Section80Ccd1b => Some(50_000.0),
+            TaxSection::Hra => None,
+            TaxSection::HomeLoanInterest => Some(200_000.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaxRuleKind {
+    Category,
+    Keyword,
+}
+
+impl TaxRuleKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "category" => Some(TaxRuleKind::Category),
+            "keyword" => Some(TaxRuleKind::Keyword),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaxRuleKind::Category => "category",
+            TaxRuleKind::Keyword => "keyword",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaxRule {
+    pub id: Option<i64>,
+    pub section: TaxSection,
+    pub kind: TaxRuleKind,
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaxItem {
+    pub transaction_id: i64,
+    pub date: String,
+    pub description: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaxSectionSummary {
+    pub section: TaxSection,
+    pub label: String,
+    pub claimed: f64,
+    pub limit: Option<f64>,
+    pub eligible: f64,
+    pub items: Vec<TaxItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HraExemption {
+    pub rent_paid: f64,
+    pub hra_received: f64,
+    pub basic_salary: f64,
+    pub metro: bool,
+    pub exempt: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaxReport {
+    pub financial_year: String,
+    pub start: String,
+    pub end: String,
+    pub sections: Vec<TaxSectionSummary>,
+    pub hra: Option<HraExemption>,
+    pub total_deductions: f64,
+    /// Settings the report needs but are unset, e.g. `tax_basic_salary` while rent
+    /// was paid; the affected deductions count as zero until they are filled in.
+    pub missing_inputs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetClass {
+    EquityFund,
+    DebtFund,
+    HybridFund,
+    Stock,
+    Etf,
+    FixedDeposit,
+    Ppf,
+    Epf,
+    Gold,
+    Other,
+}
+
+impl AssetClass {
+    pub const ALL: [AssetClass; 10] = [
+        AssetClass::EquityFund,
+        AssetClass::DebtFund,
+        AssetClass::HybridFund,
+        AssetClass::Stock,
+        AssetClass::Etf,
+        AssetClass::FixedDeposit,
+        AssetClass::Ppf,
+        AssetClass::Epf,
+        AssetClass::Gold,
+        AssetClass::Other,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AssetClass::EquityFund => "equity_fund",
+            AssetClass::DebtFund => "debt_fund",
+            AssetClass::HybridFund => "hybrid_fund",
+            AssetClass::Stock => "stock",
+            AssetClass::Etf => "etf",
+            AssetClass::FixedDeposit => "fixed_deposit",
+            AssetClass::Ppf => "ppf",
+            AssetClass::Epf => "epf",
+            AssetClass::Gold => "gold",
+            AssetClass::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewHolding {
+    pub name: String,
+    pub asset_class: AssetClass,
+    /// ISIN, ticker or AMFI scheme code; unique together with `folio`.
+    pub symbol: String,
+    pub folio: Option<String>,
+    /// For ETFs, whether the underlying is domestic equity; other ETFs are taxed like
+    /// debt funds.
+    #[serde(default)]
+    pub equity_oriented: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Holding {
+    pub id: i64,
+    pub name: String,
+    pub asset_class: AssetClass,
+    pub symbol: String,
+    pub folio: Option<String>,
+    pub equity_oriented: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LotKind {
+    Buy,
+    Sell,
+}
+
+impl LotKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LotKind::Buy => "buy",
+            LotKind::Sell => "sell",
+        }
+    }
+}
+
+/// A purchase or sale of units. Deposits without units (FD, PPF, EPF) are recorded as
+/// `units` = amount at `price` 1.0, with later price points carrying accrued value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoldingLot {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub holding_id: i64,
+    pub kind: LotKind,
+    pub date: String,
+    pub units: f64,
+    pub price: f64,
+    #[serde(default)]
+    pub charges: f64,
+    #[serde(default)]
+    pub external_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricePoint {
+    pub date: String,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoldingValuation {
+    pub holding: Holding,
+    pub units: f64,
+    pub cost_basis: f64,
+    pub price: Option<f64>,
+    pub price_date: Option<String>,
+    pub market_value: f64,
+    pub unrealized_gain: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CasTxKind {
+    Purchase,
+    Sip,
+    Redemption,
+    SwitchIn,
+    SwitchOut,
+    Dividend,
+    DividendReinvest,
+    StampDuty,
+    Stt,
+    Tds,
+}
+
+impl CasTxKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CasTxKind::Purchase => "purchase",
+            CasTxKind::Sip => "sip",
+            CasTxKind::Redemption => "redemption",
+            CasTxKind::SwitchIn => "switch_in",
+            CasTxKind::SwitchOut => "switch_out",
+            CasTxKind::Dividend => "dividend",
+            CasTxKind::DividendReinvest => "dividend_reinvest",
+            CasTxKind::StampDuty => "stamp_duty",
+            CasTxKind::Stt => "stt",
+            CasTxKind::Tds => "tds",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CasImportSummary {
+    pub schemes: usize,
+    pub lots_added: usize,
+    pub lots_skipped: usize,
+    pub prices: usize,
+    pub dividends: f64,
+    pub stt: f64,
+    pub tds: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Broker {
+    Zerodha,
+    Groww,
+}
+
+impl Broker {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "zerodha" => Some(Broker::Zerodha),
+            "groww" => Some(Broker::Groww),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Broker::Zerodha => "zerodha",
+            Broker::Groww => "groww",
+        }
+    }
+
+    /// Upper-case fragments identifying the broker in bank statement narrations.
+    pub fn ledger_patterns(&self) -> &'static [&'static str] {
+        match self {
+            Broker::Zerodha => &["ZERODHA"],
+            Broker::Groww => &["GROWW", "NEXTBILLION"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokerImportSummary {
+    pub broker: Broker,
+    pub trades: usize,
+    /// Tradebook rows left out as non-equity or unreadable.
+    pub rows_skipped: usize,
+    pub holdings: usize,
+    pub lots_added: usize,
+    pub lots_skipped: usize,
+    pub fundings_linked: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokerFunding {
+    pub transaction_id: i64,
+    pub broker: Broker,
+    pub date: String,
+    pub description: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GainTerm {
+    ShortTerm,
+    LongTerm,
+}
+
+/// How a gain is taxed, mirroring the buckets of ITR Schedule CG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GainCategory {
+    /// Listed equity short-term gains under section 111A.
+    EquityStcg,
+    /// Listed equity long-term gains under section 112A.
+    EquityLtcg,
+    /// Short-term gains (and specified mutual fund gains) taxed at slab rates.
+    Slab,
+    /// Long-term gains under section 112 with indexation.
+    LtcgIndexed,
+    /// Long-term gains under section 112 without indexation.
+    Ltcg,
+}
+
+impl GainCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GainCategory::EquityStcg => "equity_stcg",
+            GainCategory::EquityLtcg => "equity_ltcg",
+            GainCategory::Slab => "slab",
+            GainCategory::LtcgIndexed => "ltcg_indexed",
+            GainCategory::Ltcg => "ltcg",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GainCategory::EquityStcg => "STCG on equity (111A)",
+            GainCategory::EquityLtcg => "LTCG on equity (112A)",
+            GainCategory::Slab => "STCG at slab rates",
+            GainCategory::LtcgIndexed => "LTCG with indexation (112)",
+            GainCategory::Ltcg => "LTCG without indexation (112)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealizedGain {
+    pub holding_id: i64,
+    pub holding_name: String,
+    pub asset_class: AssetClass,
+    pub symbol: String,
+    pub buy_date: String,
+    pub sell_date: String,
+    pub units: f64,
+    /// Sale consideration net of transfer charges.
+    pub sale_value: f64,
+    /// Actual purchase cost including charges.
+    pub cost: f64,
+    /// Fair market value on 31-Jan-2018 for grandfathered equity.
+    pub fmv_2018: Option<f64>,
+    pub indexed_cost: Option<f64>,
+    /// The cost deducted after grandfathering or indexation.
+    pub cost_used: f64,
+    pub gain: f64,
+    pub term: GainTerm,
+    pub category: GainCategory,
+    /// Flat rate in percent; `None` for slab-taxed gains.
+    pub rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapitalGainsLine {
+    pub category: GainCategory,
+    pub label: String,
+    pub term: GainTerm,
+    pub rate: Option<f64>,
+    pub sale_value: f64,
+    pub cost: f64,
+    pub gain: f64,
+    pub exemption: f64,
+    pub taxable: f64,
+    pub estimated_tax: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnrealizedGain {
+    pub holding_id: i64,
+    pub holding_name: String,
+    pub asset_class: AssetClass,
+    pub units: f64,
+    pub cost: f64,
+    pub market_value: f64,
+    pub short_term_gain: f64,
+    pub long_term_gain: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapitalGainsReport {
+    pub financial_year: String,
+    pub start: String,
+    pub end: String,
+    pub realized: Vec<RealizedGain>,
+    pub schedule: Vec<CapitalGainsLine>,
+    pub unrealized: Vec<UnrealizedGain>,
+    pub total_realized: f64,
+    pub total_unrealized: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReturnMetrics {
+    /// Total bought, including charges.
+    pub invested: f64,
+    /// Total sale proceeds net of charges.
+    pub withdrawn: f64,
+    pub current_value: f64,
+    pub gain: f64,
+    /// Annualised money-weighted return as a fraction; `None` when it cannot be solved.
+    pub xirr: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoldingReturn {
+    pub holding: Holding,
+    pub metrics: ReturnMetrics,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetClassReturn {
+    pub asset_class: AssetClass,
+    pub metrics: ReturnMetrics,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PerformanceReport {
+    pub as_of: String,
+    pub portfolio: ReturnMetrics,
+    pub asset_classes: Vec<AssetClassReturn>,
+    pub holdings: Vec<HoldingReturn>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeWeightedReturn {
+    pub start: String,
+    pub end: String,
+    pub asset_class: Option<AssetClass>,
+    /// Cumulative return over the period as a fraction.
+    pub twr: Option<f64>,
+    pub annualized: Option<f64>,
+    pub sub_periods: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoanKind {
+    Home,
+    Car,
+    Personal,
+    Education,
+    Other,
+}
+
+impl LoanKind {
+    pub const ALL: [LoanKind; 5] = [
+        LoanKind::Home,
+        LoanKind::Car,
+        LoanKind::Personal,
+        LoanKind::Education,
+        LoanKind::Other,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoanKind::Home => "home",
+            LoanKind::Car => "car",
+            LoanKind::Personal => "personal",
+            LoanKind::Education => "education",
+            LoanKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewLoan {
+    pub name: String,
+    pub kind: LoanKind,
+    pub principal: f64,
+    /// Annual interest rate in percent.
+    pub annual_rate: f64,
+    pub tenure_months: u32,
+    /// Date of the first EMI (`YYYY-MM-DD`); later EMIs fall on the same day monthly.
+    pub start_date: String,
+    /// EMI charged by the lender when it differs from the computed one.
+    #[serde(default)]
+    pub emi: Option<f64>,
+    /// Narration fragment identifying EMI debits, e.g. `HDFC HOME LOAN`.
+    #[serde(default)]
+    pub match_pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Loan {
+    pub id: i64,
+    pub name: String,
+    pub kind: LoanKind,
+    pub principal: f64,
+    pub annual_rate: f64,
+    pub tenure_months: u32,
+    pub start_date: String,
+    pub emi: f64,
+    pub match_pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoanPrepayment {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub loan_id: i64,
+    pub date: String,
+    pub amount: f64,
+    #[serde(default)]
+    pub transaction_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AmortizationRow {
+    pub installment: u32,
+    pub date: String,
+    pub opening_balance: f64,
+    pub emi: f64,
+    pub interest: f64,
+    pub principal: f64,
+    /// Prepayments made since the previous installment, applied after this EMI.
+    pub prepayment: f64,
+    pub closing_balance: f64,
+    /// The bank transaction matched to this EMI, if any.
+    pub transaction_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoanSummary {
+    pub loan: Loan,
+    pub outstanding_principal: f64,
+    pub principal_paid: f64,
+    pub interest_paid: f64,
+    pub emis_paid: u32,
+    pub emis_matched: u32,
+    pub emis_remaining: u32,
+    pub next_due: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewCreditCard {
+    pub name: String,
+    pub last_four: Option<String>,
+    /// Day of month the statement is generated (clamped to short months).
+    pub statement_day: u8,
+    /// Day of month payment is due; before `statement_day` means the following month.
+    pub due_day: u8,
+    #[serde(default)]
+    pub credit_limit: Option<f64>,
+    /// Narration fragment identifying swipes on this card in imported statements.
+    #[serde(default)]
+    pub spend_pattern: Option<String>,
+    /// Narration fragment identifying bill payments on the bank side.
+    #[serde(default)]
+    pub payment_pattern: Option<String>,
+    /// Minimum due as a percentage of the statement balance (defaults to 5).
+    #[serde(default)]
+    pub min_due_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreditCard {
+    pub id: i64,
+    pub name: String,
+    pub last_four: Option<String>,
+    pub statement_day: u8,
+    pub due_day: u8,
+    pub credit_limit: Option<f64>,
+    pub spend_pattern: Option<String>,
+    pub payment_pattern: Option<String>,
+    pub min_due_percent: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardStatementStatus {
+    /// Due date not reached and not yet paid in full.
+    Due,
+    Paid,
+    /// At least the minimum due was paid by the due date.
+    PartiallyPaid,
+    Overdue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardStatement {
+    pub card_id: i64,
+    pub period_start: String,
+    pub statement_date: String,
+    pub due_date: String,
+    /// Previous balance less payments received during the cycle.
+    pub opening_balance: f64,
+    pub spend: f64,
+    pub credits: f64,
+    pub payments: f64,
+    pub closing_balance: f64,
+    pub minimum_due: f64,
+    /// Payments received between the statement date and the due date.
+    pub paid_by_due: f64,
+    pub status: CardStatementStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardOutstanding {
+    pub card: CreditCard,
+    pub last_statement: Option<CardStatement>,
+    /// Spend less credits since the last statement.
+    pub unbilled: f64,
+    pub paid_since_statement: f64,
+    pub outstanding: f64,
+    pub minimum_due: f64,
+    pub due_date: Option<String>,
+    pub available_credit: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardMatchSummary {
+    pub spends_linked: usize,
+    pub payments_linked: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewGoal {
+    pub name: String,
+    /// Fixed target; ignored when `expense_multiple` is set.
+    #[serde(default)]
+    pub target_amount: Option<f64>,
+    /// Target as a multiple of average monthly spend, e.g. 6 for an emergency fund.
+    #[serde(default)]
+    pub expense_multiple: Option<f64>,
+    #[serde(default)]
+    pub target_date: Option<String>,
+    /// Expected annual return in percent on the money set aside.
+    #[serde(default)]
+    pub expected_return: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Goal {
+    pub id: i64,
+    pub name: String,
+    pub target_amount: Option<f64>,
+    pub expense_multiple: Option<f64>,
+    pub target_date: Option<String>,
+    pub expected_return: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalHolding {
+    pub goal_id: i64,
+    pub holding_id: i64,
+    /// Fraction of the holding's value earmarked for the goal.
+    pub share: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalContribution {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub goal_id: i64,
+    pub date: String,
+    pub amount: f64,
+    /// The ledger transaction this contribution was tagged from, if any.
+    #[serde(default)]
+    pub transaction_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub target_amount: f64,
+    pub current_amount: f64,
+    pub from_holdings: f64,
+    pub from_contributions: f64,
+    /// Net ledger balance of the accounts set aside for the goal.
+    pub from_accounts: f64,
+    /// Progress as a fraction of the target, capped at 1.
+    pub progress: f64,
+    pub remaining: f64,
+    pub months_left: Option<i32>,
+    pub required_monthly: Option<f64>,
+    pub average_monthly_contribution: f64,
+    pub projected_completion: Option<String>,
+    pub on_track: Option<bool>,
+    pub holdings: Vec<GoalHolding>,
+    pub accounts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultTableCount {
+    pub name: String,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultBundleInfo {
+    pub format_version: u32,
+    pub schema_version: i64,
+    /// Unix timestamp (seconds) of the export.
+    pub exported_at: i64,
+    pub tables: Vec<VaultTableCount>,
+}
+
+/// How many snapshots to keep: the newest one of each of the last `daily` days,
+/// `weekly` weeks and `monthly` months.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupPolicy {
+    pub daily: usize,
+    pub weekly: usize,
+    pub monthly: usize,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            daily: 7,
+            weekly: 4,
+            monthly: 12,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSnapshot {
+    pub name: String,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`.
+    pub taken_at: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportKind {
+    Ledger,
+    CategorySummary,
+    Budgets,
+}
+
+/// Inclusive `YYYY-MM-DD` range plus an optional account. Budgets are tracked
+/// across accounts, so a budget export with an account filter is refused.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportFilter {
+    pub start: String,
+    pub end: String,
+    pub account: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub date: String,
+    pub description: String,
+    pub amount: f64,
+    pub flow: FlowKind,
+    pub category: Option<String>,
+    pub account: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategorySummaryExport {
+    pub start: String,
+    pub end: String,
+    pub account: Option<String>,
+    pub total_spend: f64,
+    pub by_category: Vec<CategoryAmount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonthlyBudgetUsage {
+    pub month: String,
+    pub budgets: Vec<BudgetUsage>,
+}
+
+/// One entry of the vault's append-only audit log. `before`/`after` are `None`
+/// when the entity did not exist on that side of the change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i64,
+    /// UTC, RFC 3339 with milliseconds.
+    pub occurred_at: String,
+    pub operation: String,
+    pub entity: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub hash: String,
+}
+
+/// Audit log page, newest first. `before_id` continues from the last entry of the
+/// previous page.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditQuery {
+    pub operation: Option<String>,
+    /// Exact match, or a prefix when it ends in `:` (e.g. `budget:`).
+    pub entity: Option<String>,
+    pub before_id: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditVerification {
+    pub entries: usize,
+    pub valid: bool,
+    /// First entry whose hash or link to its predecessor does not check out.
+    pub first_invalid_id: Option<i64>,
+    /// The chain ends short of the head anchored by the last append, i.e. the
+    /// newest entries were removed.
+    pub truncated: bool,
+    /// Hash of the newest entry.
+    pub head_hash: Option<String>,
+}
+
+/// An undoable operation. `undone` entries form the redo stack.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub id: i64,
+    /// UTC, RFC 3339 with milliseconds.
+    pub occurred_at: String,
+    pub operation: String,
+    /// Human-readable description, e.g. for an "Undo …" menu item.
+    pub label: String,
+    pub undone: bool,
+}
+
+/// A token the categorization memory has learned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LearnedToken {
+    pub token: String,
+    pub category: String,
+    /// Commits that confirmed `category`, less corrections away from it.
+    pub hit_count: i64,
+    pub updated_at: String,
+    /// Committed transactions that taught this token, whatever category they taught.
+    pub transaction_count: usize,
+}
+
+/// A committed transaction that taught a token, and what it taught.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenProvenance {
+    pub transaction_id: i64,
+    pub date: String,
+    pub description: String,
+    pub amount: f64,
+    pub category: String,
+    pub taught_at: String,
+}
@@ -0,0 +1,290 @@
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection};
+use time::Date;
+
+use crate::{
+    budgets,
+    dto::{
+        CategoryAmount, CategorySummaryExport, ExportFilter, ExportFormat, ExportKind, FlowKind,
+        LedgerEntry, MonthlyBudgetUsage,
+    },
+    periods,
+};
+
+/// Byte-order mark so spreadsheet apps detect UTF-8 (₹, non-ASCII merchant names).
+const UTF8_BOM: &str = "\u{feff}";
+
+/// Renders one export. JSON uses the same camelCase shapes as the app's API
+/// contracts; CSV is flat with one header row.
+pub fn render(
+    conn: &Connection,
+    kind: ExportKind,
+    format: ExportFormat,
+    filter: &ExportFilter,
+    today: Date,
+) -> Result<String> {
+    let (start, end) = parse_range(filter)?;
+    if kind == ExportKind::Budgets && filter.account.is_some() {
+        bail!("Budgets cover every account; clear the account filter to export them");
+    }
+    match (kind, format) {
+        (ExportKind::Ledger, ExportFormat::Json) => to_json(&ledger(conn, filter)?),
+        (ExportKind::Ledger, ExportFormat::Csv) => ledger_csv(&ledger(conn, filter)?),
+        (ExportKind::CategorySummary, ExportFormat::Json) => {
+            to_json(&category_summary(conn, filter)?)
+        }
+        (ExportKind::CategorySummary, ExportFormat::Csv) => {
+            category_summary_csv(&category_summary(conn, filter)?)
+        }
+        (ExportKind::Budgets, ExportFormat::Json) => {
+            to_json(&budget_months(conn, start, end, today)?)
+        }
+        (ExportKind::Budgets, ExportFormat::Csv) => {
+            budgets_csv(&budget_months(conn, start, end, today)?)
+        }
+    }
+}
+
+fn parse_range(filter: &ExportFilter) -> Result<(Date, Date)> {
+    let start = periods::parse_date(&filter.start)?;
+    let end = periods::parse_date(&filter.end)?;
+    if end < start {
+        bail!("Export end date precedes start date");
+    }
+    Ok((start, end))
+}
+
+fn ledger(conn: &Connection, filter: &ExportFilter) -> Result<Vec<LedgerEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, date, description, amount, flow, category, account FROM transactions
+         WHERE date >= ?1 AND date <= ?2 AND (?3 IS NULL OR account = ?3)
+         ORDER BY date, id",
+    )?;
+    let rows = stmt
+        .query_map(params![filter.start, filter.end, filter.account], |row| {
+            Ok(LedgerEntry {
+                id: row.get(0)?,
+                date: row.get(1)?,
+                description: row.get(2)?,
+                amount: row.get(3)?,
+                flow: match row.get::<_, String>(4)?.as_str() {
+                    "credit" => FlowKind::Credit,
+                    _ => FlowKind::Debit,
+                },
+                category: row.get(5)?,
+                account: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch ledger for export")?;
+    Ok(rows)
+}
+
+fn category_summary(conn: &Connection, filter: &ExportFilter) -> Result<CategorySummaryExport> {
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(category, 'Uncategorized') AS cat, SUM(ABS(amount)) FROM transactions
+         WHERE amount < 0 AND date >= ?1 AND date <= ?2 AND (?3 IS NULL OR account = ?3)
+           AND id NOT IN (SELECT transaction_id FROM card_payments)
+         GROUP BY cat ORDER BY SUM(ABS(amount)) DESC",
+    )?;
+    let by_category = stmt
+        .query_map(params![filter.start, filter.end, filter.account], |row| {
+            Ok(CategoryAmount {
+                category: row.get(0)?,
+                amount: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch category summary for export")?;
+
+    Ok(CategorySummaryExport {
+        start: filter.start.clone(),
+        end: filter.end.clone(),
+        account: filter.account.clone(),
+        total_spend: by_category.iter().map(|line| line.amount).sum(),
+        by_category,
+    })
+}
+
+/// Budget usage for every calendar month touching the range.
+fn budget_months(
+    conn: &Connection,
+    start: Date,
+    end: Date,
+    today: Date,
+) -> Result<Vec<MonthlyBudgetUsage>> {
+    let mut months = Vec::new();
+    let mut cursor = periods::month_start(start);
+    while cursor <= end {
+        let month = format!("{:04}-{:02}", cursor.year(), u8::from(cursor.month()));
+        months.push(MonthlyBudgetUsage {
+            budgets: budgets::budget_usage(conn, &month, today)?,
+            month,
+        });
+        cursor = periods::add_months(cursor, 1);
+    }
+    Ok(months)
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).context("Failed to serialise export")
+}
+
+fn finish(writer: csv::Writer<Vec<u8>>) -> Result<String> {
+    let bytes = writer.into_inner().context("Failed to finalise CSV")?;
+    Ok(format!("{UTF8_BOM}{}", String::from_utf8(bytes)?))
+}
+
+/// Spreadsheets evaluate cells starting with these characters as formulas; a bank
+/// description like `=HYPERLINK(...)` must stay text.
+fn text_cell(value: &str) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    }
+}
+
+fn ledger_csv(entries: &[LedgerEntry]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "id",
+        "date",
+        "description",
+        "amount",
+        "flow",
+        "category",
+        "account",
+    ])?;
+    for entry in entries {
+        writer.write_record([
+            entry.id.to_string().as_str(),
+            entry.date.as_str(),
+            &text_cell(&entry.description),
+            &format!("{:.2}", entry.amount),
+            entry.flow.as_str(),
+            &text_cell(entry.category.as_deref().unwrap_or("")),
+            &text_cell(entry.account.as_deref().unwrap_or("")),
+        ])?;
+    }
+    finish(writer)
+}
+
+fn category_summary_csv(summary: &CategorySummaryExport) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["start", "end", "account", "category", "amount"])?;
+    let account = text_cell(summary.account.as_deref().unwrap_or(""));
+    for line in &summary.by_category {
+        writer.write_record([
+            summary.start.as_str(),
+            summary.end.as_str(),
+            &account,
+            &text_cell(&line.category),
+            &format!("{:.2}", line.amount),
+        ])?;
+    }
+    writer.write_record([
+        summary.start.as_str(),
+        summary.end.as_str(),
+        &account,
+        "Total",
+        &format!("{:.2}", summary.total_spend),
+    ])?;
+    finish(writer)
+}
+
+fn budgets_csv(months: &[MonthlyBudgetUsage]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["month", "category", "cap", "spent", "percent_used"])?;
+    for month in months {
+        for budget in &month.budgets {
+            writer.write_record([
+                month.month.as_str(),
+                &text_cell(&budget.category),
+                &format!("{:.2}", budget.cap),
+                &format!("{:.2}", budget.spent),
+                &format!("{:.2}", budget.percent_used),
+            ])?;
+        }
+    }
+    finish(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+    use time::macros::date;
+
+    #[test]
+    fn exports_filtered_ledger_as_spreadsheet_safe_csv() {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        for (date, description, amount, account) in [
+            ("2024-03-02", "=cmd|' /C calc'!A0", -120.0, "HDFC"),
+            ("2024-03-05", "Salary", 50_000.0, "HDFC"),
+            ("2024-03-06", "Groceries", -80.0, "ICICI"),
+            ("2024-04-01", "Rent", -20_000.0, "HDFC"),
+        ] {
+            conn.execute(
+                "INSERT INTO transactions (date, description, amount, flow, category, account)
+                 VALUES (?1, ?2, ?3, ?4, 'Misc', ?5)",
+                params![
+                    date,
+                    description,
+                    amount,
+                    FlowKind::from_amount(amount).as_str(),
+                    account
+                ],
+            )
+            .unwrap();
+        }
+
+        let filter = ExportFilter {
+            start: "2024-03-01".into(),
+            end: "2024-03-31".into(),
+            account: Some("HDFC".into()),
+        };
+        let csv = render(
+            &conn,
+            ExportKind::Ledger,
+            ExportFormat::Csv,
+            &filter,
+            date!(2024 - 04 - 15),
+        )
+        .unwrap();
+        let lines = csv.trim_start_matches(UTF8_BOM).lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines[1].contains("'=cmd"),
+            "formula not neutralised: {}",
+            lines[1]
+        );
+        assert!(lines[2].contains("50000.00,credit"));
+
+        let summary = category_summary(&conn, &filter).unwrap();
+        assert_eq!(summary.total_spend, 120.0);
+
+        // Budget caps span all accounts, so an account filter is refused.
+        assert!(render(
+            &conn,
+            ExportKind::Budgets,
+            ExportFormat::Json,
+            &filter,
+            date!(2024 - 04 - 15),
+        )
+        .is_err());
+        let json = render(
+            &conn,
+            ExportKind::Budgets,
+            ExportFormat::Json,
+            &ExportFilter {
+                account: None,
+                ..filter
+            },
+            date!(2024 - 04 - 15),
+        )
+        .unwrap();
+        assert!(json.contains("\"month\": \"2024-03\""));
+    }
+}
@@ -0,0 +1,511 @@
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection};
+use time::Date;
+
+use crate::{
+    dto::{Goal, GoalContribution, GoalHolding, GoalProgress, NewGoal},
+    holdings, periods,
+};
+
+/// Months of history behind the average spend and average contribution figures.
+const LOOKBACK_MONTHS: i32 = 6;
+/// Projections stop after this many months and report no completion date.
+const MAX_PROJECTION_MONTHS: i32 = 1200;
+
+pub fn add_goal(conn: &Connection, goal: &NewGoal) -> Result<Goal> {
+    validate(goal)?;
+    conn.execute(
+        "INSERT INTO goals (name, target_amount, expense_multiple, target_date, expected_return) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            goal.name.trim(),
+            goal.target_amount,
+            goal.expense_multiple,
+            goal.target_date,
+            goal.expected_return.unwrap_or(0.0)
+        ],
+    )
+    .context("Failed to add goal")?;
+    get_goal(conn, conn.last_insert_rowid())
+}
+
+pub fn update_goal(conn: &Connection, id: i64, goal: &NewGoal) -> Result<Goal> {
+    validate(goal)?;
+    conn.execute(
+        "UPDATE goals SET name=?2, target_amount=?3, expense_multiple=?4, target_date=?5, expected_return=?6 WHERE id=?1",
+        params![
+            id,
+            goal.name.trim(),
+            goal.target_amount,
+            goal.expense_multiple,
+            goal.target_date,
+            goal.expected_return.unwrap_or(0.0)
+        ],
+    )
+    .context("Failed to update goal")?;
+    get_goal(conn, id)
+}
+
+pub fn delete_goal(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for goal delete")?;
+    tx.execute("DELETE FROM goal_holdings WHERE goal_id=?1", params![id])?;
+    tx.execute("DELETE FROM goal_accounts WHERE goal_id=?1", params![id])?;
+    tx.execute(
+        "DELETE FROM goal_contributions WHERE goal_id=?1",
+        params![id],
+    )?;
+    tx.execute("DELETE FROM goals WHERE id=?1", params![id])?;
+    tx.commit().context("Commit goal delete failed")?;
+    Ok(())
+}
+
+pub fn get_goal(conn: &Connection, id: i64) -> Result<Goal> {
+    conn.query_row(
+        "SELECT id, name, target_amount, expense_multiple, target_date, expected_return FROM goals WHERE id=?1",
+        params![id],
+        map_goal,
+    )
+    .with_context(|| format!("Goal {id} not found"))
+}
+
+pub fn get_goals(conn: &Connection) -> Result<Vec<Goal>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, target_amount, expense_multiple, target_date, expected_return FROM goals ORDER BY COALESCE(target_date, '9999'), name",
+    )?;
+    let rows = stmt
+        .query_map([], map_goal)?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch goals")?;
+    Ok(rows)
+}
+
+/// Earmarks `share` (0–1] of a holding's market value for a goal.
+pub fn link_holding(conn: &Connection, goal_id: i64, holding_id: i64, share: f64) -> Result<()> {
+    if share <= 0.0 || share > 1.0 {
+        bail!("Share must be greater than 0 and at most 1");
+    }
+    conn.execute(
+        "INSERT INTO goal_holdings (goal_id, holding_id, share) VALUES (?1, ?2, ?3) ON CONFLICT(goal_id, holding_id) DO UPDATE SET share=excluded.share",
+        params![goal_id, holding_id, share],
+    )
+    .context("Failed to link holding to goal")?;
+    Ok(())
+}
+
+pub fn unlink_holding(conn: &Connection, goal_id: i64, holding_id: i64) -> Result<()> {
+    conn.execute(
+        "DELETE FROM goal_holdings WHERE goal_id=?1 AND holding_id=?2",
+        params![goal_id, holding_id],
+    )
+    .context("Failed to unlink holding from goal")?;
+    Ok(())
+}
+
+/// Sets a whole ledger account (e.g. a savings account kept for the goal) aside for
+/// a goal; its net balance counts as progress and its net inflow as contributions.
+pub fn link_account(conn: &Connection, goal_id: i64, account: &str) -> Result<()> {
+    let account = account.trim();
+    if account.is_empty() {
+        bail!("Account is required");
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO goal_accounts (goal_id, account) VALUES (?1, ?2)",
+        params![goal_id, account],
+    )
+    .context("Failed to link account to goal")?;
+    Ok(())
+}
+
+pub fn unlink_account(conn: &Connection, goal_id: i64, account: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM goal_accounts WHERE goal_id=?1 AND account=?2",
+        params![goal_id, account],
+    )
+    .context("Failed to unlink account from goal")?;
+    Ok(())
+}
+
+pub fn add_contribution(conn: &Connection, contribution: &GoalContribution) -> Result<i64> {
+    periods::parse_date(&contribution.date)?;
+    conn.execute(
+        "INSERT INTO goal_contributions (goal_id, date, amount, transaction_id) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            contribution.goal_id,
+            contribution.date,
+            contribution.amount,
+            contribution.transaction_id
+        ],
+    )
+    .context("Failed to add goal contribution")?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Counts a ledger transaction (typically a transfer to savings) towards a goal,
+/// replacing any earlier tag on the same transaction.
+pub fn tag_transaction(conn: &Connection, goal_id: i64, transaction_id: i64) -> Result<i64> {
+    let (date, amount): (String, f64) = conn
+        .query_row(
+            "SELECT date, amount FROM transactions WHERE id=?1",
+            params![transaction_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .with_context(|| format!("Transaction {transaction_id} not found"))?;
+
+    conn.execute(
+        "INSERT INTO goal_contributions (goal_id, date, amount, transaction_id) VALUES (?1, ?2, ?3, ?4) ON CONFLICT(transaction_id) DO UPDATE SET goal_id=excluded.goal_id, date=excluded.date, amount=excluded.amount",
+        params![goal_id, date, amount.abs(), transaction_id],
+    )
+    .context("Failed to tag transaction to goal")?;
+
+    conn.query_row(
+        "SELECT id FROM goal_contributions WHERE transaction_id=?1",
+        params![transaction_id],
+        |row| row.get(0),
+    )
+    .context("Failed to reload goal contribution")
+}
+
+pub fn delete_contribution(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM goal_contributions WHERE id=?1", params![id])
+        .context("Failed to delete goal contribution")?;
+    Ok(())
+}
+
+pub fn get_contributions(conn: &Connection, goal_id: i64) -> Result<Vec<GoalContribution>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, goal_id, date, amount, transaction_id FROM goal_contributions WHERE goal_id=?1 ORDER BY date, id",
+    )?;
+    let rows = stmt
+        .query_map(params![goal_id], |row| {
+            Ok(GoalContribution {
+                id: row.get(0)?,
+                goal_id: row.get(1)?,
+                date: row.get(2)?,
+                amount: row.get(3)?,
+                transaction_id: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch goal contributions")?;
+    Ok(rows)
+}
+
+/// Current progress and forecasts for every goal as of `today`.
+pub fn progress(conn: &Connection, today: Date) -> Result<Vec<GoalProgress>> {
+    let as_of = periods::format_date(today);
+    let lookback_start = periods::format_date(periods::add_months(
+        periods::month_start(today),
+        -LOOKBACK_MONTHS,
+    ));
+    let month_start = periods::format_date(periods::month_start(today));
+    let day_after = periods::format_date(periods::shift_days(today, 1));
+    let average_spend = average_monthly_spend(conn, &lookback_start, &month_start)?;
+    let valuations = holdings::valuations(conn, &as_of)?;
+
+    get_goals(conn)?
+        .into_iter()
+        .map(|goal| {
+            let linked = goal_holdings(conn, goal.id)?;
+            let from_holdings = linked
+                .iter()
+                .filter_map(|link| {
+                    valuations
+                        .iter()
+                        .find(|valuation| valuation.holding.id == link.holding_id)
+                        .map(|valuation| valuation.market_value * link.share)
+                })
+                .sum::<f64>();
+
+            let accounts = goal_accounts(conn, goal.id)?;
+            let from_accounts = account_flows(conn, goal.id, "", &day_after)?;
+            let average_account_inflow =
+                account_flows(conn, goal.id, &lookback_start, &month_start)?
+                    / LOOKBACK_MONTHS as f64;
+
+            let contributions = get_contributions(conn, goal.id)?;
+            let from_contributions = contributions
+                .iter()
+                .filter(|contribution| contribution.date <= as_of)
+                .map(|contribution| contribution.amount)
+                .sum::<f64>();
+            let average_monthly_contribution = contributions
+                .iter()
+                .filter(|contribution| {
+                    contribution.date >= lookback_start && contribution.date < month_start
+                })
+                .map(|contribution| contribution.amount)
+                .sum::<f64>()
+                / LOOKBACK_MONTHS as f64
+                + average_account_inflow;
+
+            let target_amount = match goal.expense_multiple {
+                Some(multiple) => multiple * average_spend,
+                None => goal.target_amount.unwrap_or(0.0),
+            };
+            let current_amount = from_holdings + from_contributions + from_accounts;
+            let remaining = (target_amount - current_amount).max(0.0);
+
+            let target_date = goal
+                .target_date
+                .as_deref()
+                .map(periods::parse_date)
+                .transpose()?;
+            let months_left = target_date.map(|date| periods::months_between(today, date).max(0));
+            let required_monthly = months_left.map(|months| {
+                required_monthly(current_amount, target_amount, months, goal.expected_return)
+            });
+            let projected_completion = projected_months(
+                current_amount,
+                target_amount,
+                average_monthly_contribution,
+                goal.expected_return,
+            )
+            .map(|months| periods::format_date(periods::add_months(today, months)));
+            let on_track = target_date.map(|date| {
+                projected_completion
+                    .as_deref()
+                    .is_some_and(|projected| projected <= periods::format_date(date).as_str())
+            });
+
+            Ok(GoalProgress {
+                target_amount,
+                current_amount,
+                from_holdings,
+                from_contributions,
+                from_accounts,
+                progress: if target_amount > 0.0 {
+                    (current_amount / target_amount).min(1.0)
+                } else {
+                    1.0
+                },
+                remaining,
+                months_left,
+                required_monthly,
+                average_monthly_contribution,
+                projected_completion,
+                on_track,
+                holdings: linked,
+                accounts,
+                goal,
+            })
+        })
+        .collect()
+}
+
+/// Monthly saving needed to grow `current` into `target` over `months`, with
+/// contributions and the balance compounding monthly at `annual_return` percent.
+fn required_monthly(current: f64, target: f64, months: i32, annual_return: f64) -> f64 {
+    let rate = annual_return / 1200.0;
+    if months <= 0 {
+        return (target - current).max(0.0);
+    }
+    let growth = (1.0 + rate).powi(months);
+    let shortfall = target - current * growth;
+    if shortfall <= 0.0 {
+        return 0.0;
+    }
+    if rate.abs() < f64::EPSILON {
+        shortfall / months as f64
+    } else {
+        shortfall * rate / (growth - 1.0)
+    }
+}
+
+/// Months until `current` plus `monthly` contributions reach `target`, or `None`
+/// if it never does within the projection horizon.
+fn projected_months(current: f64, target: f64, monthly: f64, annual_return: f64) -> Option<i32> {
+    let rate = annual_return / 1200.0;
+    let mut balance = current;
+    for month in 0..=MAX_PROJECTION_MONTHS {
+        if balance >= target - 0.005 {
+            return Some(month);
+        }
+        balance = balance * (1.0 + rate) + monthly;
+        if monthly <= 0.0 && rate <= 0.0 {
+            return None;
+        }
+    }
+    None
+}
+
+/// Average spend per month over `start..end`, across the months that have ledger
+/// entries. Money moved rather than spent is left out: card bill payments, broker
+/// fundings, EMIs and prepayments, and transfers tagged to a goal.
+fn average_monthly_spend(conn: &Connection, start: &str, end: &str) -> Result<f64> {
+    let (total, months): (f64, i64) = conn
+        .query_row(
+            r#"
+            SELECT
+                (SELECT COALESCE(SUM(ABS(amount)), 0) FROM transactions
+                 WHERE amount < 0 AND date >= ?1 AND date < ?2
+                   AND id NOT IN (SELECT transaction_id FROM card_payments)
+                   AND id NOT IN (SELECT transaction_id FROM broker_fundings)
+                   AND id NOT IN (SELECT transaction_id FROM loan_payments)
+                   AND id NOT IN (SELECT transaction_id FROM loan_prepayments WHERE transaction_id IS NOT NULL)
+                   AND id NOT IN (SELECT transaction_id FROM goal_contributions WHERE transaction_id IS NOT NULL)),
+                (SELECT COUNT(DISTINCT substr(date, 1, 7)) FROM transactions
+                 WHERE date >= ?1 AND date < ?2)
+            "#,
+            params![start, end],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .context("Failed to compute average spend")?;
+    Ok(if months > 0 {
+        total / months as f64
+    } else {
+        0.0
+    })
+}
+
+fn goal_accounts(conn: &Connection, goal_id: i64) -> Result<Vec<String>> {
+    let mut stmt =
+        conn.prepare("SELECT account FROM goal_accounts WHERE goal_id=?1 ORDER BY account")?;
+    let rows = stmt
+        .query_map(params![goal_id], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch goal accounts")?;
+    Ok(rows)
+}
+
+/// Net flow into the goal's linked accounts dated in `start..end`. Transactions
+/// already tagged as contributions of the goal are not counted twice.
+fn account_flows(conn: &Connection, goal_id: i64, start: &str, end: &str) -> Result<f64> {
+    conn.query_row(
+        r#"
+        SELECT COALESCE(SUM(amount), 0) FROM transactions
+        WHERE account IN (SELECT account FROM goal_accounts WHERE goal_id=?1)
+          AND date >= ?2 AND date < ?3
+          AND id NOT IN (
+              SELECT transaction_id FROM goal_contributions
+              WHERE goal_id=?1 AND transaction_id IS NOT NULL
+          )
+        "#,
+        params![goal_id, start, end],
+        |row| row.get(0),
+    )
+    .context("Failed to total goal account flows")
+}
+
+fn goal_holdings(conn: &Connection, goal_id: i64) -> Result<Vec<GoalHolding>> {
+    let mut stmt = conn.prepare(
+        "SELECT goal_id, holding_id, share FROM goal_holdings WHERE goal_id=?1 ORDER BY holding_id",
+    )?;
+    let rows = stmt
+        .query_map(params![goal_id], |row| {
+            Ok(GoalHolding {
+                goal_id: row.get(0)?,
+                holding_id: row.get(1)?,
+                share: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch goal holdings")?;
+    Ok(rows)
+}
+
+fn validate(goal: &NewGoal) -> Result<()> {
+    if goal.name.trim().is_empty() {
+        bail!("Goal name is required");
+    }
+    match (goal.target_amount, goal.expense_multiple) {
+        (_, Some(multiple)) if multiple <= 0.0 => bail!("Expense multiple must be positive"),
+        (Some(amount), None) if amount <= 0.0 => bail!("Target amount must be positive"),
+        (None, None) => bail!("Goal needs a target amount or an expense multiple"),
+        _ => {}
+    }
+    if let Some(date) = &goal.target_date {
+        periods::parse_date(date)?;
+    }
+    Ok(())
+}
+
+fn map_goal(row: &rusqlite::Row<'_>) -> rusqlite::Result<Goal> {
+    Ok(Goal {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        target_amount: row.get(2)?,
+        expense_multiple: row.get(3)?,
+        target_date: row.get(4)?,
+        expected_return: row.get(5)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forecasts_required_saving_and_completion() {
+        // 60k saved towards 1.2L in 12 months with no growth: 5k a month.
+        assert!((required_monthly(60_000.0, 120_000.0, 12, 0.0) - 5_000.0).abs() < 1e-6);
+        // Growth reduces what needs to be saved.
+        assert!(required_monthly(60_000.0, 120_000.0, 12, 7.0) < 5_000.0);
+        assert_eq!(required_monthly(130_000.0, 120_000.0, 12, 0.0), 0.0);
+
+        assert_eq!(
+            projected_months(60_000.0, 120_000.0, 5_000.0, 0.0),
+            Some(12)
+        );
+        assert_eq!(projected_months(120_000.0, 120_000.0, 0.0, 0.0), Some(0));
+        assert_eq!(projected_months(10_000.0, 120_000.0, 0.0, 0.0), None);
+    }
+
+    #[test]
+    fn expense_targets_use_observed_months_and_skip_transfers() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::migrate(&conn).unwrap();
+        for (id, date, description, amount, account) in [
+            (1, "2025-03-04", "RENT", -30_000.0, "HDFC"),
+            (2, "2025-04-04", "RENT", -30_000.0, "HDFC"),
+            (3, "2025-04-10", "ZERODHA FUNDS", -50_000.0, "HDFC"),
+            (4, "2025-04-20", "CC BILL PAYMENT", -12_000.0, "HDFC"),
+            (5, "2025-04-12", "TRANSFER FROM HDFC", 20_000.0, "SBI"),
+        ] {
+            conn.execute(
+                "INSERT INTO transactions (id, date, description, amount, flow, account)
+                 VALUES (?1, ?2, ?3, ?4, 'debit', ?5)",
+                params![id, date, description, amount, account],
+            )
+            .unwrap();
+        }
+        conn.execute_batch(
+            "INSERT INTO broker_fundings (transaction_id, broker) VALUES (3, 'zerodha');
+             INSERT INTO credit_cards (id, name, statement_day, due_day) VALUES (1, 'Card', 10, 28);
+             INSERT INTO card_payments (transaction_id, card_id) VALUES (4, 1);",
+        )
+        .unwrap();
+
+        let goal = add_goal(
+            &conn,
+            &NewGoal {
+                name: "Emergency fund".into(),
+                target_amount: None,
+                expense_multiple: Some(6.0),
+                target_date: None,
+                expected_return: None,
+            },
+        )
+        .unwrap();
+        add_contribution(
+            &conn,
+            &GoalContribution {
+                id: None,
+                goal_id: goal.id,
+                date: "2025-04-30".into(),
+                amount: 5_000.0,
+                transaction_id: None,
+            },
+        )
+        .unwrap();
+        link_account(&conn, goal.id, "SBI").unwrap();
+
+        let today = time::macros::date!(2025 - 05 - 15);
+        let progress = progress(&conn, today).unwrap().remove(0);
+        // Two months of rent, averaged over the two months on record.
+        assert_eq!(progress.target_amount, 180_000.0);
+        assert_eq!(progress.from_contributions, 5_000.0);
+        assert_eq!(progress.from_accounts, 20_000.0);
+        assert_eq!(progress.accounts, ["SBI"]);
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::VecDeque;
+
+use anyhow::{bail, Context, Result};
+use csv::ReaderBuilder;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    csv_import,
+    dto::{AssetClass, Holding, HoldingLot, HoldingValuation, LotKind, NewHolding, PricePoint},
+};
+
+/// A buy lot (or the unsold part of one) still held after FIFO matching of sells.
+#[derive(Debug, Clone)]
+pub struct OpenLot {
+    pub date: String,
+    pub units: f64,
+    /// Purchase price per unit including a proportional share of charges.
+    pub unit_cost: f64,
+}
+
+/// The part of a sell matched against one buy lot.
+#[derive(Debug, Clone)]
+pub struct Disposal {
+    pub buy_date: String,
+    pub sell_date: String,
+    pub units: f64,
+    pub unit_cost: f64,
+    /// Sale price per unit net of a proportional share of the sell's charges.
+    pub unit_proceeds: f64,
+}
+
+/// Returns the holding for `symbol`/`folio`, creating it if it does not exist yet.
+pub fn upsert_holding(conn: &Connection, holding: &NewHolding) -> Result<Holding> {
+    let folio = holding.folio.clone().unwrap_or_default();
+    conn.execute(
+        r#"
+        INSERT INTO holdings (name, asset_class, symbol, folio, equity_oriented)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT(symbol, folio) DO UPDATE SET
+            name=excluded.name,
+            asset_class=excluded.asset_class,
+            equity_oriented=excluded.equity_oriented
+        "#,
+        params![
+            holding.name,
+            holding.asset_class.as_str(),
+            holding.symbol,
+            folio,
+            holding.equity_oriented
+        ],
+    )
+    .context("Failed to upsert holding")?;
+
+    conn.query_row(
+        "SELECT id, name, asset_class, symbol, folio, equity_oriented FROM holdings WHERE symbol=?1 AND folio=?2",
+        params![holding.symbol, folio],
+        map_holding,
+    )
+    .context("Failed to reload holding")
+}
+
+pub fn get_holdings(conn: &Connection) -> Result<Vec<Holding>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, asset_class, symbol, folio, equity_oriented FROM holdings ORDER BY name, folio",
+    )?;
+    let rows = stmt
+        .query_map([], map_holding)?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch holdings")?;
+    Ok(rows)
+}
+
+pub fn delete_holding(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for holding delete")?;
+    tx.execute("DELETE FROM holding_lots WHERE holding_id=?1", params![id])?;
+    tx.execute(
+        "DELETE FROM holding_prices WHERE holding_id=?1",
+        params![id],
+    )?;
+    tx.execute(
+        "DELETE FROM holding_cash_flows WHERE holding_id=?1",
+        params![id],
+    )?;
+    tx.execute("DELETE FROM goal_holdings WHERE holding_id=?1", params![id])?;
+    tx.execute("DELETE FROM holdings WHERE id=?1", params![id])?;
+    tx.commit().context("Commit holding delete failed")?;
+    Ok(())
+}
+
+/// Records a lot. Lots carrying an `external_ref` already present are skipped so
+/// re-importing the same statement is harmless; returns `None` in that case.
+pub fn add_lot(conn: &Connection, lot: &HoldingLot) -> Result<Option<i64>> {
+    if lot.units <= 0.0 || !lot.units.is_finite() {
+        bail!("Lot units must be positive");
+    }
+
+    let inserted = conn
+        .execute(
+            r#"
+            INSERT OR IGNORE INTO holding_lots
+                (holding_id, kind, date, units, price, charges, external_ref)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            params![
+                lot.holding_id,
+                lot.kind.as_str(),
+                lot.date,
+                lot.units,
+                lot.price,
+                lot.charges,
+                lot.external_ref
+            ],
+        )
+        .context("Failed to insert lot")?;
+
+    Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+}
+
+pub fn delete_lot(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM holding_lots WHERE id=?1", params![id])
+        .context("Failed to delete lot")?;
+    Ok(())
+}
+
+pub fn get_lots(conn: &Connection, holding_id: i64) -> Result<Vec<HoldingLot>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, holding_id, kind, date, units, price, charges, external_ref FROM holding_lots WHERE holding_id=?1 ORDER BY date, id",
+    )?;
+    let rows = stmt
+        .query_map(params![holding_id], |row| {
+            Ok(HoldingLot {
+                id: row.get(0)?,
+                holding_id: row.get(1)?,
+                kind: match row.get::<_, String>(2)?.as_str() {
+                    "sell" => LotKind::Sell,
+                    _ => LotKind::Buy,
+                },
+                date: row.get(3)?,
+                units: row.get(4)?,
+                price: row.get(5)?,
+                charges: row.get(6)?,
+                external_ref: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch lots")?;
+    Ok(rows)
+}
+
+/// Records cash paid out on or withheld from a holding without changing units: a
+/// `dividend`, or `stt`/`tds` deducted by the fund. Like lots, a repeated
+/// `external_ref` is skipped; returns whether a row was added.
+pub fn add_cash_flow(
+    conn: &Connection,
+    holding_id: i64,
+    kind: &str,
+    date: &str,
+    amount: f64,
+    external_ref: &str,
+) -> Result<bool> {
+    let inserted = conn
+        .execute(
+            "INSERT OR IGNORE INTO holding_cash_flows (holding_id, kind, date, amount, external_ref) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![holding_id, kind, date, amount, external_ref],
+        )
+        .context("Failed to insert holding cash flow")?;
+    Ok(inserted > 0)
+}
+
+/// Dividends paid out of a holding as `(date, amount)`, oldest first.
+pub fn dividend_payouts(conn: &Connection, holding_id: i64) -> Result<Vec<(String, f64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, amount FROM holding_cash_flows
+         WHERE holding_id=?1 AND kind='dividend' ORDER BY date, id",
+    )?;
+    let payouts = stmt
+        .query_map(params![holding_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch dividend payouts")?;
+    Ok(payouts)
+}
+
+pub fn set_price(conn: &Connection, holding_id: i64, date: &str, price: f64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO holding_prices (holding_id, date, price) VALUES (?1, ?2, ?3) ON CONFLICT(holding_id, date) DO UPDATE SET price=excluded.price",
+        params![holding_id, date, price],
+    )
+    .context("Failed to store price")?;
+    Ok(())
+}
+
+pub fn get_prices(conn: &Connection, holding_id: i64) -> Result<Vec<PricePoint>> {
+    let mut stmt =
+        conn.prepare("SELECT date, price FROM holding_prices WHERE holding_id=?1 ORDER BY date")?;
+    let rows = stmt
+        .query_map(params![holding_id], |row| {
+            Ok(PricePoint {
+                date: row.get(0)?,
+                price: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch prices")?;
+    Ok(rows)
+}
+
+/// Imports a NAV/price history CSV with a date column and a price/NAV/close column.
+/// Rows whose date cannot be read are skipped. Returns the number of price points
+/// stored.
+pub fn import_prices_csv(conn: &Connection, holding_id: i64, bytes: &[u8]) -> Result<usize> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(true)
+        .from_reader(bytes);
+
+    let headers = reader
+        .headers()
+        .context("Price CSV missing header row")?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect::<Vec<_>>();
+
+    let date_idx = csv_import::find_index(&headers, &["date", "nav date", "price date"])
+        .context("Price CSV missing date column")?;
+    let price_idx = csv_import::find_index(
+        &headers,
+        &["nav", "price", "close", "closing price", "net asset value"],
+    )
+    .context("Price CSV missing price column")?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for price import")?;
+    let mut stored = 0;
+    let mut undated = 0;
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                log::warn!("Skipping malformed price row: {error}");
+                continue;
+            }
+        };
+
+        let Some(raw_date) = record
+            .get(date_idx)
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        else {
+            continue;
+        };
+        let Some(date) = csv_import::normalize_date(raw_date) else {
+            log::warn!("Skipping price row with unreadable date {raw_date:?}");
+            undated += 1;
+            continue;
+        };
+
+        let Ok(price) = csv_import::parse_amount(record.get(price_idx).unwrap_or("")) else {
+            log::warn!("Skipping price row with unreadable price on {date}");
+            continue;
+        };
+        if price <= 0.0 {
+            continue;
+        }
+
+        set_price(&tx, holding_id, &date, price)?;
+        stored += 1;
+    }
+
+    tx.commit().context("Commit price import failed")?;
+    if undated > 0 {
+        log::warn!("Price import skipped {undated} rows with unreadable dates");
+    }
+    Ok(stored)
+}
+
+/// Matches sells against the oldest buys and returns what remains open. Only lots
+/// dated on or before `as_of` (`YYYY-MM-DD`) are considered.
+pub fn open_lots(lots: &[HoldingLot], as_of: &str) -> Vec<OpenLot> {
+    match_fifo(lots, as_of).0
+}
+
+/// FIFO matching that also reports each sell split across the buys it consumed.
+pub fn match_fifo(lots: &[HoldingLot], as_of: &str) -> (Vec<OpenLot>, Vec<Disposal>) {
+    let mut open: VecDeque<OpenLot> = VecDeque::new();
+    let mut disposals = Vec::new();
+
+    for lot in lots.iter().filter(|lot| lot.date.as_str() <= as_of) {
+        match lot.kind {
+            LotKind::Buy => open.push_back(OpenLot {
+                date: lot.date.clone(),
+                units: lot.units,
+                unit_cost: lot.price + lot.charges / lot.units,
+            }),
+            LotKind::Sell => {
+                let unit_proceeds = lot.price - lot.charges / lot.units;
+                let mut remaining = lot.units;
+                while remaining > 1e-9 {
+                    let Some(front) = open.front_mut() else {
+                        log::warn!("Sell on {} exceeds units held", lot.date);
+                        break;
+                    };
+                    let used = remaining.min(front.units);
+                    disposals.push(Disposal {
+                        buy_date: front.date.clone(),
+                        sell_date: lot.date.clone(),
+                        units: used,
+                        unit_cost: front.unit_cost,
+                        unit_proceeds,
+                    });
+                    front.units -= used;
+                    remaining -= used;
+                    if front.units <= 1e-9 {
+                        open.pop_front();
+                    }
+                }
+            }
+        }
+    }
+
+    (open.into_iter().collect(), disposals)
+}
+
+/// Latest known price on or before `as_of`, falling back to the last lot price.
+pub fn price_on(
+    conn: &Connection,
+    holding_id: i64,
+    lots: &[HoldingLot],
+    as_of: &str,
+) -> Result<Option<PricePoint>> {
+    let stored = conn
+        .query_row(
+            "SELECT date, price FROM holding_prices WHERE holding_id=?1 AND date <= ?2 ORDER BY date DESC LIMIT 1",
+            params![holding_id, as_of],
+            |row| {
+                Ok(PricePoint {
+                    date: row.get(0)?,
+                    price: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+        .context("Failed to look up price")?;
+
+    let from_lots = lots
+        .iter()
+        .rev()
+        .find(|lot| lot.date.as_str() <= as_of)
+        .map(|lot| PricePoint {
+            date: lot.date.clone(),
+            price: lot.price,
+        });
+
+    Ok(match (stored, from_lots) {
+        (Some(stored), Some(lot)) if lot.date > stored.date => Some(lot),
+        (Some(stored), _) => Some(stored),
+        (None, lot) => lot,
+    })
+}
+
+pub fn valuation(conn: &Connection, holding: Holding, as_of: &str) -> Result<HoldingValuation> {
+    let lots = get_lots(conn, holding.id)?;
+    let open = open_lots(&lots, as_of);
+
+    let units: f64 = open.iter().map(|lot| lot.units).sum();
+    let cost_basis: f64 = open.iter().map(|lot| lot.units * lot.unit_cost).sum();
+    let price = price_on(conn, holding.id, &lots, as_of)?;
+    let market_value = price
+        .as_ref()
+        .map(|point| point.price * units)
+        .unwrap_or(cost_basis);
+
+    Ok(HoldingValuation {
+        holding,
+        units,
+        cost_basis,
+        price: price.as_ref().map(|point| point.price),
+        price_date: price.map(|point| point.date),
+        market_value,
+        unrealized_gain: market_value - cost_basis,
+    })
+}
+
+pub fn valuations(conn: &Connection, as_of: &str) -> Result<Vec<HoldingValuation>> {
+    get_holdings(conn)?
+        .into_iter()
+        .map(|holding| valuation(conn, holding, as_of))
+        .collect()
+}
+
+/// Total market value of all holdings on `as_of`; the invested side of net worth.
+pub fn invested_value(conn: &Connection, as_of: &str) -> Result<f64> {
+    Ok(valuations(conn, as_of)?
+        .iter()
+        .map(|valuation| valuation.market_value)
+        .sum())
+}
+
+fn map_holding(row: &rusqlite::Row<'_>) -> rusqlite::Result<Holding> {
+    let folio: String = row.get(4)?;
+    Ok(Holding {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        asset_class: AssetClass::parse(&row.get::<_, String>(2)?).unwrap_or(AssetClass::Other),
+        symbol: row.get(3)?,
+        folio: (!folio.is_empty()).then_some(folio),
+        equity_oriented: row.get(5)?,
+    })
+}
+
+/// Whether an ETF's name or symbol points at a domestic equity index rather than
+/// gold, silver, debt or a foreign index.
+pub fn is_equity_etf(name: &str) -> bool {
+    const NON_EQUITY: &[&str] = &[
+        "GOLD",
+        "SILVER",
+        "LIQUID",
+        "GILT",
+        "BOND",
+        "BBETF",
+        "DEBT",
+        "GSEC",
+        "G-SEC",
+        "SDL",
+        "MONEY MARKET",
+        "OVERNIGHT",
+        "NASDAQ",
+        "MON100",
+        "FANG",
+        "S&P",
+        "HANG SENG",
+        "HNGSNG",
+        "INTERNATIONAL",
+        "GLOBAL",
+    ];
+    let upper = name.to_uppercase();
+    !NON_EQUITY.iter().any(|needle| upper.contains(needle))
+}
+
+/// Whether a hybrid scheme's name marks it as equity-oriented (65%+ in equity), as
+/// aggressive hybrid, balanced advantage and equity savings funds are.
+pub fn is_equity_hybrid(name: &str) -> bool {
+    const EQUITY_ORIENTED: &[&str] = &[
+        "AGGRESSIVE",
+        "BALANCED ADVANTAGE",
+        "DYNAMIC ASSET ALLOCATION",
+        "EQUITY SAVINGS",
+        "EQUITY HYBRID",
+        "EQUITY & DEBT",
+    ];
+    let upper = name.to_uppercase();
+    EQUITY_ORIENTED.iter().any(|needle| upper.contains(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+
+    fn lot(kind: LotKind, date: &str, units: f64, price: f64, charges: f64) -> HoldingLot {
+        HoldingLot {
+            id: None,
+            holding_id: 1,
+            kind,
+            date: date.into(),
+            units,
+            price,
+            charges,
+            external_ref: None,
+        }
+    }
+
+    #[test]
+    fn fifo_sells_consume_the_oldest_buys_first() {
+        let lots = vec![
+            lot(LotKind::Buy, "2023-01-10", 10.0, 100.0, 10.0),
+            lot(LotKind::Buy, "2023-06-10", 10.0, 120.0, 0.0),
+            lot(LotKind::Sell, "2024-02-01", 15.0, 150.0, 15.0),
+            lot(LotKind::Buy, "2024-03-01", 5.0, 130.0, 0.0),
+        ];
+
+        let (open, disposals) = match_fifo(&lots, "2024-02-28");
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].date, "2023-06-10");
+        assert!((open[0].units - 5.0).abs() < 1e-9);
+
+        assert_eq!(disposals.len(), 2);
+        assert_eq!(disposals[0].buy_date, "2023-01-10");
+        assert!((disposals[0].units - 10.0).abs() < 1e-9);
+        // Charges are spread across units on both sides.
+        assert!((disposals[0].unit_cost - 101.0).abs() < 1e-9);
+        assert!((disposals[0].unit_proceeds - 149.0).abs() < 1e-9);
+        assert_eq!(disposals[1].buy_date, "2023-06-10");
+        assert!((disposals[1].units - 5.0).abs() < 1e-9);
+
+        assert_eq!(open_lots(&lots, "2024-12-31").len(), 2);
+    }
+
+    #[test]
+    fn values_open_units_at_the_latest_price() {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        let holding = upsert_holding(
+            &conn,
+            &NewHolding {
+                name: "Index Fund".into(),
+                asset_class: AssetClass::EquityFund,
+                symbol: "INF000000001".into(),
+                folio: None,
+                equity_oriented: false,
+            },
+        )
+        .unwrap();
+        let mut buy = lot(LotKind::Buy, "2024-01-05", 10.0, 50.0, 0.0);
+        buy.holding_id = holding.id;
+        add_lot(&conn, &buy).unwrap();
+
+        let csv = b"Date,NAV\n01/02/2024,55\nlast week,99\n2024-03-01,60\n";
+        assert_eq!(import_prices_csv(&conn, holding.id, csv).unwrap(), 2);
+        assert_eq!(get_prices(&conn, holding.id).unwrap().len(), 2);
+
+        let february = valuation(&conn, holding.clone(), "2024-02-15").unwrap();
+        assert_eq!(february.price_date.as_deref(), Some("2024-02-01"));
+        assert!((february.market_value - 550.0).abs() < 1e-9);
+        assert!((february.unrealized_gain - 50.0).abs() < 1e-9);
+
+        // Without a stored price yet, the lot price stands in.
+        let january = valuation(&conn, holding, "2024-01-31").unwrap();
+        assert_eq!(january.price, Some(50.0));
+        assert!((january.market_value - january.cost_basis).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use crate::dto::{IncomeAmount, IncomeSource};
+
+/// Credit totals for a period, split by where the money came from.
+pub struct IncomeBreakdown {
+    pub by_source: Vec<IncomeAmount>,
+    pub total_income: f64,
+    pub refunds: f64,
+    pub transfers_in: f64,
+}
+
+pub fn income_breakdown(conn: &Connection, like: &str) -> Result<IncomeBreakdown> {
+    let mut stmt = conn.prepare(
+        "SELECT description, category, amount FROM transactions WHERE amount > 0 AND date LIKE ?1",
+    )?;
+
+    let rows = stmt
+        .query_map(params![like], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch credits")?;
+
+    let mut totals: BTreeMap<IncomeSource, f64> = BTreeMap::new();
+    for (description, category, amount) in rows {
+        let source = classify(&description, category.as_deref());
+        *totals.entry(source).or_insert(0.0) += amount;
+    }
+
+    // Dividends from a CAS import count too, unless a bank credit that reads as a
+    // dividend already did.
+    let mut stmt = conn.prepare(
+        "SELECT date, amount FROM holding_cash_flows WHERE kind = 'dividend' AND date LIKE ?1",
+    )?;
+    let payouts = stmt
+        .query_map(params![like], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch holding dividends")?;
+    let mut credits = conn.prepare(
+        "SELECT description, category FROM transactions
+         WHERE amount > 0 AND ABS(amount - ?2) < 0.005
+           AND date BETWEEN date(?1, '-7 days') AND date(?1, '+7 days')",
+    )?;
+    for (date, amount) in payouts {
+        let banked = credits
+            .query_map(params![date, amount], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to match holding dividends")?
+            .iter()
+            .any(|(description, category)| {
+                classify(description, category.as_deref()) == IncomeSource::Dividend
+            });
+        if !banked {
+            *totals.entry(IncomeSource::Dividend).or_insert(0.0) += amount;
+        }
+    }
+
+    let mut breakdown = IncomeBreakdown {
+        by_source: Vec::with_capacity(totals.len()),
+        total_income: 0.0,
+        refunds: 0.0,
+        transfers_in: 0.0,
+    };
+
+    for (source, amount) in totals {
+        match source {
+            IncomeSource::Transfer => breakdown.transfers_in += amount,
+            source if source.is_income() => breakdown.total_income += amount,
+            _ => breakdown.refunds += amount,
+        }
+        breakdown.by_source.push(IncomeAmount { source, amount });
+    }
+
+    breakdown
+        .by_source
+        .sort_by(|a, b| b.amount.total_cmp(&a.amount));
+
+    Ok(breakdown)
+}
+
+/// Classifies a credit, preferring the user's category over description heuristics.
+pub fn classify(description: &str, category: Option<&str>) -> IncomeSource {
+    if let Some(source) = category.and_then(IncomeSource::from_category) {
+        return source;
+    }
+
+    let upper = description.to_uppercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| upper.contains(needle));
+
+    if has(&["REFUND", "REVERSAL", "CASHBACK", "CHARGEBACK"]) {
+        IncomeSource::Refund
+    } else if has(&["REIMB", "EXPENSE CLAIM"]) {
+        IncomeSource::Reimbursement
+    } else if has(&["SALARY", "SAL CREDIT", "PAYROLL"]) {
+        IncomeSource::Salary
+    } else if has(&["DIVIDEND", "DIV ", "ACH C-"]) {
+        IncomeSource::Dividend
+    } else if has(&["INTEREST", "INT.PD", "INT PD", "INT CR"]) {
+        IncomeSource::Interest
+    } else if has(&[
+        "SELF",
+        "OWN ACCOUNT",
+        "SWEEP",
+        "FD CLOSURE",
+        "TRANSFER FROM",
+    ]) {
+        IncomeSource::Transfer
+    } else {
+        IncomeSource::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+
+    #[test]
+    fn separates_salary_from_refunds_and_transfers() {
+        assert_eq!(
+            classify("NEFT ACME CORP SALARY JAN", None),
+            IncomeSource::Salary
+        );
+        assert_eq!(classify("AMAZON REFUND 40213", None), IncomeSource::Refund);
+        assert_eq!(classify("IMPS SELF TRANSFER", None), IncomeSource::Transfer);
+        assert_eq!(classify("UPI FROM RAVI", None), IncomeSource::Other);
+        // The user's category wins over the description.
+        assert_eq!(
+            classify("AMAZON REFUND 40213", Some("Salary")),
+            IncomeSource::Salary
+        );
+
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        for (description, amount) in [
+            ("NEFT ACME CORP SALARY JAN", 90000.0),
+            ("SB INTEREST CREDIT", 500.0),
+            ("ACH C- HDFC AMC DIVIDEND", 250.0),
+            ("AMAZON REFUND 40213", 1200.0),
+            ("IMPS SELF TRANSFER", 20000.0),
+            ("SWIGGY ORDER", -450.0),
+        ] {
+            conn.execute(
+                "INSERT INTO transactions (date, description, amount, flow)
+                 VALUES ('2025-01-10', ?1, ?2, 'credit')",
+                params![description, amount],
+            )
+            .unwrap();
+        }
+
+        // A CAS dividend already seen as a dividend credit is not counted twice; a
+        // credit of the same amount that is not a dividend does not hide one.
+        conn.execute(
+            "INSERT INTO holdings (id, name, asset_class, symbol) VALUES (1, 'Fund', 'equity_fund', 'INF1')",
+            [],
+        )
+        .unwrap();
+        for (date, amount) in [
+            ("2025-01-08", 500.0),
+            ("2025-01-12", 250.0),
+            ("2025-01-20", 80.0),
+        ] {
+            conn.execute(
+                "INSERT INTO holding_cash_flows (holding_id, kind, date, amount) VALUES (1, 'dividend', ?1, ?2)",
+                params![date, amount],
+            )
+            .unwrap();
+        }
+
+        let breakdown = income_breakdown(&conn, "2025-01-%").unwrap();
+        assert_eq!(breakdown.total_income, 91330.0);
+        assert_eq!(breakdown.refunds, 1200.0);
+        assert_eq!(breakdown.transfers_in, 20000.0);
+        assert_eq!(breakdown.by_source[0].source, IncomeSource::Salary);
+    }
+}
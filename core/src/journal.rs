@@ -0,0 +1,463 @@
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    categorization,
+    dto::JournalEntry,
+    sql::{column_names, from_json, quote, to_json},
+};
+
+/// Slices an operation may journal, as (table, key column). Undo and redo refuse to
+/// write anything else, whatever a stored entry says.
+const JOURNALED: &[(&str, &str)] = &[
+    ("inbox", "temp_id"),
+    ("transactions", "id"),
+    ("categorization_memory", "token"),
+    ("categorization_provenance", "token"),
+    ("categorization_provenance", "transaction_id"),
+    ("budgets", "category"),
+    ("budget_thresholds", "category"),
+];
+/// Tables linking to a transaction by `transaction_id`. They are not journaled, so
+/// deletes, undo and redo refuse to remove a transaction any of them still points
+/// at.
+const TRANSACTION_LINKS: &[&str] = &[
+    "tax_tags",
+    "card_transactions",
+    "card_payments",
+    "goal_contributions",
+    "loan_payments",
+    "loan_prepayments",
+    "broker_fundings",
+];
+/// Undo history kept; older entries are dropped.
+const MAX_ENTRIES: i64 = 100;
+
+/// The rows of `table` whose `column` equals `key`, before and after an operation.
+#[derive(Serialize, Deserialize)]
+struct Slice {
+    table: String,
+    column: String,
+    key: Value,
+    columns: Vec<String>,
+    before: Vec<Vec<Value>>,
+    after: Vec<Vec<Value>>,
+}
+
+/// Collects the rows an operation touches. Snapshot each slice with `track` before
+/// changing it, then call `finish` on the same transaction once the operation ran:
+/// undo writes the snapshots back, redo the rows as they were left.
+#[derive(Default)]
+pub struct Recorder {
+    slices: Vec<Slice>,
+}
+
+impl Recorder {
+    /// Snapshots the rows of `table` whose `column` equals `key`. Tracking a slice
+    /// twice keeps the first snapshot.
+    pub fn track(
+        &mut self,
+        conn: &Connection,
+        table: &str,
+        column: &str,
+        key: impl Into<Value>,
+    ) -> Result<()> {
+        let key = key.into();
+        if self.tracks(table, column, &key) {
+            return Ok(());
+        }
+        let columns = column_names(conn, table)?;
+        let before = select_slice(conn, table, column, &key, &columns)?;
+        self.slices.push(Slice {
+            table: table.to_string(),
+            column: column.to_string(),
+            key,
+            columns,
+            before,
+            after: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Tracks a slice the caller knows was empty, such as a row just inserted under
+    /// a fresh id.
+    pub fn track_inserted(
+        &mut self,
+        conn: &Connection,
+        table: &str,
+        column: &str,
+        key: impl Into<Value>,
+    ) -> Result<()> {
+        let key = key.into();
+        if self.tracks(table, column, &key) {
+            return Ok(());
+        }
+        self.slices.push(Slice {
+            table: table.to_string(),
+            column: column.to_string(),
+            key,
+            columns: column_names(conn, table)?,
+            before: Vec::new(),
+            after: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Snapshots the memory tokens `learn` or `unlearn` touch for `description`, with
+    /// their provenance.
+    pub fn track_learning(&mut self, conn: &Connection, description: &str) -> Result<()> {
+        for token in categorization::tokenize(description) {
+            self.track(conn, "categorization_memory", "token", token.as_str())?;
+            self.track(conn, "categorization_provenance", "token", token)?;
+        }
+        Ok(())
+    }
+
+    /// Journals the operation as the newest undo step and clears the redo stack.
+    /// Slices the operation left unchanged are dropped; if none changed, nothing is
+    /// journaled.
+    pub fn finish(self, conn: &Connection, operation: &str, label: &str) -> Result<()> {
+        let mut changed = Vec::with_capacity(self.slices.len());
+        for mut slice in self.slices {
+            slice.after = select_slice(
+                conn,
+                &slice.table,
+                &slice.column,
+                &slice.key,
+                &slice.columns,
+            )?;
+            if slice.after != slice.before {
+                changed.push(slice);
+            }
+        }
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        conn.execute("DELETE FROM undo_journal WHERE undone = 1", [])
+            .context("Failed to clear redo history")?;
+        conn.execute(
+            "INSERT INTO undo_journal (operation, label, slices_json) VALUES (?1, ?2, ?3)",
+            params![operation, label, serde_json::to_string(&changed)?],
+        )
+        .context("Failed to journal operation")?;
+        conn.execute(
+            "DELETE FROM undo_journal WHERE id NOT IN
+             (SELECT id FROM undo_journal ORDER BY id DESC LIMIT ?1)",
+            params![MAX_ENTRIES],
+        )
+        .context("Failed to trim undo history")?;
+        Ok(())
+    }
+
+    fn tracks(&self, table: &str, column: &str, key: &Value) -> bool {
+        self.slices
+            .iter()
+            .any(|slice| slice.table == table && slice.column == column && slice.key == *key)
+    }
+}
+
+/// Newest first.
+pub fn list(conn: &Connection) -> Result<Vec<JournalEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, occurred_at, operation, label, undone FROM undo_journal ORDER BY id DESC",
+    )?;
+    let entries = stmt
+        .query_map([], entry_from_row)?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch undo history")?;
+    Ok(entries)
+}
+
+/// Reverts the newest operation not yet undone. Returns `None` when there is
+/// nothing to undo.
+pub fn undo(conn: &Connection) -> Result<Option<JournalEntry>> {
+    step(conn, false)
+}
+
+/// Re-applies the most recently undone operation. Returns `None` when there is
+/// nothing to redo.
+pub fn redo(conn: &Connection) -> Result<Option<JournalEntry>> {
+    step(conn, true)
+}
+
+/// Writes one entry's slices back to one side of the operation. Every slice must
+/// still look like the other side: a row changed since by something the journal
+/// did not see fails the whole step rather than being overwritten, as does removing
+/// a transaction that a table in `TRANSACTION_LINKS` still points at. Run it on a
+/// transaction so a failure leaves the vault untouched.
+fn step(conn: &Connection, redo: bool) -> Result<Option<JournalEntry>> {
+    let sql = if redo {
+        "SELECT id, occurred_at, operation, label, undone, slices_json FROM undo_journal
+         WHERE undone = 1 ORDER BY id LIMIT 1"
+    } else {
+        "SELECT id, occurred_at, operation, label, undone, slices_json FROM undo_journal
+         WHERE undone = 0 ORDER BY id DESC LIMIT 1"
+    };
+    let Some((mut entry, slices)) = conn
+        .query_row(sql, [], |row| {
+            Ok((entry_from_row(row)?, row.get::<_, String>(5)?))
+        })
+        .optional()
+        .context("Failed to read undo history")?
+    else {
+        return Ok(None);
+    };
+    let slices: Vec<Slice> =
+        serde_json::from_str(&slices).context("Undo journal entry is corrupt")?;
+
+    let action = if redo { "redo" } else { "undo" };
+    for slice in &slices {
+        if !JOURNALED.contains(&(slice.table.as_str(), slice.column.as_str())) {
+            bail!("Undo journal entry is corrupt");
+        }
+        let expected = if redo { &slice.before } else { &slice.after };
+        let current = select_slice(
+            conn,
+            &slice.table,
+            &slice.column,
+            &slice.key,
+            &slice.columns,
+        )?;
+        if current != *expected {
+            bail!(
+                "Cannot {action} \"{}\": the data it changed has been modified since",
+                entry.label
+            );
+        }
+        let target = if redo { &slice.after } else { &slice.before };
+        if slice.table == "transactions" && target.is_empty() && !current.is_empty() {
+            if let Some(table) = transaction_link(conn, &slice.key)? {
+                bail!(
+                    "Cannot {action} \"{}\": a transaction it would remove is referenced by {table}",
+                    entry.label
+                );
+            }
+        }
+    }
+    for slice in &slices {
+        write_slice(conn, slice, if redo { &slice.after } else { &slice.before })?;
+    }
+
+    conn.execute(
+        "UPDATE undo_journal SET undone = ?1 WHERE id = ?2",
+        params![!redo, entry.id],
+    )
+    .context("Failed to update undo history")?;
+    entry.undone = !redo;
+    Ok(Some(entry))
+}
+
+/// The first table still linking to transaction `id`, if any.
+pub fn transaction_link(conn: &Connection, id: &Value) -> Result<Option<&'static str>> {
+    let id = from_json(id)?;
+    for table in TRANSACTION_LINKS {
+        let linked: bool = conn.query_row(
+            &format!(
+                "SELECT EXISTS (SELECT 1 FROM {} WHERE transaction_id = ?1)",
+                quote(table)
+            ),
+            params![id],
+            |row| row.get(0),
+        )?;
+        if linked {
+            return Ok(Some(table));
+        }
+    }
+    Ok(None)
+}
+
+fn entry_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<JournalEntry> {
+    Ok(JournalEntry {
+        id: row.get(0)?,
+        occurred_at: row.get(1)?,
+        operation: row.get(2)?,
+        label: row.get(3)?,
+        undone: row.get(4)?,
+    })
+}
+
+fn select_slice(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    key: &Value,
+    columns: &[String],
+) -> Result<Vec<Vec<Value>>> {
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {} = ?1 ORDER BY rowid",
+        columns
+            .iter()
+            .map(|column| quote(column))
+            .collect::<Vec<_>>()
+            .join(", "),
+        quote(table),
+        quote(column)
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(params![from_json(key)?])?;
+    let mut slice = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut values = Vec::with_capacity(columns.len());
+        for index in 0..columns.len() {
+            values.push(to_json(row.get_ref(index)?));
+        }
+        slice.push(values);
+    }
+    Ok(slice)
+}
+
+fn write_slice(conn: &Connection, slice: &Slice, rows: &[Vec<Value>]) -> Result<()> {
+    conn.execute(
+        &format!(
+            "DELETE FROM {} WHERE {} = ?1",
+            quote(&slice.table),
+            quote(&slice.column)
+        ),
+        params![from_json(&slice.key)?],
+    )
+    .with_context(|| format!("Failed to clear {} rows", slice.table))?;
+
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote(&slice.table),
+        slice
+            .columns
+            .iter()
+            .map(|column| quote(column))
+            .collect::<Vec<_>>()
+            .join(", "),
+        vec!["?"; slice.columns.len()].join(", ")
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    for row in rows {
+        let values = row.iter().map(from_json).collect::<Result<Vec<_>>>()?;
+        stmt.execute(params_from_iter(values))
+            .with_context(|| format!("Failed to restore {} row", slice.table))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{categorization::CategorizationMemory, storage};
+
+    fn categorize(conn: &Connection, memory: &mut CategorizationMemory, category: &str) {
+        let tx = conn.unchecked_transaction().unwrap();
+        let mut journal = Recorder::default();
+        journal.track(&tx, "inbox", "temp_id", "t1").unwrap();
+        journal.track_learning(&tx, "SWIGGY ORDER").unwrap();
+        tx.execute(
+            "UPDATE inbox SET suggested_category = ?1 WHERE temp_id = 't1'",
+            params![category],
+        )
+        .unwrap();
+        memory.learn(&tx, "SWIGGY ORDER", category, 1).unwrap();
+        journal
+            .finish(
+                &tx,
+                "set_inbox_category",
+                &format!("Categorize as {category}"),
+            )
+            .unwrap();
+        tx.commit().unwrap();
+    }
+
+    fn state(conn: &Connection) -> (Option<String>, Option<String>) {
+        let inbox = conn
+            .query_row(
+                "SELECT suggested_category FROM inbox WHERE temp_id = 't1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let memory = conn
+            .query_row(
+                "SELECT category FROM categorization_memory WHERE token = 'swiggy'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap();
+        (inbox, memory)
+    }
+
+    #[test]
+    fn undoes_and_redoes_including_learning() {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO inbox (temp_id, date, description, amount, flow)
+             VALUES ('t1', '2024-01-05', 'SWIGGY ORDER', -420.0, 'debit')",
+            [],
+        )
+        .unwrap();
+        let mut memory = CategorizationMemory::default();
+
+        categorize(&conn, &mut memory, "Food");
+        categorize(&conn, &mut memory, "Travel");
+        assert_eq!(state(&conn), (Some("Travel".into()), Some("Travel".into())));
+
+        assert_eq!(undo(&conn).unwrap().unwrap().label, "Categorize as Travel");
+        assert_eq!(state(&conn), (Some("Food".into()), Some("Food".into())));
+        undo(&conn).unwrap();
+        assert_eq!(state(&conn), (None, None));
+        assert!(undo(&conn).unwrap().is_none());
+
+        redo(&conn).unwrap();
+        assert_eq!(state(&conn), (Some("Food".into()), Some("Food".into())));
+
+        // A change the journal never saw blocks the redo instead of being lost.
+        conn.execute(
+            "UPDATE inbox SET suggested_category = 'Rent' WHERE temp_id = 't1'",
+            [],
+        )
+        .unwrap();
+        assert!(redo(&conn).is_err());
+        assert_eq!(state(&conn), (Some("Rent".into()), Some("Food".into())));
+
+        // A new operation discards what is left to redo.
+        categorize(&conn, &mut memory, "Groceries");
+        assert!(redo(&conn).unwrap().is_none());
+        assert_eq!(list(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn refuses_to_remove_a_linked_transaction() {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        let tx = conn.unchecked_transaction().unwrap();
+        let mut journal = Recorder::default();
+        tx.execute(
+            "INSERT INTO transactions (id, date, description, amount, flow)
+             VALUES (7, '2024-01-05', 'LIC PREMIUM', -12000.0, 'debit')",
+            [],
+        )
+        .unwrap();
+        journal
+            .track_inserted(&tx, "transactions", "id", 7)
+            .unwrap();
+        journal
+            .finish(&tx, "commit_inbox", "Commit 1 inbox item(s)")
+            .unwrap();
+        tx.commit().unwrap();
+
+        conn.execute(
+            "INSERT INTO tax_tags (transaction_id, section) VALUES (7, '80C')",
+            [],
+        )
+        .unwrap();
+        let error = undo(&conn).unwrap_err();
+        assert!(error.to_string().contains("tax_tags"), "{error}");
+
+        conn.execute("DELETE FROM tax_tags", []).unwrap();
+        assert!(undo(&conn).unwrap().is_some());
+        let left: i64 = conn
+            .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{anyhow, bail, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+
+use crate::key_provider::VaultKey;
+
+pub const KEY_LEN: usize = 32;
+pub const SALT_LEN: usize = 16;
+
+/// Argon2id cost parameters. Persisted next to whatever they protect so the secret
+/// stays derivable if the defaults are raised later; bounded on read so a crafted
+/// file cannot demand unbounded memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    pub const DEFAULT: Self = Self {
+        memory_kib: 64 * 1024,
+        iterations: 3,
+        parallelism: 1,
+    };
+
+    pub fn validate(self) -> Result<Self> {
+        if !(8..=1024 * 1024).contains(&self.memory_kib)
+            || !(1..=16).contains(&self.iterations)
+            || !(1..=16).contains(&self.parallelism)
+        {
+            bail!("Unsupported key derivation parameters");
+        }
+        Ok(self)
+    }
+}
+
+pub fn derive_key(secret: &[u8], salt: &[u8], params: KdfParams) -> Result<VaultKey> {
+    let params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(KEY_LEN),
+    )
+    .map_err(|error| anyhow!("Invalid key derivation parameters: {error}"))?;
+    let mut key = VaultKey::zeroed();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(secret, salt, key.as_mut_bytes())
+        .map_err(|error| anyhow!("Key derivation failed: {error}"))?;
+    Ok(key)
+}
@@ -0,0 +1,635 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::kdf::{derive_key, KdfParams, KEY_LEN, SALT_LEN};
+
+const SERVICE: &str = "com.artha.vault";
+const ACCOUNT: &str = "vault-encryption-key";
+const PENDING_ACCOUNT: &str = "vault-encryption-key.pending";
+const DEFAULT_ENV_VAR: &str = "ARTHA_VAULT_KEY";
+
+/// The 32-byte vault key. Wiped from memory on drop, compared in constant time, and
+/// never printed by `Debug`.
+#[derive(Clone)]
+pub struct VaultKey([u8; KEY_LEN]);
+
+impl VaultKey {
+    /// `None` unless `bytes` is exactly 32 bytes long.
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != KEY_LEN {
+            return None;
+        }
+        let mut key = Self::zeroed();
+        key.0.copy_from_slice(bytes);
+        Some(key)
+    }
+
+    pub fn random() -> Self {
+        let mut key = Self::zeroed();
+        OsRng.fill_bytes(&mut key.0);
+        key
+    }
+
+    pub fn as_bytes(&self) -> &[u8; KEY_LEN] {
+        &self.0
+    }
+
+    pub(crate) fn zeroed() -> Self {
+        Self([0; KEY_LEN])
+    }
+
+    /// Lets a key be derived in place rather than copied in.
+    pub(crate) fn as_mut_bytes(&mut self) -> &mut [u8; KEY_LEN] {
+        &mut self.0
+    }
+}
+
+/// Takes ownership of the bytes; copies the caller made earlier are not wiped.
+impl From<[u8; KEY_LEN]> for VaultKey {
+    fn from(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Drop for VaultKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl PartialEq for VaultKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+    }
+}
+
+impl Eq for VaultKey {}
+
+impl fmt::Debug for VaultKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VaultKey(<redacted>)")
+    }
+}
+
+/// Which key a provider operation targets. `Pending` holds the next key while a
+/// rotation is in flight, so a crash between re-keying the database and saving the
+/// key never loses the only working key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySlot {
+    Current,
+    Pending,
+}
+
+impl KeySlot {
+    fn account(self) -> &'static str {
+        match self {
+            KeySlot::Current => ACCOUNT,
+            KeySlot::Pending => PENDING_ACCOUNT,
+        }
+    }
+}
+
+/// Source of the 32-byte vault key.
+pub trait KeyProvider: Send + Sync {
+    /// Short name used in error messages.
+    fn name(&self) -> &'static str;
+
+    /// The key in `slot`, `None` if there is none and an error if it is unreadable
+    /// or malformed.
+    fn load(&self, slot: KeySlot) -> Result<Option<VaultKey>>;
+
+    /// Creates, persists and returns a fresh key in `slot`.
+    fn create(&self, slot: KeySlot) -> Result<VaultKey>;
+
+    fn delete(&self, slot: KeySlot) -> Result<()>;
+
+    /// Makes the pending key current.
+    fn promote(&self) -> Result<()>;
+}
+
+/// How the shell obtains the vault key, parsed from `ARTHA_KEY_PROVIDER`:
+/// `keychain`, `secret-service`, `file:<path>`, `passphrase` or `env[:<VAR>]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum KeyProviderConfig {
+    /// macOS Keychain.
+    Keychain,
+    /// freedesktop Secret Service (GNOME Keyring, KWallet) via `secret-tool`.
+    SecretService,
+    /// Key files in a directory, readable only by the current user.
+    File { dir: PathBuf },
+    /// Derived from a passphrase with Argon2id; the salt is stored beside the vault.
+    Passphrase,
+    /// Hex key in an environment variable. Read-only; meant for CI and tests.
+    Env { variable: String },
+}
+
+impl KeyProviderConfig {
+    pub fn platform_default() -> Self {
+        if cfg!(target_os = "macos") {
+            KeyProviderConfig::Keychain
+        } else {
+            KeyProviderConfig::SecretService
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        let (kind, argument) = match raw.split_once(':') {
+            Some((kind, argument)) => (kind, Some(argument)),
+            None => (raw, None),
+        };
+        Ok(match (kind.trim(), argument) {
+            ("keychain", None) => KeyProviderConfig::Keychain,
+            ("secret-service", None) => KeyProviderConfig::SecretService,
+            ("file", Some(dir)) if !dir.is_empty() => KeyProviderConfig::File { dir: dir.into() },
+            ("passphrase", None) => KeyProviderConfig::Passphrase,
+            ("env", None) => KeyProviderConfig::Env {
+                variable: DEFAULT_ENV_VAR.into(),
+            },
+            ("env", Some(variable)) if !variable.is_empty() => KeyProviderConfig::Env {
+                variable: variable.into(),
+            },
+            _ => bail!("Unknown key provider: {raw}"),
+        })
+    }
+
+    /// Builds the provider for the vault at `vault`. `passphrase` is required by
+    /// (and only used for) the passphrase provider.
+    pub fn build(&self, vault: &Path, passphrase: Option<&str>) -> Result<Box<dyn KeyProvider>> {
+        Ok(match self {
+            KeyProviderConfig::Keychain => keychain_provider()?,
+            KeyProviderConfig::SecretService => Box::new(Stored {
+                name: "Secret Service",
+                store: SecretTool,
+            }),
+            KeyProviderConfig::File { dir } => Box::new(Stored {
+                name: "key file",
+                store: FileStore { dir: dir.clone() },
+            }),
+            KeyProviderConfig::Passphrase => {
+                let passphrase = passphrase
+                    .filter(|passphrase| !passphrase.is_empty())
+                    .ok_or_else(|| anyhow!("A passphrase is required to unlock this vault"))?;
+                Box::new(PassphraseKeyProvider {
+                    passphrase: Zeroizing::new(passphrase.to_string()),
+                    salt_path: salt_path(vault),
+                })
+            }
+            KeyProviderConfig::Env { variable } => Box::new(EnvKeyProvider {
+                variable: variable.clone(),
+            }),
+        })
+    }
+}
+
+/// Returns the vault key, creating one only when there is no vault yet. A missing or
+/// malformed key next to an existing vault is an error: a fresh key could never open
+/// that file.
+pub fn get_or_create_vault_key(provider: &dyn KeyProvider, vault_exists: bool) -> Result<VaultKey> {
+    match provider.load(KeySlot::Current) {
+        Ok(Some(key)) => Ok(key),
+        Ok(None) if vault_exists => bail!(
+            "Vault exists but its key is missing from the {}",
+            provider.name()
+        ),
+        Err(error) if vault_exists => Err(error),
+        Ok(None) => provider.create(KeySlot::Current),
+        Err(error) => {
+            // No vault depends on the unreadable key, so it is safe to replace.
+            log::warn!("Replacing unusable vault key: {error}");
+            provider.delete(KeySlot::Current)?;
+            provider.create(KeySlot::Current)
+        }
+    }
+}
+
+fn parse_key(bytes: &[u8], source: &str) -> Result<VaultKey> {
+    VaultKey::from_slice(bytes)
+        .ok_or_else(|| anyhow!("Key in {source} is malformed ({} bytes)", bytes.len()))
+}
+
+/// Backends that can hold an opaque secret per account.
+trait SecretStore: Send + Sync {
+    fn get(&self, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>>;
+    fn set(&self, account: &str, secret: &[u8]) -> Result<()>;
+    fn delete(&self, account: &str) -> Result<()>;
+}
+
+/// A provider holding random keys in a [`SecretStore`].
+struct Stored<S> {
+    name: &'static str,
+    store: S,
+}
+
+impl<S: SecretStore> KeyProvider for Stored<S> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn load(&self, slot: KeySlot) -> Result<Option<VaultKey>> {
+        self.store
+            .get(slot.account())?
+            .map(|bytes| parse_key(&bytes, self.name))
+            .transpose()
+    }
+
+    fn create(&self, slot: KeySlot) -> Result<VaultKey> {
+        let key = VaultKey::random();
+        self.store.set(slot.account(), key.as_bytes())?;
+        Ok(key)
+    }
+
+    fn delete(&self, slot: KeySlot) -> Result<()> {
+        self.store.delete(slot.account())
+    }
+
+    fn promote(&self) -> Result<()> {
+        let key = self
+            .load(KeySlot::Pending)?
+            .ok_or_else(|| anyhow!("No pending vault key to promote"))?;
+        self.store.set(ACCOUNT, key.as_bytes())?;
+        self.store.delete(PENDING_ACCOUNT)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_provider() -> Result<Box<dyn KeyProvider>> {
+    Ok(Box::new(Stored {
+        name: "Keychain",
+        store: Keychain,
+    }))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain_provider() -> Result<Box<dyn KeyProvider>> {
+    bail!("The Keychain key provider is only available on macOS")
+}
+
+#[cfg(target_os = "macos")]
+struct Keychain;
+
+#[cfg(target_os = "macos")]
+impl SecretStore for Keychain {
+    fn get(&self, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        use security_framework::passwords::get_generic_password;
+        // errSecItemNotFound
+        const NOT_FOUND: i32 = -25300;
+
+        match get_generic_password(SERVICE, account) {
+            Ok(secret) => Ok(Some(Zeroizing::new(secret))),
+            Err(error) if error.code() == NOT_FOUND => Ok(None),
+            Err(error) => Err(anyhow!("Keychain read failed: {error}")),
+        }
+    }
+
+    fn set(&self, account: &str, secret: &[u8]) -> Result<()> {
+        security_framework::passwords::set_generic_password(SERVICE, account, secret)
+            .map_err(|error| anyhow!("Keychain write failed: {error}"))
+    }
+
+    fn delete(&self, account: &str) -> Result<()> {
+        let _ = security_framework::passwords::delete_generic_password(SERVICE, account);
+        Ok(())
+    }
+}
+
+/// Secret Service through libsecret's `secret-tool`, which ships with GNOME and
+/// talks to whichever keyring daemon is running. Keys are stored hex-encoded since
+/// the CLI deals in text.
+struct SecretTool;
+
+impl SecretTool {
+    fn run(&self, args: &[&str], input: Option<&str>) -> Result<std::process::Output> {
+        use std::{
+            io::Write,
+            process::{Command, Stdio},
+        };
+
+        let mut child = Command::new("secret-tool")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run secret-tool (is libsecret installed?)")?;
+        if let Some(input) = input {
+            child
+                .stdin
+                .take()
+                .context("secret-tool stdin unavailable")?
+                .write_all(input.as_bytes())?;
+        }
+        child.wait_with_output().context("secret-tool failed")
+    }
+}
+
+impl SecretStore for SecretTool {
+    fn get(&self, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        let output = self.run(&["lookup", "service", SERVICE, "account", account], None)?;
+        let stored = Zeroizing::new(output.stdout);
+        let stored = stored.trim_ascii();
+        if !output.status.success() || stored.is_empty() {
+            return Ok(None);
+        }
+        hex::decode(stored)
+            .map(|secret| Some(Zeroizing::new(secret)))
+            .context("Secret Service entry is not valid hex")
+    }
+
+    fn set(&self, account: &str, secret: &[u8]) -> Result<()> {
+        let output = self.run(
+            &[
+                "store",
+                "--label=Artha vault key",
+                "service",
+                SERVICE,
+                "account",
+                account,
+            ],
+            Some(&Zeroizing::new(hex::encode(secret))),
+        )?;
+        if !output.status.success() {
+            bail!(
+                "Secret Service write failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    fn delete(&self, account: &str) -> Result<()> {
+        self.run(&["clear", "service", SERVICE, "account", account], None)?;
+        Ok(())
+    }
+}
+
+/// One raw key file per slot, created with owner-only permissions.
+struct FileStore {
+    dir: PathBuf,
+}
+
+impl SecretStore for FileStore {
+    fn get(&self, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        match fs::read(self.dir.join(account)) {
+            Ok(secret) => Ok(Some(Zeroizing::new(secret))),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error).context("Failed to read key file"),
+        }
+    }
+
+    fn set(&self, account: &str, secret: &[u8]) -> Result<()> {
+        use std::io::Write;
+
+        fs::create_dir_all(&self.dir).context("Failed to create key directory")?;
+        let path = self.dir.join(account);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(secret))
+            .with_context(|| format!("Failed to write key file {}", path.display()))
+    }
+
+    fn delete(&self, account: &str) -> Result<()> {
+        match fs::remove_file(self.dir.join(account)) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(error).context("Failed to delete key file")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SaltFile {
+    salt: String,
+    #[serde(flatten)]
+    params: KdfParams,
+}
+
+/// `vault.db.enc` → `vault.db.enc.kdf`.
+fn salt_path(vault: &Path) -> PathBuf {
+    let mut path = vault.as_os_str().to_owned();
+    path.push(".kdf");
+    PathBuf::from(path)
+}
+
+/// Derives the key from a passphrase. Creating a key means picking a new salt, so a
+/// rotation keeps the passphrase but changes the key.
+struct PassphraseKeyProvider {
+    passphrase: Zeroizing<String>,
+    salt_path: PathBuf,
+}
+
+impl PassphraseKeyProvider {
+    fn path(&self, slot: KeySlot) -> PathBuf {
+        match slot {
+            KeySlot::Current => self.salt_path.clone(),
+            KeySlot::Pending => self.salt_path.with_extension("kdf.pending"),
+        }
+    }
+}
+
+impl KeyProvider for PassphraseKeyProvider {
+    fn name(&self) -> &'static str {
+        "passphrase"
+    }
+
+    fn load(&self, slot: KeySlot) -> Result<Option<VaultKey>> {
+        let raw = match fs::read(self.path(slot)) {
+            Ok(raw) => raw,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error).context("Failed to read passphrase salt"),
+        };
+        let file: SaltFile = serde_json::from_slice(&raw).context("Passphrase salt is corrupt")?;
+        let salt = hex::decode(&file.salt).context("Passphrase salt is corrupt")?;
+        derive_key(self.passphrase.as_bytes(), &salt, file.params.validate()?).map(Some)
+    }
+
+    fn create(&self, slot: KeySlot) -> Result<VaultKey> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let key = derive_key(self.passphrase.as_bytes(), &salt, KdfParams::DEFAULT)?;
+
+        let file = SaltFile {
+            salt: hex::encode(salt),
+            params: KdfParams::DEFAULT,
+        };
+        fs::write(self.path(slot), serde_json::to_vec_pretty(&file)?)
+            .context("Failed to write passphrase salt")?;
+        Ok(key)
+    }
+
+    fn delete(&self, slot: KeySlot) -> Result<()> {
+        match fs::remove_file(self.path(slot)) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(error).context("Failed to delete passphrase salt")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn promote(&self) -> Result<()> {
+        fs::rename(self.path(KeySlot::Pending), self.path(KeySlot::Current))
+            .context("Failed to promote pending passphrase salt")
+    }
+}
+
+/// Reads a hex key from an environment variable. It cannot persist anything, so it
+/// only opens vaults (or creates one when the variable is set); rotation fails.
+struct EnvKeyProvider {
+    variable: String,
+}
+
+impl KeyProvider for EnvKeyProvider {
+    fn name(&self) -> &'static str {
+        "environment"
+    }
+
+    fn load(&self, slot: KeySlot) -> Result<Option<VaultKey>> {
+        if slot == KeySlot::Pending {
+            return Ok(None);
+        }
+        let Ok(raw) = std::env::var(&self.variable).map(Zeroizing::new) else {
+            return Ok(None);
+        };
+        let bytes = hex::decode(raw.trim())
+            .map(Zeroizing::new)
+            .with_context(|| format!("{} is not a hex-encoded key", self.variable))?;
+        parse_key(&bytes, &self.variable).map(Some)
+    }
+
+    fn create(&self, slot: KeySlot) -> Result<VaultKey> {
+        match self.load(slot)? {
+            Some(key) => Ok(key),
+            None => bail!(
+                "Set {} to a 64-character hex key; the environment provider cannot create keys",
+                self.variable
+            ),
+        }
+    }
+
+    fn delete(&self, _slot: KeySlot) -> Result<()> {
+        Ok(())
+    }
+
+    fn promote(&self) -> Result<()> {
+        bail!("The environment key provider cannot rotate keys")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_provider_creates_once_and_rotates() {
+        let dir = std::env::temp_dir().join(format!("artha-keys-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let provider = KeyProviderConfig::parse(&format!("file:{}", dir.display()))
+            .unwrap()
+            .build(Path::new("vault.db.enc"), None)
+            .unwrap();
+
+        let key = get_or_create_vault_key(provider.as_ref(), false).unwrap();
+        assert_eq!(
+            get_or_create_vault_key(provider.as_ref(), true).unwrap(),
+            key
+        );
+
+        let next = provider.create(KeySlot::Pending).unwrap();
+        provider.promote().unwrap();
+        assert_eq!(provider.load(KeySlot::Current).unwrap(), Some(next));
+        assert_eq!(provider.load(KeySlot::Pending).unwrap(), None);
+
+        // A vault exists, so a lost key must not be silently replaced.
+        provider.delete(KeySlot::Current).unwrap();
+        assert!(get_or_create_vault_key(provider.as_ref(), true).is_err());
+
+        assert!(KeyProviderConfig::parse("carrier-pigeon").is_err());
+        assert_eq!(
+            KeyProviderConfig::parse("env").unwrap(),
+            KeyProviderConfig::Env {
+                variable: DEFAULT_ENV_VAR.into()
+            }
+        );
+    }
+
+    #[test]
+    fn debug_output_never_reveals_key_material() {
+        let key = VaultKey::from([0xa5; 32]);
+        let printed = format!("{key:?} {:?}", Some(key.clone()));
+        assert!(
+            !printed.contains("a5") && !printed.contains("165"),
+            "{printed}"
+        );
+
+        // Any `#[derive(Debug)]` type holding a key (or other wiped secret) would
+        // print it, so none may exist; `VaultKey` redacts by hand.
+        const SECRET_TYPES: [&str; 4] = ["VaultKey", "[u8; 32]", "[u8; KEY_LEN]", "Zeroizing<"];
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        for entry in fs::read_dir(src).unwrap() {
+            let path = entry.unwrap().path();
+            let source = fs::read_to_string(&path).unwrap();
+            for (start, _) in source.match_indices("#[derive(") {
+                let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+                if !source[line_start..start].trim().is_empty() {
+                    continue;
+                }
+                let item = &source[start..];
+                let derives = &item[..item.find(')').unwrap()];
+                let end = [item.find("\n}"), item.find(";\n")]
+                    .into_iter()
+                    .flatten()
+                    .min()
+                    .unwrap_or(item.len());
+                let body = &item[..end];
+                assert!(
+                    !derives.contains("Debug")
+                        || !SECRET_TYPES.iter().any(|secret| body.contains(secret)),
+                    "{} derives Debug on an item holding key material:\n{body}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[tokio::test]
+    async fn unlock_recovers_an_interrupted_rotation() {
+        let dir = std::env::temp_dir().join(format!("artha-unlock-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let vault = dir.join("vault.db.enc");
+        let provider = KeyProviderConfig::File {
+            dir: dir.join("keys"),
+        }
+        .build(&vault, None)
+        .unwrap();
+
+        let core = crate::ArthaCore::unlock(vault.clone(), provider.as_ref()).unwrap();
+        let core = core.rotate_key(provider.as_ref()).await.unwrap();
+
+        // Re-key the database but "crash" before promoting the new key.
+        let staged = provider.create(KeySlot::Pending).unwrap();
+        drop(core.rekey(staged.clone()).await.unwrap());
+        drop(core);
+
+        crate::ArthaCore::unlock(vault, provider.as_ref()).unwrap();
+        assert_eq!(provider.load(KeySlot::Current).unwrap(), Some(staged));
+        assert_eq!(provider.load(KeySlot::Pending).unwrap(), None);
+    }
+}
@@ -1,32 +1,96 @@
+mod audit;
+mod backups;
+mod broker_import;
+mod budgets;
+mod capital_gains;
+mod cas_import;
 mod categorization;
+mod credit_cards;
 mod csv_import;
 pub mod dto;
+mod exports;
+mod goals;
+mod holdings;
+mod income;
+mod journal;
+mod kdf;
+pub mod key_provider;
+mod loans;
+mod performance;
+mod periods;
+mod recovery;
+mod reports;
 
 mod settings;
+mod sql;
 mod storage;
+mod tax;
+mod vault_bundle;
 
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use categorization::{CategorizationMemory, SharedMemory};
 use dto::{
-    AppSettings, BudgetConfig, InboxItem, NetWorthPoint, SetCategoryResponse, SummaryResponse,
+    AmortizationRow, AppSettings, AssetClass, AuditEntry, AuditQuery, AuditVerification,
+    BackupPolicy, BackupSnapshot, Broker, BrokerFunding, BrokerImportSummary, BudgetAlert,
+    BudgetConfig, BudgetProposal, CapitalGainsReport, CardMatchSummary, CardOutstanding,
+    CardStatement, CasImportSummary, CreditCard, ExportFilter, ExportFormat, ExportKind, Goal,
+    GoalContribution, GoalProgress, Holding, HoldingLot, HoldingValuation, InboxItem, JournalEntry,
+    LearnedToken, Loan, LoanPrepayment, LoanSummary, NetWorthPoint, NewCreditCard, NewGoal,
+    NewHolding, NewLoan, PerformanceReport, PricePoint, ReportGrouping, ReportResponse,
+    SetCategoryResponse, SummaryResponse, TaxReport, TaxRule, TaxSection, TimeWeightedReturn,
+    TokenProvenance, VaultBundleInfo,
 };
+use key_provider::{KeyProvider, KeySlot, VaultKey};
 use parking_lot::RwLock;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, OptionalExtension};
-use time::{macros::format_description, Date, Month, OffsetDateTime};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::json;
+use time::{macros::format_description, Date, Month, OffsetDateTime, PrimitiveDateTime};
 use tokio::task;
 use uuid::Uuid;
 
 pub struct ArthaCore {
     pool: Pool<SqliteConnectionManager>,
     memory: SharedMemory,
+    path: PathBuf,
+    key: VaultKey,
 }
 
 impl ArthaCore {
-    pub fn bootstrap(path: PathBuf, key: [u8; 32]) -> Result<Self> {
+    /// Opens (or creates) the vault at `path` with the key from `provider`. If the
+    /// current key is rejected but a pending one exists, a key rotation was
+    /// interrupted after re-keying the database; the pending key is then promoted.
+    pub fn unlock(path: PathBuf, provider: &dyn KeyProvider) -> Result<Self> {
+        let key = key_provider::get_or_create_vault_key(provider, path.exists())?;
+        match Self::bootstrap(path.clone(), key) {
+            Ok(core) => {
+                // A rotation that never re-keyed the vault leaves a stale pending key.
+                if let Err(error) = provider.delete(KeySlot::Pending) {
+                    log::warn!("Failed to discard pending vault key: {error}");
+                }
+                Ok(core)
+            }
+            Err(error) => match provider.load(KeySlot::Pending).ok().flatten() {
+                Some(pending) => {
+                    let core = Self::bootstrap(path, pending).map_err(|_| error)?;
+                    provider.promote()?;
+                    Ok(core)
+                }
+                None => Err(error),
+            },
+        }
+    }
+
+    pub fn bootstrap(path: PathBuf, key: VaultKey) -> Result<Self> {
         let pool = storage::connection_pool(&path, &key)?;
         let conn = pool
             .get()
@@ -39,11 +103,14 @@ impl ArthaCore {
         Ok(Self {
             pool,
             memory: Arc::new(RwLock::new(memory)),
+            path,
+            key,
         })
     }
 
-    pub async fn import_csv(&self, bytes: &[u8]) -> Result<Vec<InboxItem>> {
+    pub async fn import_csv(&self, bytes: &[u8], account: Option<&str>) -> Result<Vec<InboxItem>> {
         let payload = bytes.to_vec();
+        let account = account.map(str::to_string);
         let pool = self.pool.clone();
         let memory = self.memory.clone();
 
@@ -65,14 +132,16 @@ impl ArthaCore {
 
                 tx.execute(
                     r#"
-                    INSERT INTO inbox (temp_id, date, description, amount, flow, suggested_category)
-                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                    INSERT INTO inbox (temp_id, date, description, amount, flow, suggested_category, account, from_memory)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
                     ON CONFLICT(temp_id) DO UPDATE SET
                         date=excluded.date,
                         description=excluded.description,
                         amount=excluded.amount,
                         flow=excluded.flow,
-                        suggested_category=excluded.suggested_category
+                        suggested_category=excluded.suggested_category,
+                        account=excluded.account,
+                        from_memory=excluded.from_memory
                     "#,
                     params![
                         temp_id,
@@ -80,7 +149,9 @@ impl ArthaCore {
                         row.description,
                         row.amount,
                         flow_kind.as_str(),
-                        suggestion.clone()
+                        suggestion.clone(),
+                        account,
+                        suggestion.is_some()
                     ],
                 )?;
 
@@ -148,27 +219,61 @@ impl ArthaCore {
 
         task::spawn_blocking(move || {
             let conn = pool.get().context("Checkout failed during category set")?;
-            let affected = conn.execute(
-                "UPDATE inbox SET suggested_category=?1 WHERE temp_id=?2",
-                params![category, temp_id],
-            )?;
+            commit_learning(&conn, &memory, "category change", |tx| {
+                let Some((entry, previous, from_memory)) = tx
+                    .query_row(
+                        "SELECT description, suggested_category, from_memory FROM inbox WHERE temp_id=?1",
+                        params![temp_id],
+                        |row| {
+                            Ok((
+                                row.get::<_, String>(0)?,
+                                row.get::<_, Option<String>>(1)?,
+                                row.get::<_, bool>(2)?,
+                            ))
+                        },
+                    )
+                    .optional()?
+                else {
+                    return Ok(SetCategoryResponse { ok: false });
+                };
+                // Moving an item away from what memory suggested counts against the
+                // suggestion. Learning itself waits for the commit, so a misclick here
+                // teaches nothing.
+                let rejected = previous
+                    .as_deref()
+                    .filter(|previous| from_memory && *previous != category);
 
-            if affected == 0 {
-                return Ok(SetCategoryResponse { ok: false });
-            }
+                let mut journal = journal::Recorder::default();
+                journal.track(tx, "inbox", "temp_id", temp_id.as_str())?;
+                if rejected.is_some() {
+                    journal.track_learning(tx, &entry)?;
+                }
 
-            if let Ok(entry) = conn.query_row(
-                "SELECT description FROM inbox WHERE temp_id=?1",
-                params![temp_id],
-                |row| row.get::<_, String>(0),
-            ) {
-                let mut guard = memory.write();
-                if let Err(error) = guard.learn(&conn, &entry, &category) {
-                    log::warn!("Failed to persist categorization memory: {error}");
+                tx.execute(
+                    "UPDATE inbox SET suggested_category=?1, from_memory=0 WHERE temp_id=?2",
+                    params![category, temp_id],
+                )?;
+                audit::record(
+                    tx,
+                    "set_inbox_category",
+                    &format!("inbox:{temp_id}"),
+                    Some(json!({ "description": entry, "category": previous })),
+                    Some(json!({ "description": entry, "category": category })),
+                )?;
+
+                if let Some(rejected) = rejected {
+                    if let Err(error) = memory.write().unlearn(tx, &entry, rejected) {
+                        log::warn!("Failed to update categorization memory: {error}");
+                    }
                 }
-            }
 
-            Ok::<_, anyhow::Error>(SetCategoryResponse { ok: true })
+                journal.finish(
+                    tx,
+                    "set_inbox_category",
+                    &format!("Categorize \"{entry}\" as {category}"),
+                )?;
+                Ok(SetCategoryResponse { ok: true })
+            })
         })
         .await
         .map_err(|error| anyhow!("Blocking task failed: {error}"))?
@@ -180,55 +285,300 @@ impl ArthaCore {
 
         task::spawn_blocking(move || {
             let conn = pool.get().context("Checkout failed during commit")?;
-            let tx = conn
-                .transaction()
-                .context("Failed to start commit transaction")?;
+            commit_learning(&conn, &memory, "inbox", |tx| {
+                let mut stmt = tx
+                    .prepare(
+                        "SELECT temp_id, date, description, amount, flow, suggested_category, account FROM inbox",
+                    )
+                    .context("Prepare inbox select for commit failed")?;
 
-            let mut stmt = tx
-                .prepare(
-                    "SELECT temp_id, date, description, amount, flow, suggested_category FROM inbox",
-                )
-                .context("Prepare inbox select for commit failed")?;
+                let rows = stmt
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, String>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, f64>(3)?,
+                            row.get::<_, String>(4)?,
+                            row.get::<_, Option<String>>(5)?,
+                            row.get::<_, Option<String>>(6)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()
+                    .context("Hydrate inbox rows for commit failed")?;
 
-            let rows = stmt
-                .query_map([], |row| {
-                    Ok((
-                        row.get::<_, String>(0)?,
-                        row.get::<_, String>(1)?,
-                        row.get::<_, String>(2)?,
-                        row.get::<_, f64>(3)?,
-                        row.get::<_, String>(4)?,
-                        row.get::<_, Option<String>>(5)?,
-                    ))
-                })?
-                .collect::<Result<Vec<_>, _>>()
-                .context("Hydrate inbox rows for commit failed")?;
+                let committed = rows.len();
 
-            let committed = rows.len();
+                let mut guard = memory.write();
+                let mut audited = Vec::with_capacity(committed);
+                let mut journal = journal::Recorder::default();
 
-            let mut guard = memory.write();
+                for (temp_id, date, description, amount, flow, category, account) in rows {
+                    journal.track(tx, "inbox", "temp_id", temp_id.as_str())?;
+                    if category.is_some() {
+                        journal.track_learning(tx, &description)?;
+                    }
+                    tx.execute(
+                        "INSERT INTO transactions (date, description, amount, flow, category, account) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        params![date, description, amount, flow, category, account],
+                    )?;
+                    let id = tx.last_insert_rowid();
+                    journal.track_inserted(tx, "transactions", "id", id)?;
+                    audited.push(json!({
+                        "id": id,
+                        "date": &date,
+                        "description": &description,
+                        "amount": amount,
+                        "category": &category,
+                        "account": &account,
+                    }));
+                    tx.execute(
+                        "DELETE FROM inbox WHERE temp_id=?1",
+                        params![temp_id],
+                    )?;
 
-            for (temp_id, date, description, amount, flow, category) in rows {
-                tx.execute(
-                    "INSERT INTO transactions (date, description, amount, flow, category) VALUES (?1, ?2, ?3, ?4, ?5)",
-                    params![date, description, amount, flow, category],
+                    if let Some(category) = &category {
+                        if let Err(error) = guard.learn(tx, &description, category, id) {
+                            log::warn!("Categorization memory update failed: {error}");
+                        }
+                    }
+                }
+
+                drop(guard);
+
+                if committed > 0 {
+                    audit::record(
+                        tx,
+                        "commit_inbox",
+                        "transactions",
+                        None,
+                        Some(json!({ "transactions": audited })),
+                    )?;
+                }
+                journal.finish(
+                    tx,
+                    "commit_inbox",
+                    &format!("Commit {committed} inbox item(s)"),
                 )?;
+                Ok(committed)
+            })
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Sets the category of every transaction in `ids`; `None` clears it. Unknown ids
+    /// are skipped. Returns the number of transactions updated.
+    pub async fn recategorize_transactions(
+        &self,
+        ids: Vec<i64>,
+        category: Option<&str>,
+    ) -> Result<usize> {
+        let category = category.map(str::to_string);
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during recategorize")?;
+            let tx = conn
+                .unchecked_transaction()
+                .context("Failed to start recategorize transaction")?;
+            let mut journal = journal::Recorder::default();
+            let mut updated = 0;
+            for id in ids {
+                let Some(previous) = tx
+                    .query_row(
+                        "SELECT category FROM transactions WHERE id=?1",
+                        params![id],
+                        |row| row.get::<_, Option<String>>(0),
+                    )
+                    .optional()?
+                else {
+                    continue;
+                };
+                if let Some(table) = journal::transaction_link(&tx, &id.into())? {
+                    bail!("Transaction {id} is linked from {table}; unlink it before deleting");
+                }
+                journal.track(&tx, "transactions", "id", id)?;
                 tx.execute(
-                    "DELETE FROM inbox WHERE temp_id=?1",
-                    params![temp_id],
+                    "UPDATE transactions SET category=?1 WHERE id=?2",
+                    params![category, id],
+                )?;
+                audit::record(
+                    &tx,
+                    "recategorize_transaction",
+                    &format!("transaction:{id}"),
+                    Some(json!({ "category": previous })),
+                    Some(json!({ "category": category })),
                 )?;
+                updated += 1;
+            }
+            journal.finish(
+                &tx,
+                "recategorize_transactions",
+                &format!("Recategorize {updated} transaction(s)"),
+            )?;
+            tx.commit().context("Commit recategorize failed")?;
+            Ok(updated)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
 
-                if let Some(category) = &category {
-                    if let Err(error) = guard.learn(&tx, &description, category) {
-                        log::warn!("Categorization memory update failed: {error}");
-                    }
+    /// Deletes the transactions in `ids`, skipping unknown ones. Returns the number
+    /// deleted. Fails, deleting nothing, if a loan, card, goal, broker or tax record
+    /// still links to one of them.
+    pub async fn delete_transactions(&self, ids: Vec<i64>) -> Result<usize> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during transaction delete")?;
+            let tx = conn
+                .unchecked_transaction()
+                .context("Failed to start transaction delete")?;
+            let mut journal = journal::Recorder::default();
+            let mut deleted = 0;
+            for id in ids {
+                let Some(previous) = tx
+                    .query_row(
+                        "SELECT date, description, amount, category, account FROM transactions WHERE id=?1",
+                        params![id],
+                        |row| {
+                            Ok(json!({
+                                "date": row.get::<_, String>(0)?,
+                                "description": row.get::<_, String>(1)?,
+                                "amount": row.get::<_, f64>(2)?,
+                                "category": row.get::<_, Option<String>>(3)?,
+                                "account": row.get::<_, Option<String>>(4)?,
+                            }))
+                        },
+                    )
+                    .optional()?
+                else {
+                    continue;
+                };
+                if let Some(table) = journal::transaction_link(&tx, &id.into())? {
+                    bail!("Transaction {id} is linked from {table}; unlink it before deleting");
                 }
+                journal.track(&tx, "transactions", "id", id)?;
+                journal.track(&tx, "categorization_provenance", "transaction_id", id)?;
+                tx.execute("DELETE FROM transactions WHERE id=?1", params![id])?;
+                tx.execute(
+                    "DELETE FROM categorization_provenance WHERE transaction_id=?1",
+                    params![id],
+                )?;
+                audit::record(
+                    &tx,
+                    "delete_transaction",
+                    &format!("transaction:{id}"),
+                    Some(previous),
+                    None,
+                )?;
+                deleted += 1;
             }
+            journal.finish(
+                &tx,
+                "delete_transactions",
+                &format!("Delete {deleted} transaction(s)"),
+            )?;
+            tx.commit().context("Commit transaction delete failed")?;
+            Ok(deleted)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_learned_tokens(&self) -> Result<Vec<LearnedToken>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during learned token fetch")?;
+            categorization::learned_tokens(&conn)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Committed transactions that taught `token`, newest first.
+    pub async fn get_token_provenance(&self, token: &str) -> Result<Vec<TokenProvenance>> {
+        let token = token.to_lowercase();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during provenance fetch")?;
+            categorization::provenance(&conn, &token)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Points a learned token at another category.
+    pub async fn update_learned_token(&self, token: &str, category: &str) -> Result<()> {
+        let token = token.to_lowercase();
+        let category = category.to_string();
+        let pool = self.pool.clone();
+        let memory = self.memory.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during learned token update")?;
+            commit_learning(&conn, &memory, "learned token update", |tx| {
+                let previous = categorization::learned_tokens(tx)?
+                    .into_iter()
+                    .find(|learned| learned.token == token)
+                    .ok_or_else(|| anyhow!("No learned token \"{token}\""))?;
+
+                let mut journal = journal::Recorder::default();
+                journal.track(tx, "categorization_memory", "token", token.as_str())?;
+                memory.write().relabel(tx, &token, &category)?;
+                audit::record(
+                    tx,
+                    "update_learned_token",
+                    &format!("token:{token}"),
+                    Some(json!({ "category": previous.category })),
+                    Some(json!({ "category": category })),
+                )?;
+                journal.finish(
+                    tx,
+                    "update_learned_token",
+                    &format!("Map \"{token}\" to {category}"),
+                )?;
+                Ok(())
+            })
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
 
-            drop(guard);
+    /// Forgets a learned token and its provenance.
+    pub async fn delete_learned_token(&self, token: &str) -> Result<()> {
+        let token = token.to_lowercase();
+        let pool = self.pool.clone();
+        let memory = self.memory.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during learned token delete")?;
+            commit_learning(&conn, &memory, "learned token delete", |tx| {
+                let previous = categorization::learned_tokens(tx)?
+                    .into_iter()
+                    .find(|learned| learned.token == token);
 
-            tx.commit().context("Commit inbox transaction failed")?;
-            Ok::<_, anyhow::Error>(committed)
+                let mut journal = journal::Recorder::default();
+                journal.track(tx, "categorization_memory", "token", token.as_str())?;
+                journal.track(tx, "categorization_provenance", "token", token.as_str())?;
+                memory.write().forget(tx, &token)?;
+                if let Some(previous) = previous {
+                    audit::record(
+                        tx,
+                        "delete_learned_token",
+                        &format!("token:{token}"),
+                        Some(json!({ "category": previous.category, "hitCount": previous.hit_count })),
+                        None,
+                    )?;
+                }
+                journal.finish(tx, "delete_learned_token", &format!("Forget \"{token}\""))?;
+                Ok(())
+            })
         })
         .await
         .map_err(|error| anyhow!("Blocking task failed: {error}"))?
@@ -244,14 +594,14 @@ impl ArthaCore {
 
             let total_spend: f64 = conn
                 .query_row(
-                    "SELECT COALESCE(SUM(ABS(amount)), 0) FROM transactions WHERE amount < 0 AND date LIKE ?1",
+                    "SELECT COALESCE(SUM(ABS(amount)), 0) FROM transactions WHERE amount < 0 AND date LIKE ?1 AND id NOT IN (SELECT transaction_id FROM card_payments)",
                     params![&like],
                     |row| row.get(0),
                 )
                 .unwrap_or(0.0);
 
             let mut stmt = conn.prepare(
-                "SELECT COALESCE(category, 'Uncategorized') AS cat, SUM(ABS(amount)) FROM transactions WHERE amount < 0 AND date LIKE ?1 GROUP BY cat ORDER BY SUM(ABS(amount)) DESC",
+                "SELECT COALESCE(category, 'Uncategorized') AS cat, SUM(ABS(amount)) FROM transactions WHERE amount < 0 AND date LIKE ?1 AND id NOT IN (SELECT transaction_id FROM card_payments) GROUP BY cat ORDER BY SUM(ABS(amount)) DESC",
             )?;
             let by_category = stmt
                 .query_map(params![&like], |row| {
@@ -262,31 +612,54 @@ impl ArthaCore {
                 })?
                 .collect::<Result<Vec<_>, _>>()?;
 
-            let mut budget_stmt = conn.prepare(
-                "SELECT b.category, b.cap, COALESCE(spent.total, 0) FROM budgets b LEFT JOIN (SELECT category, SUM(ABS(amount)) AS total FROM transactions WHERE amount < 0 AND date LIKE ?1 GROUP BY category) spent ON spent.category = b.category ORDER BY b.category",
-            )?;
+            let today = OffsetDateTime::now_utc().date();
+            let budgets = budgets::budget_usage(&conn, &month, today)?;
 
-            let budgets = budget_stmt
-                .query_map(params![&like], |row| {
-                    Ok(dto::BudgetUsage {
-                        category: row.get(0)?,
-                        cap: row.get(1)?,
-                        spent: row.get(2)?,
-                    })
-                })?
-                .collect::<Result<Vec<_>, _>>()?;
+            let income = income::income_breakdown(&conn, &like)?;
+            let free_cash_flow = income.total_income - (total_spend - income.refunds);
+            let savings_rate = if income.total_income > 0.0 {
+                free_cash_flow / income.total_income
+            } else {
+                0.0
+            };
 
             Ok::<_, anyhow::Error>(SummaryResponse {
                 month,
                 total_spend,
                 by_category,
                 budgets,
+                total_income: income.total_income,
+                income_by_source: income.by_source,
+                refunds: income.refunds,
+                transfers_in: income.transfers_in,
+                free_cash_flow,
+                savings_rate,
             })
         })
         .await
         .map_err(|error| anyhow!("Blocking task failed: {error}"))?
     }
 
+    /// Builds a spend/income report for `start..=end` (`YYYY-MM-DD`, inclusive) bucketed
+    /// by `grouping`, with comparisons against the previous period and last year.
+    pub async fn get_report(
+        &self,
+        start: &str,
+        end: &str,
+        grouping: ReportGrouping,
+    ) -> Result<ReportResponse> {
+        let start = periods::parse_date(start)?;
+        let end = periods::parse_date(end)?;
+        let pool = self.pool.clone();
+
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during report")?;
+            reports::build_report(&conn, start, end, grouping)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
     pub async fn get_networth_curve(&self) -> Result<Vec<NetWorthPoint>> {
         let pool = self.pool.clone();
 
@@ -336,19 +709,28 @@ impl ArthaCore {
             }
             months.reverse();
 
-            let mut cumulative = 0.0;
+            // Flows before the window still make up the opening balance.
+            let mut cumulative: f64 = monthly_totals
+                .iter()
+                .filter(|(date, _)| months.first().is_some_and(|first| *date < first))
+                .map(|(_, amount)| amount)
+                .sum();
             let mut curve = Vec::with_capacity(months.len());
 
             for date in months {
                 let key = monthly_totals.get(&date).copied().unwrap_or(0.0);
                 cumulative += key;
-                let cash = (cumulative * 0.4).max(0.0);
-                let invested = (cumulative * 0.55).max(0.0);
-                let debt = (-cumulative * 0.2).max(0.0);
+                let month_end = periods::shift_days(periods::add_months(date, 1), -1);
+                let as_of = periods::format_date(month_end.min(now));
+                let invested = holdings::invested_value(&conn, &as_of)?;
+
+                // Loan principal outstanding plus any overdrawn cash.
+                let cash = cumulative.max(0.0);
+                let debt = loans::outstanding_total(&conn, &as_of)? + (-cumulative).max(0.0);
 
                 curve.push(NetWorthPoint {
                     date: date.format(&format)?.to_string(),
-                    net_worth: cumulative,
+                    net_worth: cash + invested - debt,
                     cash,
                     invested,
                     debt,
@@ -364,7 +746,9 @@ impl ArthaCore {
     pub async fn get_app_settings(&self) -> Result<AppSettings> {
         let pool = self.pool.clone();
         task::spawn_blocking(move || {
-            let conn = pool.get().context("Checkout failed during settings fetch")?;
+            let conn = pool
+                .get()
+                .context("Checkout failed during settings fetch")?;
             settings::get_app_settings(&conn)
         })
         .await
@@ -376,8 +760,22 @@ impl ArthaCore {
         let value = value.to_string();
         let pool = self.pool.clone();
         task::spawn_blocking(move || {
-            let conn = pool.get().context("Checkout failed during setting update")?;
-            settings::set_setting(&conn, &key, &value)
+            let conn = pool
+                .get()
+                .context("Checkout failed during setting update")?;
+            let tx = conn
+                .unchecked_transaction()
+                .context("Failed to start setting transaction")?;
+            let previous = settings::get_setting(&tx, &key)?;
+            settings::set_setting(&tx, &key, &value)?;
+            audit::record(
+                &tx,
+                "update_setting",
+                &format!("setting:{key}"),
+                previous.map(|previous| json!({ "value": previous })),
+                Some(json!({ "value": value })),
+            )?;
+            tx.commit().context("Commit setting update failed")
         })
         .await
         .map_err(|error| anyhow!("Blocking task failed: {error}"))?
@@ -398,9 +796,1189 @@ impl ArthaCore {
         let pool = self.pool.clone();
         task::spawn_blocking(move || {
             let conn = pool.get().context("Checkout failed during budget update")?;
-            settings::set_budget(&conn, &category, cap)
+            let tx = conn
+                .unchecked_transaction()
+                .context("Failed to start budget transaction")?;
+            let previous = settings::get_budget(&tx, &category)?;
+            let mut journal = journal::Recorder::default();
+            journal.track(&tx, "budgets", "category", category.as_str())?;
+            settings::set_budget(&tx, &category, cap)?;
+            audit::record(
+                &tx,
+                "set_budget_config",
+                &format!("budget:{category}"),
+                previous.map(|previous| json!({ "cap": previous })),
+                Some(json!({ "cap": cap })),
+            )?;
+            journal.finish(&tx, "set_budget_config", &format!("Set {category} budget"))?;
+            tx.commit().context("Commit budget update failed")
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_budget_config(&self, category: &str) -> Result<()> {
+        let category = category.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during budget delete")?;
+            let tx = conn
+                .unchecked_transaction()
+                .context("Failed to start budget transaction")?;
+            let previous = settings::get_budget(&tx, &category)?;
+            let mut journal = journal::Recorder::default();
+            journal.track(&tx, "budgets", "category", category.as_str())?;
+            journal.track(&tx, "budget_thresholds", "category", category.as_str())?;
+            settings::delete_budget(&tx, &category)?;
+            if let Some(previous) = previous {
+                audit::record(
+                    &tx,
+                    "delete_budget_config",
+                    &format!("budget:{category}"),
+                    Some(json!({ "cap": previous })),
+                    None,
+                )?;
+            }
+            journal.finish(
+                &tx,
+                "delete_budget_config",
+                &format!("Delete {category} budget"),
+            )?;
+            tx.commit().context("Commit budget delete failed")
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Suggests starting budgets from the median spend of the first `months` imported months.
+    pub async fn propose_budgets(&self, months: usize) -> Result<Vec<BudgetProposal>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during budget proposal")?;
+            budgets::propose_budgets(&conn, months)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Creates the given budgets, skipping categories that already have a cap.
+    pub async fn seed_budgets(&self, budgets: Vec<BudgetConfig>) -> Result<usize> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during budget seed")?;
+            let tx = conn
+                .unchecked_transaction()
+                .context("Failed to start budget seed transaction")?;
+            let mut journal = journal::Recorder::default();
+            let mut missing = Vec::new();
+            for budget in &budgets {
+                journal.track(&tx, "budgets", "category", budget.category.as_str())?;
+                if settings::get_budget(&tx, &budget.category)?.is_none() {
+                    missing.push(budget.category.as_str());
+                }
+            }
+            let created = budgets::seed_budgets(&tx, &budgets)?;
+            for category in missing {
+                if let Some(cap) = settings::get_budget(&tx, category)? {
+                    audit::record(
+                        &tx,
+                        "seed_budgets",
+                        &format!("budget:{category}"),
+                        None,
+                        Some(json!({ "cap": cap })),
+                    )?;
+                }
+            }
+            journal.finish(&tx, "seed_budgets", &format!("Create {created} budget(s)"))?;
+            tx.commit().context("Commit budget seed failed")?;
+            Ok(created)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_budget_thresholds(&self, category: &str) -> Result<Vec<f64>> {
+        let category = category.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during threshold fetch")?;
+            budgets::get_thresholds(&conn, &category)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn set_budget_thresholds(&self, category: &str, thresholds: Vec<f64>) -> Result<()> {
+        let category = category.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during threshold update")?;
+            let tx = conn
+                .unchecked_transaction()
+                .context("Failed to start threshold transaction")?;
+            let previous = budgets::get_thresholds(&tx, &category)?;
+            let mut journal = journal::Recorder::default();
+            journal.track(&tx, "budget_thresholds", "category", category.as_str())?;
+            budgets::set_thresholds(&tx, &category, &thresholds)?;
+            audit::record(
+                &tx,
+                "set_budget_thresholds",
+                &format!("budget:{category}"),
+                Some(json!({ "thresholds": previous })),
+                Some(json!({ "thresholds": budgets::get_thresholds(&tx, &category)? })),
+            )?;
+            journal.finish(
+                &tx,
+                "set_budget_thresholds",
+                &format!("Set {category} budget alerts"),
+            )?;
+            tx.commit().context("Commit budget thresholds failed")
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Returns budget thresholds newly crossed in `month` since the last evaluation.
+    pub async fn evaluate_alerts(&self, month: &str) -> Result<Vec<BudgetAlert>> {
+        let month = month.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during alert evaluation")?;
+            let today = OffsetDateTime::now_utc().date();
+            budgets::evaluate_alerts(&conn, &month, today)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Deductions (80C, 80D, 80CCD(1B), HRA, 24(b)) for a financial year such as `FY2024-25`.
+    pub async fn get_tax_report(&self, financial_year: &str) -> Result<TaxReport> {
+        let financial_year = financial_year.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during tax report")?;
+            tax::build_report(&conn, &financial_year)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn export_tax_report_csv(&self, financial_year: &str) -> Result<String> {
+        let report = self.get_tax_report(financial_year).await?;
+        tax::report_csv(&report)
+    }
+
+    pub async fn get_tax_rules(&self) -> Result<Vec<TaxRule>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during tax rule fetch")?;
+            tax::get_rules(&conn)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn add_tax_rule(&self, rule: TaxRule) -> Result<i64> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during tax rule insert")?;
+            tax::add_rule(&conn, &rule)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_tax_rule(&self, id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during tax rule delete")?;
+            tax::delete_rule(&conn, id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Overrides rule matching for one transaction; `None` excludes it from the report.
+    pub async fn tag_tax_transaction(
+        &self,
+        transaction_id: i64,
+        section: Option<TaxSection>,
+    ) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during tax tag")?;
+            tax::tag_transaction(&conn, transaction_id, section)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn clear_tax_tag(&self, transaction_id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during tax tag clear")?;
+            tax::clear_tag(&conn, transaction_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn upsert_holding(&self, holding: NewHolding) -> Result<Holding> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during holding upsert")?;
+            holdings::upsert_holding(&conn, &holding)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_holding(&self, id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during holding delete")?;
+            holdings::delete_holding(&conn, id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Market value, units and cost basis per holding as of `as_of` (defaults to today).
+    pub async fn get_holdings(&self, as_of: Option<&str>) -> Result<Vec<HoldingValuation>> {
+        let as_of = match as_of {
+            Some(as_of) => periods::format_date(periods::parse_date(as_of)?),
+            None => periods::format_date(OffsetDateTime::now_utc().date()),
+        };
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during holdings fetch")?;
+            holdings::valuations(&conn, &as_of)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_holding_lots(&self, holding_id: i64) -> Result<Vec<HoldingLot>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during lots fetch")?;
+            holdings::get_lots(&conn, holding_id)
         })
         .await
         .map_err(|error| anyhow!("Blocking task failed: {error}"))?
     }
+
+    pub async fn add_holding_lot(&self, lot: HoldingLot) -> Result<Option<i64>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during lot insert")?;
+            holdings::add_lot(&conn, &lot)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_holding_lot(&self, id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during lot delete")?;
+            holdings::delete_lot(&conn, id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_holding_prices(&self, holding_id: i64) -> Result<Vec<PricePoint>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during price fetch")?;
+            holdings::get_prices(&conn, holding_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn set_holding_price(&self, holding_id: i64, date: &str, price: f64) -> Result<()> {
+        let date = periods::format_date(periods::parse_date(date)?);
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during price update")?;
+            holdings::set_price(&conn, holding_id, &date, price)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Imports a NAV/price history CSV for one holding; returns the number of points stored.
+    pub async fn import_holding_prices(&self, holding_id: i64, bytes: &[u8]) -> Result<usize> {
+        let payload = bytes.to_vec();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during price import")?;
+            holdings::import_prices_csv(&conn, holding_id, &payload)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Imports the text export of a CAMS/KFintech CAS into holdings, lots and NAVs.
+    pub async fn import_cas(&self, text: &str) -> Result<CasImportSummary> {
+        let text = text.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let schemes = cas_import::parse_cas(&text)?;
+            let conn = pool.get().context("Checkout failed during CAS import")?;
+            cas_import::apply(&conn, &schemes)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Imports a Zerodha Console or Groww tradebook CSV as equity lots.
+    pub async fn import_tradebook(&self, bytes: &[u8]) -> Result<BrokerImportSummary> {
+        let payload = bytes.to_vec();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let tradebook = broker_import::parse_tradebook(&payload)?;
+            let conn = pool
+                .get()
+                .context("Checkout failed during tradebook import")?;
+            broker_import::apply(&conn, &tradebook)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Applies per-day charges from a contract note CSV to that day's lots.
+    pub async fn import_contract_note(&self, broker: Broker, bytes: &[u8]) -> Result<usize> {
+        let payload = bytes.to_vec();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during contract note import")?;
+            broker_import::apply_contract_note(&conn, broker, &payload)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_broker_fundings(&self) -> Result<Vec<BrokerFunding>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during funding fetch")?;
+            broker_import::get_fundings(&conn)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Realized gains per Schedule CG bucket for a financial year, plus unrealized
+    /// gains on lots still held.
+    pub async fn get_capital_gains(&self, financial_year: &str) -> Result<CapitalGainsReport> {
+        let financial_year = financial_year.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during capital gains")?;
+            let today = OffsetDateTime::now_utc().date();
+            capital_gains::build_report(&conn, &financial_year, today)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn export_capital_gains_csv(&self, financial_year: &str) -> Result<String> {
+        let report = self.get_capital_gains(financial_year).await?;
+        capital_gains::report_csv(&report)
+    }
+
+    /// XIRR per holding, asset class and portfolio as of `as_of` (defaults to today).
+    pub async fn get_performance(&self, as_of: Option<&str>) -> Result<PerformanceReport> {
+        let as_of = match as_of {
+            Some(date) => periods::format_date(periods::parse_date(date)?),
+            None => periods::format_date(OffsetDateTime::now_utc().date()),
+        };
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during performance")?;
+            performance::build_report(&conn, &as_of)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_time_weighted_return(
+        &self,
+        start: &str,
+        end: &str,
+        asset_class: Option<AssetClass>,
+    ) -> Result<TimeWeightedReturn> {
+        let start = periods::format_date(periods::parse_date(start)?);
+        let end = periods::format_date(periods::parse_date(end)?);
+        if start > end {
+            return Err(anyhow!("Start date {start} is after end date {end}"));
+        }
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during TWR")?;
+            performance::time_weighted_return(&conn, &start, &end, asset_class)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn add_loan(&self, loan: NewLoan) -> Result<Loan> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during loan add")?;
+            loans::add_loan(&conn, &loan)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn update_loan(&self, id: i64, loan: NewLoan) -> Result<Loan> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during loan update")?;
+            loans::update_loan(&conn, id, &loan)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_loan(&self, id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during loan delete")?;
+            loans::delete_loan(&conn, id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Loans with outstanding principal, interest paid and EMIs due as of today.
+    pub async fn get_loans(&self) -> Result<Vec<LoanSummary>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during loan fetch")?;
+            loans::summaries(&conn, OffsetDateTime::now_utc().date())
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_loan_schedule(&self, loan_id: i64) -> Result<Vec<AmortizationRow>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during loan schedule")?;
+            loans::get_schedule(&conn, loan_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_loan_prepayments(&self, loan_id: i64) -> Result<Vec<LoanPrepayment>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during prepayment fetch")?;
+            loans::get_prepayments(&conn, loan_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn add_loan_prepayment(&self, prepayment: LoanPrepayment) -> Result<i64> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during prepayment add")?;
+            loans::add_prepayment(&conn, &prepayment)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_loan_prepayment(&self, id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during prepayment delete")?;
+            loans::delete_prepayment(&conn, id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Links EMI and prepayment debits in the ledger to loan schedules.
+    pub async fn match_loan_payments(&self) -> Result<usize> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during EMI matching")?;
+            loans::match_payments(&conn)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn add_credit_card(&self, card: NewCreditCard) -> Result<CreditCard> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during card add")?;
+            credit_cards::add_card(&conn, &card)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn update_credit_card(&self, id: i64, card: NewCreditCard) -> Result<CreditCard> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during card update")?;
+            credit_cards::update_card(&conn, id, &card)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_credit_card(&self, id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during card delete")?;
+            credit_cards::delete_card(&conn, id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Every card with its latest statement, unbilled spend, outstanding and minimum due.
+    pub async fn get_credit_cards(&self) -> Result<Vec<CardOutstanding>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during card fetch")?;
+            credit_cards::outstanding(&conn, OffsetDateTime::now_utc().date())
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_card_statements(&self, card_id: i64) -> Result<Vec<CardStatement>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during card statements")?;
+            let card = credit_cards::get_card(&conn, card_id)?;
+            credit_cards::get_statements(&conn, &card, OffsetDateTime::now_utc().date())
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn assign_card_transactions(
+        &self,
+        card_id: i64,
+        transaction_ids: Vec<i64>,
+    ) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during card assignment")?;
+            credit_cards::assign_transactions(&conn, card_id, &transaction_ids)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn link_card_payment(&self, card_id: i64, transaction_id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during card payment link")?;
+            credit_cards::link_payment(&conn, card_id, transaction_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn unlink_card_transaction(&self, transaction_id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during card unlink")?;
+            credit_cards::unlink_transaction(&conn, transaction_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Links card swipes and bill payments found in the ledger to their cards.
+    pub async fn match_card_transactions(&self) -> Result<CardMatchSummary> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during card matching")?;
+            credit_cards::match_transactions(&conn, OffsetDateTime::now_utc().date())
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn add_goal(&self, goal: NewGoal) -> Result<Goal> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during goal add")?;
+            goals::add_goal(&conn, &goal)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn update_goal(&self, id: i64, goal: NewGoal) -> Result<Goal> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during goal update")?;
+            goals::update_goal(&conn, id, &goal)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_goal(&self, id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during goal delete")?;
+            goals::delete_goal(&conn, id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Progress, required monthly saving and projected completion for every goal.
+    pub async fn get_goals(&self) -> Result<Vec<GoalProgress>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during goal fetch")?;
+            goals::progress(&conn, OffsetDateTime::now_utc().date())
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn link_goal_holding(&self, goal_id: i64, holding_id: i64, share: f64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during goal link")?;
+            goals::link_holding(&conn, goal_id, holding_id, share)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn unlink_goal_holding(&self, goal_id: i64, holding_id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during goal unlink")?;
+            goals::unlink_holding(&conn, goal_id, holding_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn link_goal_account(&self, goal_id: i64, account: &str) -> Result<()> {
+        let account = account.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during goal account link")?;
+            goals::link_account(&conn, goal_id, &account)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn unlink_goal_account(&self, goal_id: i64, account: &str) -> Result<()> {
+        let account = account.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during goal account unlink")?;
+            goals::unlink_account(&conn, goal_id, &account)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn get_goal_contributions(&self, goal_id: i64) -> Result<Vec<GoalContribution>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during contribution fetch")?;
+            goals::get_contributions(&conn, goal_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn add_goal_contribution(&self, contribution: GoalContribution) -> Result<i64> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during contribution add")?;
+            goals::add_contribution(&conn, &contribution)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn tag_goal_transaction(&self, goal_id: i64, transaction_id: i64) -> Result<i64> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during goal tagging")?;
+            goals::tag_transaction(&conn, goal_id, transaction_id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    pub async fn delete_goal_contribution(&self, id: i64) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during contribution delete")?;
+            goals::delete_contribution(&conn, id)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Encrypts the whole vault into a portable `.wvault` bundle.
+    pub async fn export_vault(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let passphrase = passphrase.to_string();
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during vault export")?;
+            vault_bundle::export(&conn, &passphrase)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Decrypts and verifies a bundle without restoring it.
+    pub fn inspect_vault_bundle(bundle: &[u8], passphrase: &str) -> Result<VaultBundleInfo> {
+        vault_bundle::inspect(bundle, passphrase)
+    }
+
+    /// Creates a new vault at `path` from a `.wvault` bundle. Refuses to overwrite an
+    /// existing vault; a failed restore leaves nothing behind.
+    pub fn restore_vault(
+        path: PathBuf,
+        key: VaultKey,
+        bundle: &[u8],
+        passphrase: &str,
+    ) -> Result<(Self, VaultBundleInfo)> {
+        if path.exists() {
+            bail!("A vault already exists at {}", path.display());
+        }
+
+        let restored = storage::connection_pool(&path, &key).and_then(|pool| {
+            let conn = pool.get().context("Checkout failed during vault restore")?;
+            vault_bundle::restore(&conn, bundle, passphrase)
+        });
+        match restored {
+            Ok(info) => Ok((Self::bootstrap(path, key)?, info)),
+            Err(error) => {
+                for suffix in ["", "-wal", "-shm"] {
+                    let mut file = path.clone().into_os_string();
+                    file.push(suffix);
+                    let _ = fs::remove_file(file);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Replaces the vault at `path` with snapshot `name` without unlocking it first,
+    /// for a vault that no longer opens, then opens the restored vault.
+    pub fn restore_vault_backup(path: PathBuf, key: VaultKey, name: &str) -> Result<Self> {
+        backups::restore_file(&path, name, &key)?;
+        Self::bootstrap(path, key)
+    }
+
+    /// Snapshots of the vault at `path`; needs no key, so works while locked.
+    pub fn list_vault_backups(path: &Path) -> Result<Vec<BackupSnapshot>> {
+        backups::list(&backups::directory_for(path))
+    }
+
+    /// Takes a verified snapshot of the vault and prunes older ones per `policy`.
+    pub async fn create_backup(&self, policy: BackupPolicy) -> Result<BackupSnapshot> {
+        let pool = self.pool.clone();
+        let dir = backups::directory_for(&self.path);
+        let key = self.key.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during backup")?;
+            let snapshot = backups::create(&conn, &dir, &key, utc_now())?;
+            backups::prune(&dir, &policy)?;
+            Ok(snapshot)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Snapshots only when the newest one is older than `interval`; meant to be
+    /// polled by the shell's scheduler.
+    pub async fn backup_if_due(
+        &self,
+        policy: BackupPolicy,
+        interval: Duration,
+    ) -> Result<Option<BackupSnapshot>> {
+        let dir = backups::directory_for(&self.path);
+        if !backups::is_due(&dir, utc_now(), interval)? {
+            return Ok(None);
+        }
+        self.create_backup(policy).await.map(Some)
+    }
+
+    pub async fn list_backups(&self) -> Result<Vec<BackupSnapshot>> {
+        let dir = backups::directory_for(&self.path);
+        task::spawn_blocking(move || backups::list(&dir))
+            .await
+            .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Replaces the vault contents with snapshot `name`. The current state is
+    /// snapshotted first (without pruning, so the target can't be rotated away) so
+    /// the restore itself can be undone.
+    pub async fn restore_backup(&self, name: &str) -> Result<BackupSnapshot> {
+        let name = name.to_string();
+        let pool = self.pool.clone();
+        let memory = self.memory.clone();
+        let dir = backups::directory_for(&self.path);
+        let key = self.key.clone();
+        task::spawn_blocking(move || {
+            let mut conn = pool
+                .get()
+                .context("Checkout failed during backup restore")?;
+            let safety = backups::create(&conn, &dir, &key, utc_now())?;
+            backups::restore(&mut conn, &dir, &name, &key)?;
+            *memory.write() = CategorizationMemory::load(&conn)?;
+            Ok(safety)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Ledger, category summary or budget usage for a date range as CSV or JSON.
+    pub async fn export_data(
+        &self,
+        kind: ExportKind,
+        format: ExportFormat,
+        filter: ExportFilter,
+    ) -> Result<String> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during data export")?;
+            exports::render(
+                &conn,
+                kind,
+                format,
+                &filter,
+                OffsetDateTime::now_utc().date(),
+            )
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Re-encrypts the vault and its snapshots under `new_key` and returns a core
+    /// opened with it. Pooled connections of `self` still hold the old key, so the
+    /// caller must replace this core with the returned one.
+    pub async fn rekey(&self, new_key: VaultKey) -> Result<Self> {
+        let pool = self.pool.clone();
+        let path = self.path.clone();
+        let old_key = self.key.clone();
+        task::spawn_blocking(move || {
+            {
+                let conn = pool.get().context("Checkout failed during rekey")?;
+                storage::rekey(&conn, &new_key)?;
+            }
+            storage::open_keyed(&path, &new_key)
+                .context("Vault did not open with the new key after rekey")?;
+            backups::rekey_all(&backups::directory_for(&path), &old_key, &new_key)?;
+            recovery::rewrap(&path, &old_key, &new_key)?;
+            Self::bootstrap(path, new_key)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Rotates to a fresh key from `provider`. The new key is staged in the pending
+    /// slot and only promoted once the vault opens with it; on failure it stays
+    /// staged, since it may already be the only key that opens the database, and
+    /// `unlock` sorts it out.
+    pub async fn rotate_key(&self, provider: &dyn KeyProvider) -> Result<Self> {
+        let new_key = provider.create(KeySlot::Pending)?;
+        let rotated = self.rekey(new_key).await?;
+        provider.promote()?;
+        Ok(rotated)
+    }
+
+    /// Generates a recovery phrase that unlocks this vault even without the key
+    /// provider, escrowing the wrapped key beside the database. Any earlier phrase
+    /// stops working. Key rotations keep the phrase valid.
+    pub async fn create_recovery_phrase(&self) -> Result<String> {
+        let path = self.path.clone();
+        let key = self.key.clone();
+        task::spawn_blocking(move || recovery::create(&path, &key))
+            .await
+            .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Opens the vault at `path` with its recovery phrase, e.g. on a new machine
+    /// whose key provider has never seen it, then rotates to a fresh key held by
+    /// `provider`. The phrase remains valid afterwards.
+    pub async fn recover(path: PathBuf, phrase: &str, provider: &dyn KeyProvider) -> Result<Self> {
+        if !path.exists() {
+            bail!("No vault at {}", path.display());
+        }
+        let phrase = phrase.to_string();
+        let core = task::spawn_blocking(move || {
+            let key = recovery::recover_key(&path, &phrase)?;
+            Self::bootstrap(path, key)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))??;
+        core.rotate_key(provider).await
+    }
+
+    /// Audit log entries, newest first.
+    pub async fn get_audit_log(&self, query: AuditQuery) -> Result<Vec<AuditEntry>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during audit log fetch")?;
+            audit::list(&conn, &query)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Recomputes the audit log's hash chain to detect entries edited or removed
+    /// outside the app.
+    pub async fn verify_audit_log(&self) -> Result<AuditVerification> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during audit verification")?;
+            audit::verify(&conn)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    /// Reverts the most recent journaled change (inbox categorization and commits,
+    /// ledger edits and deletes, budget changes), including what it taught the
+    /// categorization memory. Returns `None` when there is nothing to undo.
+    pub async fn undo(&self) -> Result<Option<JournalEntry>> {
+        self.replay_journal(false).await
+    }
+
+    /// Re-applies the most recently undone change. Returns `None` when there is
+    /// nothing to redo.
+    pub async fn redo(&self) -> Result<Option<JournalEntry>> {
+        self.replay_journal(true).await
+    }
+
+    /// Journaled changes, newest first; undone ones can still be redone.
+    pub async fn get_undo_history(&self) -> Result<Vec<JournalEntry>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool
+                .get()
+                .context("Checkout failed during undo history fetch")?;
+            journal::list(&conn)
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+
+    async fn replay_journal(&self, redo: bool) -> Result<Option<JournalEntry>> {
+        let pool = self.pool.clone();
+        let memory = self.memory.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get().context("Checkout failed during undo")?;
+            let tx = conn
+                .unchecked_transaction()
+                .context("Failed to start undo transaction")?;
+            let entry = if redo {
+                journal::redo(&tx)?
+            } else {
+                journal::undo(&tx)?
+            };
+            let Some(entry) = entry else {
+                return Ok(None);
+            };
+            audit::record(
+                &tx,
+                if redo { "redo" } else { "undo" },
+                &format!("journal:{}", entry.id),
+                None,
+                Some(json!({ "operation": entry.operation, "label": entry.label })),
+            )?;
+            tx.commit().context("Commit undo failed")?;
+
+            *memory.write() = CategorizationMemory::load(&conn)?;
+            Ok(Some(entry))
+        })
+        .await
+        .map_err(|error| anyhow!("Blocking task failed: {error}"))?
+    }
+}
+
+/// Runs `change` in a transaction on `conn` and commits it. `change` may update the
+/// shared categorization memory as it writes, ahead of the commit; if anything
+/// fails, the memory is reloaded from the vault so it never keeps a change that
+/// rolled back.
+fn commit_learning<T>(
+    conn: &Connection,
+    memory: &SharedMemory,
+    what: &str,
+    change: impl FnOnce(&Transaction<'_>) -> Result<T>,
+) -> Result<T> {
+    let tx = conn
+        .unchecked_transaction()
+        .with_context(|| format!("Failed to start {what} transaction"))?;
+    let result = change(&tx).and_then(|value| {
+        tx.commit()
+            .with_context(|| format!("Commit {what} failed"))?;
+        Ok(value)
+    });
+    if result.is_err() {
+        match CategorizationMemory::load(conn) {
+            Ok(reloaded) => *memory.write() = reloaded,
+            Err(error) => log::warn!("Failed to reload categorization memory: {error}"),
+        }
+    }
+    result
+}
+
+fn utc_now() -> PrimitiveDateTime {
+    let now = OffsetDateTime::now_utc();
+    PrimitiveDateTime::new(now.date(), now.time())
+}
+
+#[cfg(all(test, feature = "sqlcipher"))]
+mod tests {
+    use super::*;
+
+    fn inbox(core: &ArthaCore) -> Vec<(String, Option<String>, bool)> {
+        let conn = core.pool.get().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT temp_id, suggested_category, from_memory FROM inbox")
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        rows
+    }
+
+    #[tokio::test]
+    async fn rejecting_a_remembered_suggestion_unlearns_it() {
+        let dir = std::env::temp_dir().join(format!("artha-memory-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let core = ArthaCore::bootstrap(dir.join("vault.db.enc"), VaultKey::from([3; 32])).unwrap();
+        let csv = b"Date,Description,Amount\n2024-01-05,SWIGGY ORDER,-420\n";
+
+        // A category the user picks is not from memory, and is learned on commit.
+        core.import_csv(csv, None).await.unwrap();
+        let (temp_id, suggested, from_memory) = inbox(&core).remove(0);
+        assert_eq!((suggested, from_memory), (None, false));
+        core.set_inbox_category(&temp_id, "Food").await.unwrap();
+        assert!(!inbox(&core)[0].2);
+        core.commit_inbox().await.unwrap();
+
+        // The next import suggests it from memory...
+        core.import_csv(csv, None).await.unwrap();
+        let (temp_id, suggested, from_memory) = inbox(&core).remove(0);
+        assert_eq!((suggested.as_deref(), from_memory), (Some("Food"), true));
+
+        // ...and moving the item elsewhere takes the lesson back.
+        core.set_inbox_category(&temp_id, "Travel").await.unwrap();
+        assert!(!inbox(&core)[0].2);
+        assert!(core.get_learned_tokens().await.unwrap().is_empty());
+        assert_eq!(core.memory.read().suggest("SWIGGY ORDER"), None);
+
+        // Undo restores the lesson in the vault and in memory alike.
+        core.undo().await.unwrap();
+        assert_eq!(
+            core.memory.read().suggest("SWIGGY ORDER").as_deref(),
+            Some("Food")
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_to_delete_a_linked_transaction() {
+        let dir = std::env::temp_dir().join(format!("artha-delete-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let core = ArthaCore::bootstrap(dir.join("vault.db.enc"), VaultKey::from([4; 32])).unwrap();
+        {
+            let conn = core.pool.get().unwrap();
+            conn.execute_batch(
+                "INSERT INTO transactions (id, date, description, amount, flow)
+                 VALUES (1, '2024-01-05', 'TO RD ACCOUNT', -5000.0, 'debit'),
+                        (2, '2024-01-06', 'SWIGGY ORDER', -420.0, 'debit');
+                 INSERT INTO goals (id, name, target_amount) VALUES (1, 'House', 100000.0);
+                 INSERT INTO goal_contributions (goal_id, date, amount, transaction_id)
+                 VALUES (1, '2024-01-05', 5000.0, 1);",
+            )
+            .unwrap();
+        }
+
+        let error = core.delete_transactions(vec![2, 1]).await.unwrap_err();
+        assert!(error.to_string().contains("goal_contributions"), "{error}");
+        let left: i64 = core
+            .pool
+            .get()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(left, 2);
+
+        assert_eq!(core.delete_transactions(vec![2]).await.unwrap(), 1);
+    }
 }
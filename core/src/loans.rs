@@ -0,0 +1,589 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use time::Date;
+
+use crate::{
+    dto::{AmortizationRow, Loan, LoanKind, LoanPrepayment, LoanSummary, NewLoan},
+    periods,
+};
+
+/// How far (in days) a debit may sit from the scheduled EMI date and still match.
+const MATCH_WINDOW_DAYS: i64 = 7;
+/// Debits this many times the EMI or larger are treated as prepayments.
+const PREPAYMENT_FACTOR: f64 = 1.5;
+
+/// Equated monthly instalment for `principal` at `annual_rate` percent over `months`.
+pub fn compute_emi(principal: f64, annual_rate: f64, months: u32) -> f64 {
+    if months == 0 {
+        return principal;
+    }
+    let rate = annual_rate / 1200.0;
+    if rate.abs() < f64::EPSILON {
+        return round2(principal / months as f64);
+    }
+    let growth = (1.0 + rate).powi(months as i32);
+    round2(principal * rate * growth / (growth - 1.0))
+}
+
+pub fn add_loan(conn: &Connection, loan: &NewLoan) -> Result<Loan> {
+    validate(loan)?;
+    conn.execute(
+        "INSERT INTO loans (name, kind, principal, annual_rate, tenure_months, start_date, emi, match_pattern) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            loan.name.trim(),
+            loan.kind.as_str(),
+            loan.principal,
+            loan.annual_rate,
+            loan.tenure_months,
+            loan.start_date,
+            loan.emi,
+            pattern(loan)
+        ],
+    )
+    .context("Failed to add loan")?;
+    get_loan(conn, conn.last_insert_rowid())
+}
+
+pub fn update_loan(conn: &Connection, id: i64, loan: &NewLoan) -> Result<Loan> {
+    validate(loan)?;
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for loan update")?;
+    tx.execute(
+        "UPDATE loans SET name=?2, kind=?3, principal=?4, annual_rate=?5, tenure_months=?6, start_date=?7, emi=?8, match_pattern=?9 WHERE id=?1",
+        params![
+            id,
+            loan.name.trim(),
+            loan.kind.as_str(),
+            loan.principal,
+            loan.annual_rate,
+            loan.tenure_months,
+            loan.start_date,
+            loan.emi,
+            pattern(loan)
+        ],
+    )
+    .context("Failed to update loan")?;
+    // The schedule changed, so earlier EMI matches are redone against it.
+    tx.execute("DELETE FROM loan_payments WHERE loan_id=?1", params![id])?;
+    match_loan(&tx, &get_loan(&tx, id)?)?;
+    tx.commit().context("Commit loan update failed")?;
+    get_loan(conn, id)
+}
+
+pub fn delete_loan(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for loan delete")?;
+    tx.execute("DELETE FROM loan_payments WHERE loan_id=?1", params![id])?;
+    tx.execute("DELETE FROM loan_prepayments WHERE loan_id=?1", params![id])?;
+    tx.execute("DELETE FROM loans WHERE id=?1", params![id])?;
+    tx.commit().context("Commit loan delete failed")?;
+    Ok(())
+}
+
+pub fn get_loan(conn: &Connection, id: i64) -> Result<Loan> {
+    conn.query_row(
+        "SELECT id, name, kind, principal, annual_rate, tenure_months, start_date, emi, match_pattern FROM loans WHERE id=?1",
+        params![id],
+        map_loan,
+    )
+    .with_context(|| format!("Loan {id} not found"))
+}
+
+pub fn get_loans(conn: &Connection) -> Result<Vec<Loan>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, kind, principal, annual_rate, tenure_months, start_date, emi, match_pattern FROM loans ORDER BY start_date, id",
+    )?;
+    let rows = stmt
+        .query_map([], map_loan)?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch loans")?;
+    Ok(rows)
+}
+
+pub fn add_prepayment(conn: &Connection, prepayment: &LoanPrepayment) -> Result<i64> {
+    if prepayment.amount <= 0.0 {
+        bail!("Prepayment amount must be positive");
+    }
+    periods::parse_date(&prepayment.date)?;
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for prepayment")?;
+    tx.execute(
+        "INSERT INTO loan_prepayments (loan_id, date, amount, transaction_id) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            prepayment.loan_id,
+            prepayment.date,
+            prepayment.amount,
+            prepayment.transaction_id
+        ],
+    )
+    .context("Failed to add prepayment")?;
+    let id = tx.last_insert_rowid();
+    // Rows after the prepayment have a new principal/interest split, so EMI links
+    // are redone against the new schedule.
+    tx.execute(
+        "DELETE FROM loan_payments WHERE loan_id=?1",
+        params![prepayment.loan_id],
+    )?;
+    match_loan(&tx, &get_loan(&tx, prepayment.loan_id)?)?;
+    tx.commit().context("Commit prepayment failed")?;
+    Ok(id)
+}
+
+pub fn delete_prepayment(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for prepayment delete")?;
+    let Some(loan_id) = tx
+        .query_row(
+            "SELECT loan_id FROM loan_prepayments WHERE id=?1",
+            params![id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+    else {
+        return Ok(());
+    };
+    tx.execute(
+        "DELETE FROM loan_payments WHERE loan_id=?1",
+        params![loan_id],
+    )?;
+    tx.execute("DELETE FROM loan_prepayments WHERE id=?1", params![id])?;
+    match_loan(&tx, &get_loan(&tx, loan_id)?)?;
+    tx.commit().context("Commit prepayment delete failed")?;
+    Ok(())
+}
+
+pub fn get_prepayments(conn: &Connection, loan_id: i64) -> Result<Vec<LoanPrepayment>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, loan_id, date, amount, transaction_id FROM loan_prepayments WHERE loan_id=?1 ORDER BY date, id",
+    )?;
+    let rows = stmt
+        .query_map(params![loan_id], |row| {
+            Ok(LoanPrepayment {
+                id: row.get(0)?,
+                loan_id: row.get(1)?,
+                date: row.get(2)?,
+                amount: row.get(3)?,
+                transaction_id: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch prepayments")?;
+    Ok(rows)
+}
+
+/// The amortization schedule with prepayments applied and matched EMIs attached.
+pub fn get_schedule(conn: &Connection, loan_id: i64) -> Result<Vec<AmortizationRow>> {
+    let loan = get_loan(conn, loan_id)?;
+    let prepayments = get_prepayments(conn, loan_id)?;
+    let matched = matched_payments(conn, loan_id)?;
+    schedule(&loan, &prepayments, &matched)
+}
+
+/// Builds the schedule. Prepayments keep the EMI unchanged and shorten the tenure,
+/// which is how most Indian lenders apply them by default.
+pub fn schedule(
+    loan: &Loan,
+    prepayments: &[LoanPrepayment],
+    matched: &HashMap<u32, i64>,
+) -> Result<Vec<AmortizationRow>> {
+    let start = periods::parse_date(&loan.start_date)?;
+    let rate = loan.annual_rate / 1200.0;
+    let mut balance = loan.principal;
+    let mut rows = Vec::with_capacity(loan.tenure_months as usize);
+    let mut previous_date = String::new();
+
+    let mut installment = 0;
+    while balance > 0.005 && installment < loan.tenure_months + 12 {
+        installment += 1;
+        let date = periods::format_date(periods::add_months(start, installment as i32 - 1));
+        let opening_balance = balance;
+        let interest = round2(balance * rate);
+        let mut principal = (loan.emi - interest).min(balance);
+        if principal <= 0.0 {
+            log::warn!("EMI for loan {} does not cover interest", loan.id);
+            break;
+        }
+        // The final EMI absorbs rounding residue instead of leaving a tiny extra row.
+        if installment >= loan.tenure_months || balance - principal < loan.emi * 0.01 {
+            principal = balance;
+        }
+        balance -= principal;
+
+        let prepayment = prepayments
+            .iter()
+            .filter(|prepayment| {
+                prepayment.date > previous_date && prepayment.date.as_str() <= date.as_str()
+            })
+            .map(|prepayment| prepayment.amount)
+            .sum::<f64>()
+            .min(balance);
+        balance -= prepayment;
+
+        rows.push(AmortizationRow {
+            installment,
+            date: date.clone(),
+            opening_balance,
+            emi: principal + interest,
+            interest,
+            principal,
+            prepayment,
+            closing_balance: balance.max(0.0),
+            transaction_id: matched.get(&installment).copied(),
+        });
+        previous_date = date;
+    }
+
+    Ok(rows)
+}
+
+/// Scans the ledger for debits matching each loan's pattern. Debits close to the EMI
+/// near a scheduled date are linked to that instalment; much larger ones become
+/// prepayments. Returns the number of transactions linked.
+pub fn match_payments(conn: &Connection) -> Result<usize> {
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open tx for EMI matching")?;
+    let mut linked = 0;
+    for loan in get_loans(&tx)? {
+        linked += match_loan(&tx, &loan)?;
+    }
+    tx.commit().context("Commit EMI matching failed")?;
+    Ok(linked)
+}
+
+/// Matches one loan's debits, as `match_payments` does. Call it on the transaction
+/// that changed the loan's schedule, after dropping the EMI links it invalidated.
+fn match_loan(conn: &Connection, loan: &Loan) -> Result<usize> {
+    let Some(pattern) = loan.match_pattern.as_deref() else {
+        return Ok(0);
+    };
+    let mut linked = 0;
+    let candidates = candidate_debits(conn, pattern)?;
+    let mut reshaped = false;
+
+    for (transaction_id, date, amount) in &candidates {
+        if *amount >= loan.emi * PREPAYMENT_FACTOR && *date >= loan.start_date {
+            conn.execute(
+                "INSERT INTO loan_prepayments (loan_id, date, amount, transaction_id) VALUES (?1, ?2, ?3, ?4)",
+                params![loan.id, date, amount, transaction_id],
+            )?;
+            conn.execute(
+                "DELETE FROM loan_payments WHERE loan_id=?1",
+                params![loan.id],
+            )?;
+            linked += 1;
+            reshaped = true;
+        }
+    }
+    // A prepayment reshapes the schedule and drops earlier EMI links, so those
+    // debits have to be matched again.
+    let candidates = if reshaped {
+        candidate_debits(conn, pattern)?
+    } else {
+        candidates
+    };
+
+    let prepayments = get_prepayments(conn, loan.id)?;
+    let matched = matched_payments(conn, loan.id)?;
+    let mut open_rows = schedule(loan, &prepayments, &matched)?
+        .into_iter()
+        .filter(|row| row.transaction_id.is_none())
+        .collect::<Vec<_>>();
+    let tolerance = (loan.emi * 0.01).max(1.0);
+
+    for (transaction_id, date, amount) in candidates {
+        let Ok(paid_on) = periods::parse_date(&date) else {
+            continue;
+        };
+        let Some(index) = open_rows.iter().position(|row| {
+            (row.emi - amount).abs() <= tolerance
+                && periods::parse_date(&row.date)
+                    .map(|due| periods::days_between(due, paid_on).abs() <= MATCH_WINDOW_DAYS)
+                    .unwrap_or(false)
+        }) else {
+            continue;
+        };
+
+        let row = open_rows.remove(index);
+        conn.execute(
+            "INSERT OR IGNORE INTO loan_payments (transaction_id, loan_id, installment, principal, interest) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![transaction_id, loan.id, row.installment, row.principal, row.interest],
+        )?;
+        linked += 1;
+    }
+    Ok(linked)
+}
+
+/// Outstanding principal across all loans on `as_of`, following each schedule.
+pub fn outstanding_total(conn: &Connection, as_of: &str) -> Result<f64> {
+    let mut total = 0.0;
+    for loan in get_loans(conn)? {
+        let rows = schedule(&loan, &get_prepayments(conn, loan.id)?, &HashMap::new())?;
+        total += outstanding_on(&loan, &rows, as_of)?;
+    }
+    Ok(total)
+}
+
+pub fn summaries(conn: &Connection, today: Date) -> Result<Vec<LoanSummary>> {
+    let today = periods::format_date(today);
+    get_loans(conn)?
+        .into_iter()
+        .map(|loan| {
+            let rows = get_schedule(conn, loan.id)?;
+            let outstanding_principal = outstanding_on(&loan, &rows, &today)?;
+            let due = rows
+                .iter()
+                .filter(|row| row.date <= today)
+                .collect::<Vec<_>>();
+
+            Ok(LoanSummary {
+                outstanding_principal,
+                principal_paid: loan.principal - outstanding_principal,
+                interest_paid: due.iter().map(|row| row.interest).sum(),
+                emis_paid: due.len() as u32,
+                emis_matched: rows
+                    .iter()
+                    .filter(|row| row.transaction_id.is_some())
+                    .count() as u32,
+                emis_remaining: (rows.len() - due.len()) as u32,
+                next_due: rows
+                    .iter()
+                    .find(|row| row.date > today)
+                    .map(|row| row.date.clone()),
+                loan,
+            })
+        })
+        .collect()
+}
+
+/// Principal owed on `as_of`: nothing before disbursal (a month ahead of the first
+/// EMI), then the closing balance of the latest instalment due.
+fn outstanding_on(loan: &Loan, rows: &[AmortizationRow], as_of: &str) -> Result<f64> {
+    let start = periods::parse_date(&loan.start_date)?;
+    let disbursed = periods::format_date(periods::add_months(start, -1));
+    if as_of < disbursed.as_str() {
+        return Ok(0.0);
+    }
+    Ok(rows
+        .iter()
+        .rev()
+        .find(|row| row.date.as_str() <= as_of)
+        .map(|row| row.closing_balance)
+        .unwrap_or(loan.principal))
+}
+
+fn matched_payments(conn: &Connection, loan_id: i64) -> Result<HashMap<u32, i64>> {
+    let mut stmt =
+        conn.prepare("SELECT installment, transaction_id FROM loan_payments WHERE loan_id=?1")?;
+    let rows = stmt
+        .query_map(params![loan_id], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .context("Failed to fetch matched EMIs")?;
+    Ok(rows)
+}
+
+/// Unlinked debits whose narration contains `pattern`, as (id, date, amount paid).
+fn candidate_debits(conn: &Connection, pattern: &str) -> Result<Vec<(i64, String, f64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, date, amount FROM transactions WHERE amount < 0 AND UPPER(description) LIKE ?1 AND id NOT IN (SELECT transaction_id FROM loan_payments) AND id NOT IN (SELECT transaction_id FROM loan_prepayments WHERE transaction_id IS NOT NULL) ORDER BY date, id",
+    )?;
+    let rows = stmt
+        .query_map(params![format!("%{}%", pattern.to_uppercase())], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, f64>(2)?.abs(),
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch EMI candidates")?;
+    Ok(rows)
+}
+
+fn validate(loan: &NewLoan) -> Result<()> {
+    if loan.principal <= 0.0 || !loan.principal.is_finite() {
+        bail!("Loan principal must be positive");
+    }
+    if loan.annual_rate < 0.0 {
+        bail!("Loan rate cannot be negative");
+    }
+    if loan.tenure_months == 0 {
+        bail!("Loan tenure must be at least one month");
+    }
+    periods::parse_date(&loan.start_date)?;
+    Ok(())
+}
+
+fn pattern(loan: &NewLoan) -> Option<String> {
+    loan.match_pattern
+        .as_deref()
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(str::to_uppercase)
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn map_loan(row: &rusqlite::Row<'_>) -> rusqlite::Result<Loan> {
+    let principal: f64 = row.get(3)?;
+    let annual_rate: f64 = row.get(4)?;
+    let tenure_months: u32 = row.get(5)?;
+    let emi: Option<f64> = row.get(7)?;
+    Ok(Loan {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        kind: LoanKind::parse(&row.get::<_, String>(2)?).unwrap_or(LoanKind::Other),
+        principal,
+        annual_rate,
+        tenure_months,
+        start_date: row.get(6)?,
+        emi: emi.unwrap_or_else(|| compute_emi(principal, annual_rate, tenure_months)),
+        match_pattern: row.get(8)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn home_loan() -> Loan {
+        Loan {
+            id: 1,
+            name: "Home".into(),
+            kind: LoanKind::Home,
+            principal: 1_000_000.0,
+            annual_rate: 8.5,
+            tenure_months: 240,
+            start_date: "2024-01-05".into(),
+            emi: compute_emi(1_000_000.0, 8.5, 240),
+            match_pattern: None,
+        }
+    }
+
+    #[test]
+    fn schedule_amortizes_to_zero_and_prepayment_shortens_tenure() {
+        let loan = home_loan();
+        assert!((loan.emi - 8678.23).abs() < 0.01);
+
+        let rows = schedule(&loan, &[], &HashMap::new()).unwrap();
+        assert_eq!(rows.len(), 240);
+        assert!((rows[0].interest - 7083.33).abs() < 0.01);
+        assert_eq!(rows[1].date, "2024-02-05");
+        assert!(rows.last().unwrap().closing_balance < 0.01);
+
+        let prepayment = LoanPrepayment {
+            id: None,
+            loan_id: 1,
+            date: "2024-06-20".into(),
+            amount: 200_000.0,
+            transaction_id: None,
+        };
+        let rows = schedule(&loan, &[prepayment], &HashMap::new()).unwrap();
+        assert!(rows.len() < 180);
+        assert_eq!(rows[6].prepayment, 200_000.0);
+        assert_eq!(rows[6].date, "2024-07-05");
+    }
+
+    #[test]
+    fn prepayment_keeps_previously_matched_emis() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::migrate(&conn).unwrap();
+        let loan = add_loan(
+            &conn,
+            &NewLoan {
+                name: "Home".into(),
+                kind: LoanKind::Home,
+                principal: 1_000_000.0,
+                annual_rate: 8.5,
+                tenure_months: 240,
+                start_date: "2024-01-05".into(),
+                emi: None,
+                match_pattern: Some("HDFC HOME LOAN".into()),
+            },
+        )
+        .unwrap();
+        let debit = |date: &str, amount: f64| {
+            conn.execute(
+                "INSERT INTO transactions (date, description, amount, flow)
+                 VALUES (?1, 'HDFC HOME LOAN EMI', ?2, 'debit')",
+                params![date, -amount],
+            )
+            .unwrap();
+        };
+
+        debit("2024-01-05", loan.emi);
+        debit("2024-02-05", loan.emi);
+        assert_eq!(match_payments(&conn).unwrap(), 2);
+
+        debit("2024-02-20", 200_000.0);
+        debit("2024-03-05", loan.emi);
+        assert_eq!(match_payments(&conn).unwrap(), 4);
+
+        let rows = get_schedule(&conn, loan.id).unwrap();
+        assert!(rows[..3].iter().all(|row| row.transaction_id.is_some()));
+        assert_eq!(rows[2].prepayment, 200_000.0);
+    }
+
+    #[test]
+    fn schedule_edits_keep_emis_matched() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::migrate(&conn).unwrap();
+        let mut terms = NewLoan {
+            name: "Car".into(),
+            kind: LoanKind::Car,
+            principal: 600_000.0,
+            annual_rate: 9.0,
+            tenure_months: 60,
+            start_date: "2024-01-10".into(),
+            emi: None,
+            match_pattern: Some("ICICI CAR LOAN".into()),
+        };
+        let loan = add_loan(&conn, &terms).unwrap();
+        for date in ["2024-01-10", "2024-02-10", "2024-03-10"] {
+            conn.execute(
+                "INSERT INTO transactions (date, description, amount, flow)
+                 VALUES (?1, 'ICICI CAR LOAN EMI', ?2, 'debit')",
+                params![date, -loan.emi],
+            )
+            .unwrap();
+        }
+        assert_eq!(match_payments(&conn).unwrap(), 3);
+        let linked = |conn: &Connection| {
+            get_schedule(conn, loan.id)
+                .unwrap()
+                .iter()
+                .filter(|row| row.transaction_id.is_some())
+                .count()
+        };
+
+        let prepayment = add_prepayment(
+            &conn,
+            &LoanPrepayment {
+                id: None,
+                loan_id: loan.id,
+                date: "2024-02-15".into(),
+                amount: 100_000.0,
+                transaction_id: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(linked(&conn), 3);
+
+        delete_prepayment(&conn, prepayment).unwrap();
+        assert_eq!(linked(&conn), 3);
+
+        terms.name = "Car (ICICI)".into();
+        update_loan(&conn, loan.id, &terms).unwrap();
+        assert_eq!(linked(&conn), 3);
+    }
+}
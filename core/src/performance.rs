@@ -0,0 +1,391 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use rusqlite::Connection;
+use time::Date;
+
+use crate::{
+    dto::{
+        AssetClass, AssetClassReturn, HoldingLot, HoldingReturn, LotKind, PerformanceReport,
+        ReturnMetrics, TimeWeightedReturn,
+    },
+    holdings, periods,
+};
+
+const MAX_ITERATIONS: usize = 100;
+const TOLERANCE: f64 = 1e-9;
+
+/// Dated cash flows from the investor's side: purchases negative, proceeds and
+/// dividend payouts positive.
+type CashFlows = Vec<(Date, f64)>;
+
+/// XIRR and totals per holding, per asset class and for the whole portfolio, treating
+/// the market value on `as_of` as a final inflow.
+pub fn build_report(conn: &Connection, as_of: &str) -> Result<PerformanceReport> {
+    let as_of_date = periods::parse_date(as_of)?;
+    let mut holding_returns = Vec::new();
+    let mut by_class: BTreeMap<&'static str, (AssetClass, CashFlows, ReturnMetrics)> =
+        BTreeMap::new();
+    let mut portfolio_flows = CashFlows::new();
+    let mut portfolio = ReturnMetrics::default();
+
+    for holding in holdings::get_holdings(conn)? {
+        let lots = holdings::get_lots(conn, holding.id)?;
+        let mut flows = lot_flows(&lots, as_of)?;
+        flows.extend(payout_flows(conn, holding.id, as_of)?);
+        if flows.is_empty() {
+            continue;
+        }
+
+        let current_value = holdings::valuation(conn, holding.clone(), as_of)?.market_value;
+        let mut metrics = ReturnMetrics {
+            current_value,
+            ..ReturnMetrics::default()
+        };
+        for (_, amount) in &flows {
+            if *amount < 0.0 {
+                metrics.invested += -amount;
+            } else {
+                metrics.withdrawn += amount;
+            }
+        }
+        if current_value > 0.0 {
+            flows.push((as_of_date, current_value));
+        }
+        metrics.gain = metrics.withdrawn + metrics.current_value - metrics.invested;
+        metrics.xirr = xirr(&flows);
+
+        let (_, class_flows, class_metrics) = by_class
+            .entry(holding.asset_class.as_str())
+            .or_insert_with(|| {
+                (
+                    holding.asset_class,
+                    CashFlows::new(),
+                    ReturnMetrics::default(),
+                )
+            });
+        for target in [&mut *class_metrics, &mut portfolio] {
+            target.invested += metrics.invested;
+            target.withdrawn += metrics.withdrawn;
+            target.current_value += metrics.current_value;
+            target.gain += metrics.gain;
+        }
+        class_flows.extend(flows.iter().copied());
+        portfolio_flows.extend(flows);
+
+        holding_returns.push(HoldingReturn { holding, metrics });
+    }
+
+    portfolio.xirr = xirr(&portfolio_flows);
+    let asset_classes = by_class
+        .into_values()
+        .map(|(asset_class, flows, mut metrics)| {
+            metrics.xirr = xirr(&flows);
+            AssetClassReturn {
+                asset_class,
+                metrics,
+            }
+        })
+        .collect();
+
+    Ok(PerformanceReport {
+        as_of: as_of.to_string(),
+        portfolio,
+        asset_classes,
+        holdings: holding_returns,
+    })
+}
+
+/// Time-weighted return between `start` and `end`, optionally limited to one asset
+/// class. The period is split at every purchase, sale or dividend payout so the
+/// result reflects investment performance rather than the timing of cash flows.
+pub fn time_weighted_return(
+    conn: &Connection,
+    start: &str,
+    end: &str,
+    asset_class: Option<AssetClass>,
+) -> Result<TimeWeightedReturn> {
+    let start_date = periods::parse_date(start)?;
+    let end_date = periods::parse_date(end)?;
+
+    let scope = holdings::get_holdings(conn)?
+        .into_iter()
+        .filter(|holding| asset_class.is_none_or(|class| holding.asset_class == class))
+        .collect::<Vec<_>>();
+
+    let mut inflows: BTreeMap<String, f64> = BTreeMap::new();
+    for holding in &scope {
+        for lot in holdings::get_lots(conn, holding.id)? {
+            if lot.date.as_str() > start && lot.date.as_str() <= end {
+                *inflows.entry(lot.date.clone()).or_default() -= lot_flow(&lot);
+            }
+        }
+        for (date, amount) in holdings::dividend_payouts(conn, holding.id)? {
+            if date.as_str() > start && date.as_str() <= end {
+                *inflows.entry(date).or_default() -= amount;
+            }
+        }
+    }
+
+    let value_on = |date: &str| -> Result<f64> {
+        let mut total = 0.0;
+        for holding in &scope {
+            total += holdings::valuation(conn, holding.clone(), date)?.market_value;
+        }
+        Ok(total)
+    };
+
+    let start_value = value_on(start)?;
+    let mut steps = Vec::with_capacity(inflows.len());
+    for (date, inflow) in &inflows {
+        let after = value_on(date)?;
+        steps.push((after - inflow, after));
+    }
+    let end_value = match inflows.keys().next_back() {
+        Some(last) if last.as_str() == end => None,
+        _ => Some(value_on(end)?),
+    };
+
+    let (twr, sub_periods) = chain_returns(start_value, &steps, end_value);
+    let days = periods::days_between(start_date, end_date);
+    let annualized = twr
+        .filter(|_| days >= 365)
+        .map(|twr| (1.0 + twr).powf(365.0 / days as f64) - 1.0);
+
+    Ok(TimeWeightedReturn {
+        start: start.to_string(),
+        end: end.to_string(),
+        asset_class,
+        twr,
+        annualized,
+        sub_periods,
+    })
+}
+
+/// Links sub-period returns. Each step is the value just before and just after a
+/// flow; sub-periods starting from zero value (nothing held) are skipped.
+fn chain_returns(
+    start_value: f64,
+    steps: &[(f64, f64)],
+    end_value: Option<f64>,
+) -> (Option<f64>, usize) {
+    let mut growth = 1.0;
+    let mut sub_periods = 0;
+    let mut previous = start_value;
+
+    let closing = end_value.map(|value| (value, value));
+    for (before, after) in steps.iter().copied().chain(closing) {
+        if previous > 1e-9 {
+            growth *= before / previous;
+            sub_periods += 1;
+        }
+        previous = after;
+    }
+
+    ((sub_periods > 0).then_some(growth - 1.0), sub_periods)
+}
+
+/// Investor-side cash flow of a lot: purchases (with charges) are outflows.
+fn lot_flow(lot: &HoldingLot) -> f64 {
+    let gross = lot.units * lot.price;
+    match lot.kind {
+        LotKind::Buy => -(gross + lot.charges),
+        LotKind::Sell => gross - lot.charges,
+    }
+}
+
+fn lot_flows(lots: &[HoldingLot], as_of: &str) -> Result<CashFlows> {
+    lots.iter()
+        .filter(|lot| lot.date.as_str() <= as_of)
+        .map(|lot| Ok((periods::parse_date(&lot.date)?, lot_flow(lot))))
+        .collect()
+}
+
+fn payout_flows(conn: &Connection, holding_id: i64, as_of: &str) -> Result<CashFlows> {
+    holdings::dividend_payouts(conn, holding_id)?
+        .into_iter()
+        .filter(|(date, _)| date.as_str() <= as_of)
+        .map(|(date, amount)| Ok((periods::parse_date(&date)?, amount)))
+        .collect()
+}
+
+/// Annualised internal rate of return for irregular cash flows (actual/365). Uses
+/// Newton's method and falls back to bisection when it fails to converge.
+pub fn xirr(flows: &[(Date, f64)]) -> Option<f64> {
+    let has_outflow = flows.iter().any(|(_, amount)| *amount < 0.0);
+    let has_inflow = flows.iter().any(|(_, amount)| *amount > 0.0);
+    if !has_outflow || !has_inflow {
+        return None;
+    }
+
+    let first = flows.iter().map(|(date, _)| *date).min()?;
+    let years = flows
+        .iter()
+        .map(|(date, amount)| (periods::days_between(first, *date) as f64 / 365.0, *amount))
+        .collect::<Vec<_>>();
+
+    newton(&years).or_else(|| bisect(&years))
+}
+
+fn npv(flows: &[(f64, f64)], rate: f64) -> f64 {
+    flows
+        .iter()
+        .map(|(years, amount)| amount / (1.0 + rate).powf(*years))
+        .sum()
+}
+
+fn npv_derivative(flows: &[(f64, f64)], rate: f64) -> f64 {
+    flows
+        .iter()
+        .map(|(years, amount)| -years * amount / (1.0 + rate).powf(years + 1.0))
+        .sum()
+}
+
+fn newton(flows: &[(f64, f64)]) -> Option<f64> {
+    let mut rate = 0.1;
+    for _ in 0..MAX_ITERATIONS {
+        let value = npv(flows, rate);
+        let slope = npv_derivative(flows, rate);
+        if slope.abs() < f64::EPSILON {
+            return None;
+        }
+        let next = rate - value / slope;
+        if !next.is_finite() || next <= -1.0 {
+            return None;
+        }
+        if (next - rate).abs() < TOLERANCE {
+            return Some(next);
+        }
+        rate = next;
+    }
+    None
+}
+
+fn bisect(flows: &[(f64, f64)]) -> Option<f64> {
+    let mut low = -0.999_999;
+    let mut high = 1.0;
+    while npv(flows, low).signum() == npv(flows, high).signum() {
+        high *= 2.0;
+        if high > 1e6 {
+            return None;
+        }
+    }
+
+    for _ in 0..MAX_ITERATIONS * 3 {
+        let mid = (low + high) / 2.0;
+        let value = npv(flows, mid);
+        if value.abs() < TOLERANCE || (high - low) / 2.0 < TOLERANCE {
+            return Some(mid);
+        }
+        if value.signum() == npv(flows, low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Some((low + high) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dto::NewHolding, storage};
+    use time::Month;
+
+    fn day(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn xirr_matches_spreadsheet_results() {
+        let flows = vec![
+            (day(2008, Month::January, 1), -10_000.0),
+            (day(2008, Month::March, 1), 2_750.0),
+            (day(2008, Month::October, 30), 4_250.0),
+            (day(2009, Month::February, 15), 3_250.0),
+            (day(2009, Month::April, 1), 2_750.0),
+        ];
+        let rate = xirr(&flows).expect("xirr should converge");
+        assert!((rate - 0.373_362_535).abs() < 1e-6, "got {rate}");
+
+        let one_year = vec![
+            (day(2023, Month::January, 1), -1_000.0),
+            (day(2024, Month::January, 1), 1_100.0),
+        ];
+        assert!((xirr(&one_year).unwrap() - 0.1).abs() < 1e-6);
+
+        let total_loss = vec![
+            (day(2023, Month::January, 1), -1_000.0),
+            (day(2023, Month::July, 1), 10.0),
+        ];
+        let rate = xirr(&total_loss).expect("bisection should handle deep losses");
+        assert!(rate > -1.0 && rate < -0.99, "got {rate}");
+
+        assert_eq!(xirr(&one_year[..1]), None);
+    }
+
+    #[test]
+    fn time_weighted_return_ignores_contribution_timing() {
+        // 100 grows 10%, then 100 is added and the 210 falls 5%.
+        let (twr, sub_periods) = chain_returns(100.0, &[(110.0, 210.0)], Some(199.5));
+        assert_eq!(sub_periods, 2);
+        assert!((twr.unwrap() - (1.10 * 0.95 - 1.0)).abs() < 1e-9);
+
+        // Nothing held at the start: the first sub-period is skipped.
+        let (twr, sub_periods) = chain_returns(0.0, &[(0.0, 100.0)], Some(120.0));
+        assert_eq!(sub_periods, 1);
+        assert!((twr.unwrap() - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dividend_payouts_count_as_returned_cash() {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        let holding = holdings::upsert_holding(
+            &conn,
+            &NewHolding {
+                name: "Value Fund - IDCW".into(),
+                asset_class: AssetClass::EquityFund,
+                symbol: "INF000000002".into(),
+                folio: None,
+                equity_oriented: true,
+            },
+        )
+        .unwrap();
+        holdings::add_lot(
+            &conn,
+            &HoldingLot {
+                id: None,
+                holding_id: holding.id,
+                kind: LotKind::Buy,
+                date: "2024-01-01".into(),
+                units: 10.0,
+                price: 100.0,
+                charges: 0.0,
+                external_ref: None,
+            },
+        )
+        .unwrap();
+        // Up 10%, then 5 a unit is paid out, then up another 10%.
+        for (date, price) in [
+            ("2024-01-01", 100.0),
+            ("2024-06-30", 110.0),
+            ("2024-07-01", 105.0),
+            ("2024-12-31", 115.5),
+        ] {
+            holdings::set_price(&conn, holding.id, date, price).unwrap();
+        }
+        holdings::add_cash_flow(&conn, holding.id, "dividend", "2024-07-01", 50.0, "div-1")
+            .unwrap();
+
+        let twr = time_weighted_return(&conn, "2024-01-01", "2024-12-31", None).unwrap();
+        assert_eq!(twr.sub_periods, 2);
+        assert!((twr.twr.unwrap() - 0.21).abs() < 1e-9, "{:?}", twr.twr);
+
+        let report = build_report(&conn, "2024-12-31").unwrap();
+        let metrics = &report.holdings[0].metrics;
+        assert!((metrics.withdrawn - 50.0).abs() < 1e-9);
+        assert!((metrics.gain - 205.0).abs() < 1e-9);
+        assert!(metrics.xirr.unwrap() > 0.2);
+    }
+}
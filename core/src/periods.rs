@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use time::{macros::format_description, Date, Duration, Month};
+
+pub fn parse_date(raw: &str) -> Result<Date> {
+    let format = format_description!("[year]-[month]-[day]");
+    Date::parse(raw.trim(), &format).with_context(|| format!("Invalid date: {raw}"))
+}
+
+pub fn format_date(date: Date) -> String {
+    let format = format_description!("[year]-[month]-[day]");
+    date.format(&format).unwrap_or_else(|_| date.to_string())
+}
+
+/// Parses a `YYYY-MM` month into its first day.
+pub fn parse_month(month: &str) -> Result<Date> {
+    parse_date(&format!("{month}-01")).with_context(|| format!("Invalid month: {month}"))
+}
+
+pub fn month_start(date: Date) -> Date {
+    date.replace_day(1).unwrap_or(date)
+}
+
+/// Moves `date` by `months`, clamping the day to the end of the target month.
+pub fn add_months(date: Date, months: i32) -> Date {
+    let index = date.year() * 12 + (u8::from(date.month()) as i32 - 1) + months;
+    let year = index.div_euclid(12);
+    let month = Month::try_from((index.rem_euclid(12) + 1) as u8).unwrap_or(Month::January);
+    let day = date.day().min(days_in_month(year, month));
+    Date::from_calendar_date(year, month, day).unwrap_or(date)
+}
+
+/// Whole calendar months from `start` to `end`, ignoring the day of month.
+pub fn months_between(start: Date, end: Date) -> i32 {
+    (end.year() - start.year()) * 12
+        + (u8::from(end.month()) as i32 - u8::from(start.month()) as i32)
+}
+
+pub fn days_in_month(year: i32, month: Month) -> u8 {
+    let first = match Date::from_calendar_date(year, month, 1) {
+        Ok(first) => first,
+        Err(_) => return 30,
+    };
+    let next = next_month_start(first);
+    (next - first).whole_days() as u8
+}
+
+fn next_month_start(first: Date) -> Date {
+    match first.month() {
+        Month::December => Date::from_calendar_date(first.year() + 1, Month::January, 1),
+        month => Date::from_calendar_date(first.year(), month.next(), 1),
+    }
+    .unwrap_or(first)
+}
+
+/// First day of the Indian financial year (April–March) containing `date`.
+pub fn financial_year_start(date: Date) -> Date {
+    let year = if date.month() >= Month::April {
+        date.year()
+    } else {
+        date.year() - 1
+    };
+    Date::from_calendar_date(year, Month::April, 1).unwrap_or(date)
+}
+
+/// Financial year label such as `FY2024-25` for the year containing `date`.
+pub fn financial_year_label(date: Date) -> String {
+    let start = financial_year_start(date).year();
+    format!("FY{}-{:02}", start, (start + 1).rem_euclid(100))
+}
+
+/// Parses `FY2024-25` or `2024-25` into its April 1 and March 31 bounds.
+pub fn parse_financial_year(label: &str) -> Result<(Date, Date)> {
+    let trimmed = label.trim().trim_start_matches("FY");
+    let start_year: i32 = trimmed
+        .split('-')
+        .next()
+        .and_then(|year| year.parse().ok())
+        .with_context(|| format!("Invalid financial year: {label}"))?;
+    let start = Date::from_calendar_date(start_year, Month::April, 1)?;
+    let end = Date::from_calendar_date(start_year + 1, Month::March, 31)?;
+    Ok((start, end))
+}
+
+/// Fraction of the month starting at `start` that has elapsed by `today`, in `0.0..=1.0`.
+pub fn elapsed_fraction(start: Date, today: Date) -> f64 {
+    let days = days_in_month(start.year(), start.month()) as i64;
+    let elapsed = (today - start).whole_days() + 1;
+    elapsed.clamp(0, days) as f64 / days as f64
+}
+
+pub fn days_between(start: Date, end: Date) -> i64 {
+    (end - start).whole_days()
+}
+
+pub fn shift_days(date: Date, days: i64) -> Date {
+    date.checked_add(Duration::days(days)).unwrap_or(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    #[test]
+    fn add_months_clamps_to_month_end() {
+        assert_eq!(add_months(date!(2024 - 01 - 31), 1), date!(2024 - 02 - 29));
+        assert_eq!(
+            add_months(date!(2024 - 02 - 29), -12),
+            date!(2023 - 02 - 28)
+        );
+        assert_eq!(add_months(date!(2025 - 11 - 15), 3), date!(2026 - 02 - 15));
+    }
+
+    #[test]
+    fn financial_year_runs_april_to_march() {
+        assert_eq!(
+            financial_year_start(date!(2025 - 03 - 31)),
+            date!(2024 - 04 - 01)
+        );
+        assert_eq!(
+            financial_year_start(date!(2025 - 04 - 01)),
+            date!(2025 - 04 - 01)
+        );
+        assert_eq!(financial_year_label(date!(2025 - 01 - 10)), "FY2024-25");
+        assert_eq!(financial_year_label(date!(2099 - 06 - 01)), "FY2099-00");
+    }
+}
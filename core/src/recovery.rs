@@ -0,0 +1,280 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    kdf::{derive_key, KdfParams, SALT_LEN},
+    key_provider::VaultKey,
+};
+
+const FORMAT_VERSION: u32 = 1;
+const NONCE_LEN: usize = 24;
+/// 160 bits of entropy, which is exactly 32 base32 symbols.
+const SECRET_LEN: usize = 20;
+const GROUP_LEN: usize = 4;
+/// Crockford's base32: no I, L, O or U, so it survives being read aloud or
+/// copied by hand.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const KEY_AAD: &[u8] = b"artha-recovery/vault-key";
+const KEK_AAD: &[u8] = b"artha-recovery/phrase-key";
+
+/// Escrow file stored beside the vault. `wrapped_key` is the vault key sealed under
+/// a key derived from the recovery phrase. `wrapped_kek` is that phrase-derived key
+/// sealed under the vault key. The second copy lets a key rotation re-wrap the new
+/// vault key without asking for the phrase again.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EscrowFile {
+    format_version: u32,
+    salt: String,
+    #[serde(flatten)]
+    params: KdfParams,
+    wrapped_key: Sealed,
+    wrapped_kek: Sealed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// `vault.db.enc` → `vault.db.enc.recovery`.
+pub fn path_for(vault: &Path) -> PathBuf {
+    let mut path = vault.as_os_str().to_owned();
+    path.push(".recovery");
+    PathBuf::from(path)
+}
+
+/// Generates a new recovery phrase for `key` and writes the escrow file, replacing
+/// any earlier one (and so invalidating its phrase). The phrase is returned once
+/// and never stored.
+pub fn create(vault: &Path, key: &VaultKey) -> Result<String> {
+    create_with(vault, key, KdfParams::DEFAULT)
+}
+
+/// Recovers the vault key from `phrase`. Spacing, hyphens and case are ignored.
+pub fn recover_key(vault: &Path, phrase: &str) -> Result<VaultKey> {
+    let file = read(vault)?.ok_or_else(|| anyhow!("This vault has no recovery phrase"))?;
+    let secret = decode_phrase(phrase)?;
+    let kek = derive_key(
+        secret.as_slice(),
+        &decode_salt(&file)?,
+        file.params.validate()?,
+    )?;
+    let key = unseal(&kek, &file.wrapped_key, KEY_AAD)
+        .map_err(|_| anyhow!("Recovery phrase is incorrect"))?;
+    VaultKey::from_slice(&key).ok_or_else(|| anyhow!("Recovery file is corrupt"))
+}
+
+/// Re-wraps the escrowed key after a rotation so the existing phrase keeps working.
+/// A missing escrow file is fine; one that `old_key` can't open is left alone.
+pub fn rewrap(vault: &Path, old_key: &VaultKey, new_key: &VaultKey) -> Result<()> {
+    let Some(file) = read(vault)? else {
+        return Ok(());
+    };
+    let kek = match unseal(old_key, &file.wrapped_kek, KEK_AAD) {
+        Ok(kek) => VaultKey::from_slice(&kek).ok_or_else(|| anyhow!("Recovery file is corrupt"))?,
+        Err(_) => {
+            log::warn!("Recovery file does not match the vault key; leaving it unchanged");
+            return Ok(());
+        }
+    };
+    write(
+        vault,
+        &EscrowFile {
+            wrapped_key: seal(&kek, new_key.as_bytes(), KEY_AAD)?,
+            wrapped_kek: seal(new_key, kek.as_bytes(), KEK_AAD)?,
+            ..file
+        },
+    )
+}
+
+fn create_with(vault: &Path, key: &VaultKey, params: KdfParams) -> Result<String> {
+    let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(secret.as_mut_slice());
+    OsRng.fill_bytes(&mut salt);
+
+    let kek = derive_key(secret.as_slice(), &salt, params)?;
+    write(
+        vault,
+        &EscrowFile {
+            format_version: FORMAT_VERSION,
+            salt: hex::encode(salt),
+            params,
+            wrapped_key: seal(&kek, key.as_bytes(), KEY_AAD)?,
+            wrapped_kek: seal(key, kek.as_bytes(), KEK_AAD)?,
+        },
+    )?;
+    Ok(encode_phrase(&secret))
+}
+
+fn read(vault: &Path) -> Result<Option<EscrowFile>> {
+    let raw = match fs::read(path_for(vault)) {
+        Ok(raw) => raw,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error).context("Failed to read recovery file"),
+    };
+    let file: EscrowFile = serde_json::from_slice(&raw).context("Recovery file is corrupt")?;
+    if file.format_version != FORMAT_VERSION {
+        bail!("Unsupported recovery file version {}", file.format_version);
+    }
+    Ok(Some(file))
+}
+
+/// Writes through a temporary file so a crash never leaves a half-written escrow.
+fn write(vault: &Path, file: &EscrowFile) -> Result<()> {
+    let path = path_for(vault);
+    let mut partial = path.clone().into_os_string();
+    partial.push(".partial");
+    fs::write(&partial, serde_json::to_vec_pretty(file)?)
+        .context("Failed to write recovery file")?;
+    fs::rename(&partial, &path).context("Failed to finalise recovery file")
+}
+
+fn decode_salt(file: &EscrowFile) -> Result<Vec<u8>> {
+    let salt = hex::decode(&file.salt).context("Recovery file is corrupt")?;
+    if salt.len() != SALT_LEN {
+        bail!("Recovery file is corrupt");
+    }
+    Ok(salt)
+}
+
+fn seal(key: &VaultKey, plaintext: &[u8], aad: &[u8]) -> Result<Sealed> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = XChaCha20Poly1305::new(key.as_bytes().into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| anyhow!("Recovery key wrapping failed"))?;
+    Ok(Sealed {
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+fn unseal(key: &VaultKey, sealed: &Sealed, aad: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let nonce = hex::decode(&sealed.nonce).context("Recovery file is corrupt")?;
+    let ciphertext = hex::decode(&sealed.ciphertext).context("Recovery file is corrupt")?;
+    if nonce.len() != NONCE_LEN {
+        bail!("Recovery file is corrupt");
+    }
+    XChaCha20Poly1305::new(key.as_bytes().into())
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow!("Recovery key unwrapping failed"))
+}
+
+/// `XXXX-XXXX-…`: eight groups of four base32 symbols, written straight into the
+/// returned string so no intermediate copy is left behind.
+fn encode_phrase(secret: &[u8; SECRET_LEN]) -> String {
+    let symbols = SECRET_LEN * 8 / 5;
+    let mut phrase = String::with_capacity(symbols + symbols / GROUP_LEN);
+    let (mut buffer, mut bits, mut written) = (0u32, 0, 0);
+    for byte in secret {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            if written > 0 && written % GROUP_LEN == 0 {
+                phrase.push('-');
+            }
+            phrase.push(char::from(ALPHABET[((buffer >> bits) & 0x1f) as usize]));
+            written += 1;
+        }
+    }
+    phrase
+}
+
+fn decode_phrase(phrase: &str) -> Result<Zeroizing<[u8; SECRET_LEN]>> {
+    let mut secret = Zeroizing::new(Vec::with_capacity(SECRET_LEN));
+    let (mut buffer, mut bits, mut symbols) = (0u32, 0, 0);
+    for symbol in phrase.chars() {
+        if symbol.is_whitespace() || symbol == '-' {
+            continue;
+        }
+        // Crockford decoding folds the look-alikes it leaves out of the alphabet.
+        let symbol = match symbol.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            other => other,
+        };
+        let value = ALPHABET
+            .iter()
+            .position(|candidate| char::from(*candidate) == symbol)
+            .ok_or_else(|| anyhow!("Recovery phrase contains an invalid character: {symbol}"))?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        symbols += 1;
+        if bits >= 8 {
+            bits -= 8;
+            secret.push((buffer >> bits) as u8);
+        }
+    }
+    if symbols != SECRET_LEN * 8 / 5 {
+        bail!(
+            "Recovery phrase should have {} characters",
+            SECRET_LEN * 8 / 5
+        );
+    }
+    <[u8; SECRET_LEN]>::try_from(secret.as_slice())
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow!("Recovery phrase is malformed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn phrase_recovers_the_key_across_rotations() {
+        let dir = std::env::temp_dir().join(format!("artha-recovery-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let vault = dir.join("vault.db.enc");
+        let _ = fs::remove_file(path_for(&vault));
+
+        let (old_key, new_key) = (VaultKey::from([7; 32]), VaultKey::from([9; 32]));
+        let phrase = create_with(&vault, &old_key, FAST).unwrap();
+        assert_eq!(phrase.len(), 32 + 7);
+        assert_eq!(recover_key(&vault, &phrase).unwrap(), old_key);
+
+        let retyped = phrase.to_lowercase().replace('-', " ").replace('0', "o");
+        assert_eq!(recover_key(&vault, &retyped).unwrap(), old_key);
+
+        rewrap(&vault, &old_key, &new_key).unwrap();
+        assert_eq!(recover_key(&vault, &phrase).unwrap(), new_key);
+
+        let mut wrong = phrase.into_bytes();
+        wrong[0] = if wrong[0] == b'A' { b'B' } else { b'A' };
+        assert!(recover_key(&vault, std::str::from_utf8(&wrong).unwrap()).is_err());
+        assert!(recover_key(&vault, "too-short").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection};
+use time::Date;
+
+use crate::{
+    dto::{CategorySeries, PeriodTotals, ReportBucket, ReportGrouping, ReportResponse},
+    income, periods,
+};
+
+struct LedgerRow {
+    date: Date,
+    category: String,
+    description: String,
+    amount: f64,
+}
+
+pub fn build_report(
+    conn: &Connection,
+    start: Date,
+    end: Date,
+    grouping: ReportGrouping,
+) -> Result<ReportResponse> {
+    if end < start {
+        bail!("Report end date precedes start date");
+    }
+
+    let (previous_start, previous_end) = previous_range(start, end);
+    let (last_year_start, last_year_end) = (
+        periods::add_months(start, -12),
+        periods::add_months(end, -12),
+    );
+
+    let rows = load_rows(conn, start, end)?;
+    let previous_rows = load_rows(conn, previous_start, previous_end)?;
+    let last_year_rows = load_rows(conn, last_year_start, last_year_end)?;
+
+    let mut buckets = Vec::new();
+    let mut cursor = bucket_start(start, grouping);
+    while cursor <= end {
+        let next = next_bucket(cursor, grouping);
+        buckets.push(ReportBucket {
+            label: bucket_label(cursor, grouping),
+            start: periods::format_date(cursor),
+            end: periods::format_date(periods::shift_days(next, -1)),
+            spend: 0.0,
+            income: 0.0,
+        });
+        cursor = next;
+    }
+
+    let first_bucket = bucket_start(start, grouping);
+    let bucket_count = buckets.len();
+    let mut series: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+
+    for row in &rows {
+        let index = bucket_index(first_bucket, row.date, grouping);
+        let Some(bucket) = buckets.get_mut(index) else {
+            continue;
+        };
+
+        if row.amount < 0.0 {
+            bucket.spend += row.amount.abs();
+            series
+                .entry(row.category.clone())
+                .or_insert_with(|| vec![0.0; bucket_count])[index] += row.amount.abs();
+        } else if is_income(row) {
+            bucket.income += row.amount;
+        }
+    }
+
+    let previous_by_category = spend_by_category(&previous_rows);
+    let last_year_by_category = spend_by_category(&last_year_rows);
+
+    let mut categories = series
+        .into_iter()
+        .map(|(category, amounts)| {
+            let total = amounts.iter().sum();
+            CategorySeries {
+                previous_total: previous_by_category.get(&category).copied().unwrap_or(0.0),
+                last_year_total: last_year_by_category.get(&category).copied().unwrap_or(0.0),
+                category,
+                amounts,
+                total,
+            }
+        })
+        .collect::<Vec<_>>();
+    categories.sort_by(|a, b| b.total.total_cmp(&a.total));
+
+    Ok(ReportResponse {
+        grouping,
+        current: totals(&rows, start, end),
+        previous_period: totals(&previous_rows, previous_start, previous_end),
+        same_period_last_year: totals(&last_year_rows, last_year_start, last_year_end),
+        buckets,
+        categories,
+    })
+}
+
+fn load_rows(conn: &Connection, start: Date, end: Date) -> Result<Vec<LedgerRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, COALESCE(category, 'Uncategorized'), description, amount FROM transactions WHERE date >= ?1 AND date <= ?2 AND id NOT IN (SELECT transaction_id FROM card_payments)",
+    )?;
+
+    let rows = stmt
+        .query_map(
+            params![periods::format_date(start), periods::format_date(end)],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, f64>(3)?,
+                ))
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch report rows")?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(date, category, description, amount)| {
+            let date = periods::parse_date(&date).ok()?;
+            Some(LedgerRow {
+                date,
+                category,
+                description,
+                amount,
+            })
+        })
+        .collect())
+}
+
+fn is_income(row: &LedgerRow) -> bool {
+    income::classify(&row.description, Some(&row.category)).is_income()
+}
+
+fn spend_by_category(rows: &[LedgerRow]) -> BTreeMap<String, f64> {
+    let mut totals = BTreeMap::new();
+    for row in rows.iter().filter(|row| row.amount < 0.0) {
+        *totals.entry(row.category.clone()).or_insert(0.0) += row.amount.abs();
+    }
+    totals
+}
+
+fn totals(rows: &[LedgerRow], start: Date, end: Date) -> PeriodTotals {
+    let spend = rows
+        .iter()
+        .filter(|row| row.amount < 0.0)
+        .map(|row| row.amount.abs())
+        .sum();
+    let income = rows
+        .iter()
+        .filter(|row| row.amount > 0.0 && is_income(row))
+        .map(|row| row.amount)
+        .sum();
+
+    PeriodTotals {
+        start: periods::format_date(start),
+        end: periods::format_date(end),
+        spend,
+        income,
+    }
+}
+
+/// The range of equal length immediately before `start..=end`. Ranges made of whole
+/// months shift by months so that e.g. a quarter compares against the prior quarter.
+fn previous_range(start: Date, end: Date) -> (Date, Date) {
+    let day_after_end = periods::shift_days(end, 1);
+    if start.day() == 1 && day_after_end.day() == 1 {
+        let months = periods::months_between(start, day_after_end);
+        return (
+            periods::add_months(start, -months),
+            periods::shift_days(start, -1),
+        );
+    }
+
+    let length = periods::days_between(start, end) + 1;
+    (
+        periods::shift_days(start, -length),
+        periods::shift_days(start, -1),
+    )
+}
+
+fn bucket_start(date: Date, grouping: ReportGrouping) -> Date {
+    match grouping {
+        ReportGrouping::Day => date,
+        ReportGrouping::Week => {
+            periods::shift_days(date, -(date.weekday().number_days_from_monday() as i64))
+        }
+        ReportGrouping::Month => periods::month_start(date),
+        ReportGrouping::Quarter => {
+            let month_index = u8::from(date.month()) as i32 - 1;
+            periods::add_months(periods::month_start(date), -(month_index % 3))
+        }
+        ReportGrouping::FinancialYear => periods::financial_year_start(date),
+    }
+}
+
+fn next_bucket(start: Date, grouping: ReportGrouping) -> Date {
+    match grouping {
+        ReportGrouping::Day => periods::shift_days(start, 1),
+        ReportGrouping::Week => periods::shift_days(start, 7),
+        ReportGrouping::Month => periods::add_months(start, 1),
+        ReportGrouping::Quarter => periods::add_months(start, 3),
+        ReportGrouping::FinancialYear => periods::add_months(start, 12),
+    }
+}
+
+fn bucket_index(first: Date, date: Date, grouping: ReportGrouping) -> usize {
+    let start = bucket_start(date, grouping);
+    let months = || periods::months_between(first, start);
+
+    let index = match grouping {
+        ReportGrouping::Day => periods::days_between(first, start),
+        ReportGrouping::Week => periods::days_between(first, start) / 7,
+        ReportGrouping::Month => months() as i64,
+        ReportGrouping::Quarter => months() as i64 / 3,
+        ReportGrouping::FinancialYear => months() as i64 / 12,
+    };
+    index.max(0) as usize
+}
+
+fn bucket_label(start: Date, grouping: ReportGrouping) -> String {
+    match grouping {
+        ReportGrouping::Day | ReportGrouping::Week => periods::format_date(start),
+        ReportGrouping::Month => format!("{}-{:02}", start.year(), u8::from(start.month())),
+        ReportGrouping::Quarter => {
+            format!(
+                "{}-Q{}",
+                start.year(),
+                (u8::from(start.month()) - 1) / 3 + 1
+            )
+        }
+        ReportGrouping::FinancialYear => periods::financial_year_label(start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+    use time::macros::date;
+
+    #[test]
+    fn buckets_and_compares_against_previous_and_last_year() {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        for (date, category, amount) in [
+            ("2025-01-05", "Food", -100.0),
+            ("2025-03-31", "Food", -50.0),
+            ("2025-02-01", "Salary", 1000.0),
+            ("2024-12-31", "Food", -70.0),
+            ("2024-02-10", "Food", -30.0),
+        ] {
+            conn.execute(
+                "INSERT INTO transactions (date, description, amount, flow, category)
+                 VALUES (?1, 'ENTRY', ?2, 'debit', ?3)",
+                params![date, amount, category],
+            )
+            .unwrap();
+        }
+        // Paying the card bill settles spend already counted on the card.
+        conn.execute_batch(
+            "INSERT INTO credit_cards (id, name, statement_day, due_day) VALUES (1, 'Card', 10, 28);
+             INSERT INTO transactions (id, date, description, amount, flow)
+             VALUES (99, '2025-01-20', 'CC BILL PAYMENT', -5000.0, 'debit');
+             INSERT INTO card_payments (transaction_id, card_id) VALUES (99, 1);",
+        )
+        .unwrap();
+
+        let report = build_report(
+            &conn,
+            date!(2025 - 01 - 01),
+            date!(2025 - 03 - 31),
+            ReportGrouping::Month,
+        )
+        .unwrap();
+        let labels = report
+            .buckets
+            .iter()
+            .map(|bucket| bucket.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["2025-01", "2025-02", "2025-03"]);
+        assert_eq!(report.buckets[0].spend, 100.0);
+        assert_eq!(report.buckets[1].income, 1000.0);
+        assert_eq!(report.buckets[2].spend, 50.0);
+        assert_eq!(report.buckets[2].end, "2025-03-31");
+
+        // A whole quarter compares against the previous quarter, not 90 days back.
+        assert_eq!(report.previous_period.start, "2024-10-01");
+        assert_eq!(report.previous_period.spend, 70.0);
+        assert_eq!(report.same_period_last_year.start, "2024-01-01");
+        assert_eq!(report.same_period_last_year.spend, 30.0);
+        assert_eq!(report.categories[0].amounts, [100.0, 0.0, 50.0]);
+        assert_eq!(report.categories[0].previous_total, 70.0);
+
+        let quarters = build_report(
+            &conn,
+            date!(2025 - 02 - 15),
+            date!(2025 - 05 - 10),
+            ReportGrouping::Quarter,
+        )
+        .unwrap();
+        let labels = quarters
+            .buckets
+            .iter()
+            .map(|bucket| bucket.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["2025-Q1", "2025-Q2"]);
+        assert_eq!(quarters.buckets[0].start, "2025-01-01");
+        assert_eq!(quarters.buckets[0].spend, 50.0);
+    }
+}
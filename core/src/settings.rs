@@ -1,6 +1,14 @@
-use crate::dto::{AppSettings, BudgetConfig};
-use anyhow::{Context, Result};
-use rusqlite::{params, Connection};
+use crate::{
+    audit,
+    dto::{AppSettings, BudgetConfig},
+};
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub const AUTO_LOCK_MINUTES: &str = "auto_lock_minutes";
+const DEFAULT_AUTO_LOCK_MINUTES: u32 = 5;
+/// Keys the vault maintains itself; `set_setting` refuses to write them.
+const RESERVED: &[&str] = &[audit::ANCHOR];
 
 pub fn get_app_settings(conn: &Connection) -> Result<AppSettings> {
     let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
@@ -10,20 +18,47 @@ pub fn get_app_settings(conn: &Connection) -> Result<AppSettings> {
 
     let mut theme = "system".to_string();
     let mut accounts = None;
+    let mut auto_lock_minutes = DEFAULT_AUTO_LOCK_MINUTES;
 
     for row in rows {
         let (key, value) = row?;
         match key.as_str() {
             "theme" => theme = value,
             "accounts" => accounts = Some(value),
+            AUTO_LOCK_MINUTES => match value.parse() {
+                Ok(minutes) => auto_lock_minutes = minutes,
+                Err(_) => log::warn!("Ignoring invalid {AUTO_LOCK_MINUTES} setting: {value}"),
+            },
             _ => {}
         }
     }
 
-    Ok(AppSettings { theme, accounts })
+    Ok(AppSettings {
+        theme,
+        accounts,
+        auto_lock_minutes,
+    })
+}
+
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key=?1",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+    .context("Failed to read setting")
 }
 
 pub fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    if RESERVED.contains(&key) {
+        bail!("{key} is maintained by the vault and cannot be set");
+    }
+    if key == AUTO_LOCK_MINUTES {
+        value
+            .parse::<u32>()
+            .with_context(|| format!("{AUTO_LOCK_MINUTES} must be a whole number of minutes"))?;
+    }
     conn.execute(
         "INSERT INTO settings (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value=excluded.value",
         params![key, value],
@@ -46,6 +81,16 @@ pub fn get_budgets(conn: &Connection) -> Result<Vec<BudgetConfig>> {
     Ok(rows)
 }
 
+pub fn get_budget(conn: &Connection, category: &str) -> Result<Option<f64>> {
+    conn.query_row(
+        "SELECT cap FROM budgets WHERE category=?1",
+        params![category],
+        |row| row.get(0),
+    )
+    .optional()
+    .context("Failed to read budget")
+}
+
 pub fn set_budget(conn: &Connection, category: &str, cap: f64) -> Result<()> {
     conn.execute(
         "INSERT INTO budgets (category, cap) VALUES (?1, ?2) ON CONFLICT(category) DO UPDATE SET cap=excluded.cap",
@@ -54,3 +99,14 @@ pub fn set_budget(conn: &Connection, category: &str, cap: f64) -> Result<()> {
     .context("Failed to update budget")?;
     Ok(())
 }
+
+pub fn delete_budget(conn: &Connection, category: &str) -> Result<()> {
+    conn.execute("DELETE FROM budgets WHERE category=?1", params![category])
+        .context("Failed to delete budget")?;
+    conn.execute(
+        "DELETE FROM budget_thresholds WHERE category=?1",
+        params![category],
+    )
+    .context("Failed to delete budget thresholds")?;
+    Ok(())
+}
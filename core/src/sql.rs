@@ -0,0 +1,58 @@
+use anyhow::{anyhow, bail, Context, Result};
+use rusqlite::{
+    types::{Value as SqlValue, ValueRef},
+    Connection,
+};
+use serde_json::{json, Value};
+
+/// Key of the object a BLOB is serialised as, holding its hex encoding.
+const BLOB_MARKER: &str = "$blob";
+
+/// Columns of `table`, in declaration order.
+pub fn column_names(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote(table)))?;
+    let names = stmt
+        .query_map([], |row| row.get(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(names)
+}
+
+/// Quotes `identifier` for use as a table or column name.
+pub fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Serialises a column value; BLOBs become `{"$blob": "<hex>"}`.
+pub fn to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(int) => json!(int),
+        ValueRef::Real(real) => json!(real),
+        ValueRef::Text(text) => Value::String(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(blob) => json!({ BLOB_MARKER: hex::encode(blob) }),
+    }
+}
+
+/// Reverses [`to_json`].
+pub fn from_json(value: &Value) -> Result<SqlValue> {
+    Ok(match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(flag) => SqlValue::Integer(i64::from(*flag)),
+        Value::Number(number) => match number.as_i64() {
+            Some(int) => SqlValue::Integer(int),
+            None => SqlValue::Real(
+                number
+                    .as_f64()
+                    .ok_or_else(|| anyhow!("Unrepresentable number in stored row"))?,
+            ),
+        },
+        Value::String(text) => SqlValue::Text(text.clone()),
+        Value::Object(object) => match object.get(BLOB_MARKER).and_then(Value::as_str) {
+            Some(encoded) => {
+                SqlValue::Blob(hex::decode(encoded).context("Invalid blob in stored row")?)
+            }
+            None => bail!("Unexpected object value in stored row"),
+        },
+        Value::Array(_) => bail!("Unexpected array value in stored row"),
+    })
+}
@@ -1,39 +1,212 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
+use zeroize::Zeroizing;
 
-pub fn connection_pool(path: &Path, key: &[u8; 32]) -> Result<Pool<SqliteConnectionManager>> {
+use crate::{kdf::KEY_LEN, key_provider::VaultKey};
+
+/// First bytes of every unencrypted SQLite database file.
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Bumped whenever `migrate` changes the shape of an existing table. Recorded in
+/// `PRAGMA user_version` and in exported vault bundles.
+pub const SCHEMA_VERSION: i64 = 3;
+
+pub fn connection_pool(path: &Path, key: &VaultKey) -> Result<Pool<SqliteConnectionManager>> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("Failed to create storage directory: {}", parent.display()))?;
     }
 
-    let key_hex = hex::encode(key);
-    let key_statement = format!("PRAGMA key = \"x'{}'\";", key_hex);
+    if is_plaintext(path) && cfg!(feature = "sqlcipher") {
+        encrypt_legacy_vault(path, key)?;
+    }
+
+    // Fails fast on a wrong key or missing SQLCipher; r2d2 would otherwise keep
+    // retrying the connection init until its checkout timeout.
+    drop(open_keyed(path, key)?);
+
+    // New pooled connections need the key for as long as the pool lives. Only the
+    // wiped-on-drop key is kept; its hex form is rebuilt per connection and dropped.
+    let key = key.clone();
 
     let manager = SqliteConnectionManager::file(path).with_init(move |conn| {
+        // The key has to be the first statement: anything else reads the header first.
+        apply_key(conn, &key_pragma("key", &key))?;
         conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
             .ok();
 
-        // SQLCipher pragma. Ignored silently if the binary is not compiled with SQLCipher.
-        if let Err(error) = conn.execute_batch(&key_statement) {
-            log::warn!("PRAGMA key failed (is SQLCipher installed?): {error}");
-        }
-
         Ok(())
     });
 
     let pool = Pool::new(manager).context("Failed to create SQLite pool")?;
     {
-        let conn = pool.get().context("Pool checkout failed during migration")?;
+        let conn = pool
+            .get()
+            .context("Pool checkout failed during migration")?;
         migrate(&conn)?;
     }
     Ok(pool)
 }
 
+/// Opens a standalone connection (outside the pool) to an encrypted database file
+/// such as a backup snapshot.
+pub fn open_keyed(path: &Path, key: &VaultKey) -> Result<Connection> {
+    let conn = Connection::open(path)
+        .with_context(|| format!("Failed to open database: {}", path.display()))?;
+    if let Err(error) = apply_key(&conn, &key_pragma("key", key)) {
+        if is_plaintext(path) && cfg!(feature = "sqlcipher") {
+            bail!(KeyError::UnencryptedLegacyVault);
+        }
+        return Err(error.into());
+    }
+    Ok(conn)
+}
+
+/// Whether `path` holds an unencrypted SQLite database, as vaults written before
+/// SQLCipher did.
+fn is_plaintext(path: &Path) -> bool {
+    let mut header = [0; PLAINTEXT_HEADER.len()];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| &header == PLAINTEXT_HEADER)
+}
+
+/// Encrypts a legacy plaintext vault in place under `key`. The original is only
+/// replaced once the encrypted copy opens with the key and passes
+/// `PRAGMA integrity_check`.
+fn encrypt_legacy_vault(path: &Path, key: &VaultKey) -> Result<()> {
+    log::warn!("Encrypting legacy unencrypted vault {}", path.display());
+    let staged = with_suffix(path, ".encrypting");
+    let _ = fs::remove_file(&staged);
+
+    if let Err(error) = export_encrypted(path, &staged, key) {
+        let _ = fs::remove_file(&staged);
+        return Err(error.context("Failed to encrypt legacy vault; it was left unchanged"));
+    }
+
+    fs::rename(&staged, path).context("Failed to replace legacy vault")?;
+    for suffix in ["-wal", "-shm"] {
+        let _ = fs::remove_file(with_suffix(path, suffix));
+    }
+    Ok(())
+}
+
+/// Copies the plaintext database at `path` into a new database at `target` keyed
+/// with `key`, using SQLCipher's `sqlcipher_export`, and verifies the copy.
+fn export_encrypted(path: &Path, target: &Path, key: &VaultKey) -> Result<()> {
+    let plain = Connection::open(path).context("Failed to open legacy vault")?;
+    let version: i64 = plain.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let quoted = target
+        .to_str()
+        .context("Vault path is not valid UTF-8")?
+        .replace('\'', "''");
+    plain
+        .execute_batch(&keyed_statement(
+            &format!("ATTACH DATABASE '{quoted}' AS encrypted KEY"),
+            key,
+        ))
+        .context("Failed to attach encrypted copy")?;
+    plain
+        .query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+        .context("sqlcipher_export failed")?;
+    plain.execute_batch(&format!(
+        "PRAGMA encrypted.user_version = {version}; DETACH DATABASE encrypted;"
+    ))?;
+    drop(plain);
+
+    let copy = open_keyed(target, key)?;
+    let check: String = copy.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        bail!("Encrypted copy failed integrity check: {check}");
+    }
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file = path.as_os_str().to_owned();
+    file.push(suffix);
+    PathBuf::from(file)
+}
+
+/// Re-encrypts the database behind `conn` under `new_key`. Other connections still
+/// hold the old key and must be reopened.
+pub fn rekey(conn: &Connection, new_key: &VaultKey) -> Result<()> {
+    let cipher_version: Option<String> = conn
+        .query_row("PRAGMA cipher_version", [], |row| row.get(0))
+        .optional()?;
+    if cipher_version.is_none() {
+        bail!(KeyError::EncryptionUnavailable);
+    }
+    conn.execute_batch(&key_pragma("rekey", new_key))
+        .context("PRAGMA rekey failed")
+}
+
+/// `PRAGMA key = "x'…'"`; see [`keyed_statement`].
+fn key_pragma(pragma: &str, key: &VaultKey) -> Zeroizing<String> {
+    keyed_statement(&format!("PRAGMA {pragma} ="), key)
+}
+
+/// `prefix` followed by the key as a blob literal, built in a buffer sized up front
+/// so no reallocation leaves a stray copy of the hex key behind, and wiped on drop.
+fn keyed_statement(prefix: &str, key: &VaultKey) -> Zeroizing<String> {
+    const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+    const FRAME: &str = " \"x''\";";
+    let mut statement = Zeroizing::new(String::with_capacity(
+        FRAME.len() + prefix.len() + 2 * KEY_LEN,
+    ));
+    statement.push_str(prefix);
+    statement.push_str(" \"x'");
+    for byte in key.as_bytes() {
+        statement.push(char::from(HEX_DIGITS[usize::from(byte >> 4)]));
+        statement.push(char::from(HEX_DIGITS[usize::from(byte & 0x0f)]));
+    }
+    statement.push_str("'\";");
+    statement
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyError {
+    #[error("SQLCipher is not available; refusing to open the vault unencrypted")]
+    EncryptionUnavailable,
+    #[error("Vault key was rejected (wrong key or corrupt file)")]
+    KeyRejected,
+    #[error("Vault is an unencrypted legacy database and has not been migrated")]
+    UnencryptedLegacyVault,
+}
+
+/// Keys the connection and proves it took effect. Plain SQLite accepts `PRAGMA key`
+/// as a no-op, so SQLCipher must answer `PRAGMA cipher_version`, and a read of
+/// `sqlite_master` must succeed with the key. Without SQLCipher this fails closed
+/// unless built with the `insecure-plaintext` development feature.
+fn apply_key(conn: &Connection, key_statement: &str) -> rusqlite::Result<()> {
+    let fail = |error: KeyError| rusqlite::Error::UserFunctionError(Box::new(error));
+
+    conn.execute_batch(key_statement)?;
+    let cipher_version: Option<String> = conn
+        .query_row("PRAGMA cipher_version", [], |row| row.get(0))
+        .optional()?;
+    if cipher_version.is_none() {
+        if !cfg!(feature = "insecure-plaintext") {
+            return Err(fail(KeyError::EncryptionUnavailable));
+        }
+        log::warn!("SQLCipher unavailable: vault is stored UNENCRYPTED (insecure-plaintext)");
+    }
+
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+        row.get::<_, i64>(0)
+    })
+    .map_err(|_| fail(KeyError::KeyRejected))?;
+    Ok(())
+}
+
 pub fn migrate(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         r#"
@@ -44,6 +217,8 @@ pub fn migrate(conn: &Connection) -> Result<()> {
             amount REAL NOT NULL,
             flow TEXT NOT NULL,
             suggested_category TEXT,
+            account TEXT,
+            from_memory INTEGER NOT NULL DEFAULT 0,
             created_at TEXT NOT NULL DEFAULT (datetime('now'))
         );
 
@@ -54,6 +229,7 @@ pub fn migrate(conn: &Connection) -> Result<()> {
             amount REAL NOT NULL,
             flow TEXT NOT NULL,
             category TEXT,
+            account TEXT,
             created_at TEXT NOT NULL DEFAULT (datetime('now'))
         );
 
@@ -64,49 +240,351 @@ pub fn migrate(conn: &Connection) -> Result<()> {
             updated_at TEXT NOT NULL DEFAULT (datetime('now'))
         );
 
+        CREATE TABLE IF NOT EXISTS categorization_provenance (
+            token TEXT NOT NULL,
+            transaction_id INTEGER NOT NULL,
+            category TEXT NOT NULL,
+            taught_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (token, transaction_id)
+        );
+
         CREATE TABLE IF NOT EXISTS budgets (
             category TEXT PRIMARY KEY,
             cap REAL NOT NULL
         );
 
+        CREATE TABLE IF NOT EXISTS budget_thresholds (
+            category TEXT NOT NULL,
+            threshold REAL NOT NULL,
+            PRIMARY KEY (category, threshold)
+        );
+
+        CREATE TABLE IF NOT EXISTS budget_alert_log (
+            category TEXT NOT NULL,
+            period TEXT NOT NULL,
+            threshold REAL NOT NULL,
+            spent REAL NOT NULL,
+            fired_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (category, period, threshold)
+        );
+
+        CREATE TABLE IF NOT EXISTS tax_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            section TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            pattern TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS tax_tags (
+            transaction_id INTEGER PRIMARY KEY,
+            section TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS holdings (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            asset_class TEXT NOT NULL,
+            symbol TEXT NOT NULL,
+            folio TEXT NOT NULL DEFAULT '',
+            equity_oriented INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE (symbol, folio)
+        );
+
+        CREATE TABLE IF NOT EXISTS holding_lots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            holding_id INTEGER NOT NULL REFERENCES holdings(id),
+            kind TEXT NOT NULL,
+            date TEXT NOT NULL,
+            units REAL NOT NULL,
+            price REAL NOT NULL,
+            charges REAL NOT NULL DEFAULT 0,
+            external_ref TEXT UNIQUE,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        CREATE TABLE IF NOT EXISTS holding_prices (
+            holding_id INTEGER NOT NULL REFERENCES holdings(id),
+            date TEXT NOT NULL,
+            price REAL NOT NULL,
+            PRIMARY KEY (holding_id, date)
+        );
+
+        CREATE TABLE IF NOT EXISTS holding_cash_flows (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            holding_id INTEGER NOT NULL REFERENCES holdings(id),
+            kind TEXT NOT NULL,
+            date TEXT NOT NULL,
+            amount REAL NOT NULL,
+            external_ref TEXT UNIQUE
+        );
+
+        CREATE TABLE IF NOT EXISTS broker_fundings (
+            transaction_id INTEGER PRIMARY KEY,
+            broker TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS loans (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            principal REAL NOT NULL,
+            annual_rate REAL NOT NULL,
+            tenure_months INTEGER NOT NULL,
+            start_date TEXT NOT NULL,
+            emi REAL,
+            match_pattern TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS loan_prepayments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            loan_id INTEGER NOT NULL REFERENCES loans(id),
+            date TEXT NOT NULL,
+            amount REAL NOT NULL,
+            transaction_id INTEGER
+        );
+
+        CREATE TABLE IF NOT EXISTS loan_payments (
+            transaction_id INTEGER PRIMARY KEY,
+            loan_id INTEGER NOT NULL REFERENCES loans(id),
+            installment INTEGER NOT NULL,
+            principal REAL NOT NULL,
+            interest REAL NOT NULL,
+            UNIQUE(loan_id, installment)
+        );
+
+        CREATE TABLE IF NOT EXISTS credit_cards (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            last_four TEXT,
+            statement_day INTEGER NOT NULL,
+            due_day INTEGER NOT NULL,
+            credit_limit REAL,
+            spend_pattern TEXT,
+            payment_pattern TEXT,
+            min_due_percent REAL NOT NULL DEFAULT 5,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS card_transactions (
+            transaction_id INTEGER PRIMARY KEY,
+            card_id INTEGER NOT NULL REFERENCES credit_cards(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS card_payments (
+            transaction_id INTEGER PRIMARY KEY,
+            card_id INTEGER NOT NULL REFERENCES credit_cards(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS goals (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            target_amount REAL,
+            expense_multiple REAL,
+            target_date TEXT,
+            expected_return REAL NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS goal_holdings (
+            goal_id INTEGER NOT NULL REFERENCES goals(id),
+            holding_id INTEGER NOT NULL REFERENCES holdings(id),
+            share REAL NOT NULL DEFAULT 1,
+            PRIMARY KEY (goal_id, holding_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS goal_accounts (
+            goal_id INTEGER NOT NULL REFERENCES goals(id),
+            account TEXT NOT NULL,
+            PRIMARY KEY (goal_id, account)
+        );
+
+        CREATE TABLE IF NOT EXISTS goal_contributions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            goal_id INTEGER NOT NULL REFERENCES goals(id),
+            date TEXT NOT NULL,
+            amount REAL NOT NULL,
+            transaction_id INTEGER UNIQUE
+        );
+
         CREATE TABLE IF NOT EXISTS settings (
             key TEXT PRIMARY KEY,
             value TEXT NOT NULL
         );
+
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            occurred_at TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            entity TEXT NOT NULL,
+            before_json TEXT,
+            after_json TEXT,
+            prev_hash TEXT NOT NULL UNIQUE,
+            hash TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log(entity);
+        CREATE INDEX IF NOT EXISTS idx_categorization_provenance_transaction
+            ON categorization_provenance(transaction_id);
+
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_log is append-only');
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_log is append-only');
+        END;
+
+        CREATE TABLE IF NOT EXISTS undo_journal (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            occurred_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+            operation TEXT NOT NULL,
+            label TEXT NOT NULL,
+            slices_json TEXT NOT NULL,
+            undone INTEGER NOT NULL DEFAULT 0
+        );
     "#,
     )
     .context("Base schema migration failed")?;
 
-    seed_default_budgets(conn)?;
+    ensure_column(conn, "inbox", "account", "TEXT")?;
+    ensure_column(conn, "transactions", "account", "TEXT")?;
+    ensure_column(conn, "inbox", "from_memory", "INTEGER NOT NULL DEFAULT 0")?;
+    ensure_column(
+        conn,
+        "holdings",
+        "equity_oriented",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+        .context("Failed to record schema version")?;
+
     Ok(())
 }
 
-fn seed_default_budgets(conn: &Connection) -> Result<()> {
-    let existing: i64 = conn
-        .query_row("SELECT COUNT(1) FROM budgets", [], |row| row.get(0))
-        .unwrap_or(0);
+/// Adds a column introduced after its table first shipped; `CREATE TABLE IF NOT
+/// EXISTS` leaves tables in older vaults as they were.
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))
+        .with_context(|| format!("Failed to add {table}.{column}"))?;
+    }
+    Ok(())
+}
+
+// The insecure-plaintext build has nothing to prove about encryption.
+#[cfg(all(test, any(feature = "sqlcipher", not(feature = "insecure-plaintext"))))]
+mod tests {
+    use super::*;
 
-    if existing > 0 {
-        return Ok(());
+    fn scratch_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("artha-storage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
     }
 
-    let defaults = [
-        ("Housing", 1800.0),
-        ("Groceries", 700.0),
-        ("Dining", 350.0),
-        ("Transportation", 250.0),
-        ("Discretionary", 500.0),
-    ];
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn vault_file_is_encrypted_on_disk() {
+        let path = scratch_path("encrypted.db");
+        let _ = fs::remove_file(&path);
+        {
+            let pool = connection_pool(&path, &VaultKey::from([7; 32])).unwrap();
+            let conn = pool.get().unwrap();
+            conn.execute(
+                "INSERT INTO settings (key, value) VALUES ('marker', 'plaintext-canary')",
+                [],
+            )
+            .unwrap();
+        }
 
-    let tx = conn.transaction().context("Failed to open tx for budgets seed")?;
-    {
-        let mut stmt =
-            tx.prepare("INSERT INTO budgets (category, cap) VALUES (?1, ?2)")?;
-        for (category, cap) in defaults {
-            stmt.execute((category, cap))
-                .with_context(|| format!("Seed insert failed for {category}"))?;
+        let bytes = fs::read(&path).unwrap();
+        assert!(!bytes.starts_with(b"SQLite format 3\0"));
+        assert!(!bytes
+            .windows(b"plaintext-canary".len())
+            .any(|window| window == b"plaintext-canary"));
+
+        let error = open_keyed(&path, &VaultKey::from([8; 32])).unwrap_err();
+        assert!(error.to_string().contains("rejected"), "{error}");
+        assert!(open_keyed(&path, &VaultKey::from([7; 32])).is_ok());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn encrypts_a_legacy_plaintext_vault_once() {
+        let path = scratch_path("legacy.db");
+        let _ = fs::remove_file(&path);
+        {
+            let plain = Connection::open(&path).unwrap();
+            plain
+                .execute_batch(
+                    "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+                     INSERT INTO settings (key, value) VALUES ('marker', 'legacy');
+                     PRAGMA user_version = 1;",
+                )
+                .unwrap();
+        }
+        let key = VaultKey::from([9; 32]);
+        let error = open_keyed(&path, &key).unwrap_err();
+        assert!(error.to_string().contains("legacy"), "{error}");
+
+        let pool = connection_pool(&path, &key).unwrap();
+        let marker: String = pool
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'marker'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(marker, "legacy");
+        drop(pool);
+
+        assert!(!is_plaintext(&path));
+        assert!(!with_suffix(&path, ".encrypting").exists());
+        assert!(open_keyed(&path, &VaultKey::from([1; 32])).is_err());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn rekey_replaces_the_vault_key() {
+        let path = scratch_path("rekey.db");
+        let _ = fs::remove_file(&path);
+        {
+            let pool = connection_pool(&path, &VaultKey::from([1; 32])).unwrap();
+            let conn = pool.get().unwrap();
+            conn.execute("INSERT INTO settings (key, value) VALUES ('a', 'b')", [])
+                .unwrap();
+            rekey(&conn, &VaultKey::from([2; 32])).unwrap();
         }
+
+        assert!(open_keyed(&path, &VaultKey::from([1; 32])).is_err());
+        let conn = open_keyed(&path, &VaultKey::from([2; 32])).unwrap();
+        let value: String = conn
+            .query_row("SELECT value FROM settings WHERE key = 'a'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(value, "b");
+    }
+
+    #[cfg(not(any(feature = "sqlcipher", feature = "insecure-plaintext")))]
+    #[test]
+    fn refuses_plaintext_vault_without_sqlcipher() {
+        let error = open_keyed(&scratch_path("plain.db"), &VaultKey::from([7; 32])).unwrap_err();
+        assert!(error.to_string().contains("unencrypted"), "{error}");
     }
-    tx.commit().context("Commit budgets seed failed")?;
-    Ok(())
 }
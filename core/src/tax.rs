@@ -0,0 +1,400 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    dto::{HraExemption, TaxItem, TaxReport, TaxRule, TaxRuleKind, TaxSection, TaxSectionSummary},
+    periods,
+};
+
+/// Keyword rules used for a section until the user configures their own.
+const DEFAULT_KEYWORDS: &[(TaxSection, &[&str])] = &[
+    (
+        TaxSection::Section80C,
+        &[
+            "ELSS",
+            "PPF",
+            "EPF",
+            "VPF",
+            "LIC",
+            "LIFE INSURANCE",
+            "SUKANYA",
+            "NSC",
+            "TAX SAVER",
+        ],
+    ),
+    (
+        TaxSection::Section80D,
+        &[
+            "HEALTH INSURANCE",
+            "MEDICLAIM",
+            "STAR HEALTH",
+            "NIVA BUPA",
+            "CARE HEALTH",
+            "HDFC ERGO",
+        ],
+    ),
+    (TaxSection::Section80Ccd1b, &["NPS", "PRAN"]),
+    (TaxSection::Hra, &["RENT"]),
+    (
+        TaxSection::HomeLoanInterest,
+        &["HOME LOAN INT", "HOUSING LOAN INT"],
+    ),
+];
+
+pub fn get_rules(conn: &Connection) -> Result<Vec<TaxRule>> {
+    let mut stmt =
+        conn.prepare("SELECT id, section, kind, pattern FROM tax_rules ORDER BY section, id")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch tax rules")?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, section, kind, pattern)| {
+            Some(TaxRule {
+                id: Some(id),
+                section: TaxSection::parse(&section)?,
+                kind: TaxRuleKind::parse(&kind)?,
+                pattern,
+            })
+        })
+        .collect())
+}
+
+pub fn add_rule(conn: &Connection, rule: &TaxRule) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO tax_rules (section, kind, pattern) VALUES (?1, ?2, ?3)",
+        params![
+            rule.section.as_str(),
+            rule.kind.as_str(),
+            rule.pattern.trim()
+        ],
+    )
+    .context("Failed to add tax rule")?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn delete_rule(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM tax_rules WHERE id=?1", params![id])
+        .context("Failed to delete tax rule")?;
+    Ok(())
+}
+
+/// Pins a transaction to `section`, or excludes it from every section when `None`,
+/// overriding whatever the rules would decide.
+pub fn tag_transaction(
+    conn: &Connection,
+    transaction_id: i64,
+    section: Option<TaxSection>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO tax_tags (transaction_id, section) VALUES (?1, ?2) ON CONFLICT(transaction_id) DO UPDATE SET section=excluded.section",
+        params![transaction_id, section.map(|section| section.as_str())],
+    )
+    .context("Failed to tag transaction")?;
+    Ok(())
+}
+
+pub fn clear_tag(conn: &Connection, transaction_id: i64) -> Result<()> {
+    conn.execute(
+        "DELETE FROM tax_tags WHERE transaction_id=?1",
+        params![transaction_id],
+    )
+    .context("Failed to clear transaction tag")?;
+    Ok(())
+}
+
+/// Totals deductible payments for the financial year labelled `financial_year`
+/// (e.g. `FY2024-25`).
+pub fn build_report(conn: &Connection, financial_year: &str) -> Result<TaxReport> {
+    let (start, end) = periods::parse_financial_year(financial_year)?;
+    let rules = effective_rules(get_rules(conn)?);
+
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.date, t.description, t.amount, t.category, tt.transaction_id IS NOT NULL, tt.section FROM transactions t LEFT JOIN tax_tags tt ON tt.transaction_id = t.id WHERE t.amount < 0 AND t.date >= ?1 AND t.date <= ?2 ORDER BY t.date",
+    )?;
+    let rows = stmt
+        .query_map(
+            params![periods::format_date(start), periods::format_date(end)],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, bool>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                ))
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to fetch transactions for tax report")?;
+
+    let mut sections: Vec<TaxSectionSummary> = TaxSection::ALL
+        .iter()
+        .map(|section| TaxSectionSummary {
+            section: *section,
+            label: section.label().to_string(),
+            claimed: 0.0,
+            limit: section.limit(),
+            eligible: 0.0,
+            items: Vec::new(),
+        })
+        .collect();
+
+    for (id, date, description, amount, category, tagged, tag) in rows {
+        let section = if tagged {
+            tag.as_deref().and_then(TaxSection::parse)
+        } else {
+            match_rules(&rules, &description, category.as_deref())
+        };
+
+        let Some(section) = section else {
+            continue;
+        };
+
+        if let Some(summary) = sections
+            .iter_mut()
+            .find(|summary| summary.section == section)
+        {
+            summary.claimed += amount.abs();
+            summary.items.push(TaxItem {
+                transaction_id: id,
+                date,
+                description,
+                amount: amount.abs(),
+            });
+        }
+    }
+
+    for summary in &mut sections {
+        summary.eligible = match summary.limit {
+            Some(limit) => summary.claimed.min(limit),
+            None => summary.claimed,
+        };
+    }
+
+    let rent_paid = sections
+        .iter()
+        .find(|summary| summary.section == TaxSection::Hra)
+        .map(|summary| summary.claimed)
+        .unwrap_or(0.0);
+    let (hra, missing_inputs) = hra_exemption(conn, rent_paid)?;
+
+    // Rent is only deductible through the HRA exemption, which needs the salary
+    // inputs; without them nothing is eligible.
+    if let Some(summary) = sections
+        .iter_mut()
+        .find(|summary| summary.section == TaxSection::Hra)
+    {
+        summary.eligible = hra.as_ref().map_or(0.0, |hra| hra.exempt);
+    }
+
+    let total_deductions = sections.iter().map(|summary| summary.eligible).sum();
+
+    Ok(TaxReport {
+        financial_year: periods::financial_year_label(start),
+        start: periods::format_date(start),
+        end: periods::format_date(end),
+        sections,
+        hra,
+        total_deductions,
+        missing_inputs,
+    })
+}
+
+/// Flattens a report into CSV rows (one per matched transaction plus section totals).
+pub fn report_csv(report: &TaxReport) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["financial_year", "section", "date", "description", "amount"])?;
+
+    for summary in &report.sections {
+        for item in &summary.items {
+            writer.write_record([
+                report.financial_year.as_str(),
+                summary.section.as_str(),
+                item.date.as_str(),
+                item.description.as_str(),
+                &format!("{:.2}", item.amount),
+            ])?;
+        }
+        writer.write_record([
+            report.financial_year.as_str(),
+            summary.section.as_str(),
+            "",
+            &format!("{} (eligible)", summary.label),
+            &format!("{:.2}", summary.eligible),
+        ])?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|error| anyhow::anyhow!("Failed to flush tax CSV: {error}"))?;
+    String::from_utf8(bytes).context("Tax CSV was not valid UTF-8")
+}
+
+fn effective_rules(mut rules: Vec<TaxRule>) -> Vec<TaxRule> {
+    for (section, keywords) in DEFAULT_KEYWORDS {
+        if rules.iter().any(|rule| rule.section == *section) {
+            continue;
+        }
+        rules.extend(keywords.iter().map(|keyword| TaxRule {
+            id: None,
+            section: *section,
+            kind: TaxRuleKind::Keyword,
+            pattern: keyword.to_string(),
+        }));
+    }
+    rules
+}
+
+fn match_rules(rules: &[TaxRule], description: &str, category: Option<&str>) -> Option<TaxSection> {
+    let words = normalize_words(description);
+
+    rules
+        .iter()
+        .find(|rule| match rule.kind {
+            TaxRuleKind::Category => category
+                .map(|category| category.eq_ignore_ascii_case(rule.pattern.trim()))
+                .unwrap_or(false),
+            TaxRuleKind::Keyword => words.contains(&normalize_words(&rule.pattern)),
+        })
+        .map(|rule| rule.section)
+}
+
+/// Uppercases and pads alphanumeric runs with spaces so keywords only match whole
+/// words ("LIC" must not match "PUBLIC", "RENT" must not match "CURRENT").
+fn normalize_words(input: &str) -> String {
+    let words = input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_uppercase())
+        .collect::<Vec<_>>();
+    format!(" {} ", words.join(" "))
+}
+
+/// HRA exemption under section 10(13A): the least of HRA received, rent paid minus
+/// 10% of basic salary, and 50% (metro) or 40% of basic salary. Needs the
+/// `tax_basic_salary` and `tax_hra_received` settings; `tax_metro_city` is optional.
+/// When rent was paid but those are unset, returns the missing setting keys instead.
+fn hra_exemption(conn: &Connection, rent_paid: f64) -> Result<(Option<HraExemption>, Vec<String>)> {
+    let setting = |key: &str| -> Result<Option<String>> {
+        conn.query_row(
+            "SELECT value FROM settings WHERE key=?1",
+            params![key],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .context("Failed to read tax setting")
+    };
+
+    let basic_salary = setting("tax_basic_salary")?.and_then(|value| value.parse::<f64>().ok());
+    let hra_received = setting("tax_hra_received")?.and_then(|value| value.parse::<f64>().ok());
+    let metro = setting("tax_metro_city")?
+        .map(|value| value == "true")
+        .unwrap_or(false);
+
+    let (Some(basic_salary), Some(hra_received)) = (basic_salary, hra_received) else {
+        if rent_paid <= 0.0 {
+            return Ok((None, Vec::new()));
+        }
+        let missing = [
+            ("tax_basic_salary", basic_salary),
+            ("tax_hra_received", hra_received),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(key, _)| key.to_string())
+        .collect();
+        return Ok((None, missing));
+    };
+
+    let rent_over_basic = (rent_paid - basic_salary * 0.1).max(0.0);
+    let salary_share = basic_salary * if metro { 0.5 } else { 0.4 };
+
+    Ok((
+        Some(HraExemption {
+            rent_paid,
+            hra_received,
+            basic_salary,
+            metro,
+            exempt: hra_received.min(rent_over_basic).min(salary_share),
+        }),
+        Vec::new(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_match_whole_words_only() {
+        let rules = effective_rules(Vec::new());
+
+        assert_eq!(
+            match_rules(&rules, "UPI-LIC OF INDIA-PREMIUM", None),
+            Some(TaxSection::Section80C)
+        );
+        assert_eq!(
+            match_rules(&rules, "NEFT-HOUSE RENT-MAY", None),
+            Some(TaxSection::Hra)
+        );
+        assert_eq!(match_rules(&rules, "PUBLIC CURRENT ACCOUNT", None), None);
+    }
+
+    #[test]
+    fn user_rules_replace_defaults_for_their_section() {
+        let rules = effective_rules(vec![TaxRule {
+            id: Some(1),
+            section: TaxSection::Section80D,
+            kind: TaxRuleKind::Category,
+            pattern: "Insurance".into(),
+        }]);
+
+        assert_eq!(
+            match_rules(&rules, "STAR HEALTH POLICY", Some("Insurance")),
+            Some(TaxSection::Section80D)
+        );
+        assert_eq!(match_rules(&rules, "STAR HEALTH POLICY", None), None);
+    }
+
+    #[test]
+    fn rent_counts_only_through_a_computable_hra_exemption() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO transactions (date, description, amount, flow)
+             VALUES ('2024-05-01', 'NEFT-HOUSE RENT-MAY', -240000, 'debit')",
+            [],
+        )
+        .unwrap();
+
+        let report = build_report(&conn, "FY2024-25").unwrap();
+        assert!(report.hra.is_none());
+        assert_eq!(report.total_deductions, 0.0);
+        assert_eq!(
+            report.missing_inputs,
+            ["tax_basic_salary", "tax_hra_received"]
+        );
+
+        conn.execute_batch(
+            "INSERT INTO settings (key, value) VALUES
+                ('tax_basic_salary', '600000'), ('tax_hra_received', '300000');",
+        )
+        .unwrap();
+        let report = build_report(&conn, "FY2024-25").unwrap();
+        assert!(report.missing_inputs.is_empty());
+        // min(HRA received, rent - 10% of basic, 40% of basic)
+        assert_eq!(report.total_deductions, 180000.0);
+    }
+}
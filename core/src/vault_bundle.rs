@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use rusqlite::{params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+use crate::{
+    dto::{VaultBundleInfo, VaultTableCount},
+    kdf::{derive_key, KdfParams, SALT_LEN},
+    sql::{column_names, from_json, quote, to_json},
+    storage,
+};
+
+/// File signature of a `.wvault` bundle.
+const MAGIC: &[u8; 8] = b"WVAULT\0\x01";
+const FORMAT_VERSION: u32 = 1;
+const KDF: &str = "argon2id";
+const CIPHER: &str = "xchacha20poly1305";
+const NONCE_LEN: usize = 24;
+const MAX_HEADER_LEN: usize = 4096;
+
+/// Cleartext header. Authenticated as associated data, so any edit to it fails
+/// decryption just like an edit to the ciphertext.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleHeader {
+    format_version: u32,
+    kdf: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: String,
+    cipher: String,
+    nonce: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultPayload {
+    schema_version: i64,
+    exported_at: i64,
+    tables: BTreeMap<String, TableDump>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TableDump {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+/// Serialises every table of the vault and encrypts it under `passphrase`.
+pub fn export(conn: &Connection, passphrase: &str) -> Result<Vec<u8>> {
+    seal(&dump(conn)?, passphrase, KdfParams::DEFAULT)
+}
+
+/// Decrypts a bundle and reports what it contains without touching any vault.
+pub fn inspect(bytes: &[u8], passphrase: &str) -> Result<VaultBundleInfo> {
+    let payload = open(bytes, passphrase)?;
+    Ok(info(&payload, payload.tables.keys().cloned().collect()))
+}
+
+/// Replaces the contents of `conn` with the bundle's rows in one transaction. Columns
+/// and tables the current schema no longer has are dropped; new columns fall back to
+/// their defaults.
+pub fn restore(conn: &Connection, bytes: &[u8], passphrase: &str) -> Result<VaultBundleInfo> {
+    let payload = open(bytes, passphrase)?;
+    if payload.schema_version > storage::SCHEMA_VERSION {
+        bail!(
+            "Vault bundle schema version {} is newer than this app supports ({})",
+            payload.schema_version,
+            storage::SCHEMA_VERSION
+        );
+    }
+
+    let existing = table_names(conn)?;
+    let tx = conn
+        .unchecked_transaction()
+        .context("Failed to open restore transaction")?;
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON;")?;
+
+    let mut restored = Vec::new();
+    for (table, dump) in &payload.tables {
+        if !existing.contains(table) {
+            log::warn!("Skipping table {table} from vault bundle: not in current schema");
+            continue;
+        }
+
+        let target_columns = column_names(&tx, table)?;
+        let kept = dump
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| target_columns.contains(*column))
+            .collect::<Vec<_>>();
+
+        tx.execute(&format!("DELETE FROM {}", quote(table)), [])
+            .with_context(|| format!("Failed to clear {table} before restore"))?;
+        if kept.is_empty() {
+            continue;
+        }
+
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(table),
+            kept.iter()
+                .map(|(_, column)| quote(column))
+                .collect::<Vec<_>>()
+                .join(", "),
+            vec!["?"; kept.len()].join(", ")
+        );
+        let mut stmt = tx.prepare(&sql)?;
+        for row in &dump.rows {
+            let values = kept
+                .iter()
+                .map(|(index, _)| {
+                    row.get(*index)
+                        .ok_or_else(|| anyhow!("Vault bundle row in {table} is truncated"))
+                        .and_then(from_json)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            stmt.execute(params_from_iter(values))
+                .with_context(|| format!("Failed to restore row into {table}"))?;
+        }
+        restored.push(table.clone());
+    }
+
+    tx.commit().context("Failed to commit vault restore")?;
+    Ok(info(&payload, restored))
+}
+
+fn info(payload: &VaultPayload, tables: Vec<String>) -> VaultBundleInfo {
+    VaultBundleInfo {
+        format_version: FORMAT_VERSION,
+        schema_version: payload.schema_version,
+        exported_at: payload.exported_at,
+        tables: tables
+            .into_iter()
+            .map(|name| {
+                let rows = payload.tables.get(&name).map_or(0, |dump| dump.rows.len());
+                VaultTableCount { name, rows }
+            })
+            .collect(),
+    }
+}
+
+fn dump(conn: &Connection) -> Result<VaultPayload> {
+    let mut tables = BTreeMap::new();
+    for table in table_names(conn)? {
+        let columns = column_names(conn, &table)?;
+        let sql = format!(
+            "SELECT {} FROM {}",
+            columns
+                .iter()
+                .map(|column| quote(column))
+                .collect::<Vec<_>>()
+                .join(", "),
+            quote(&table)
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let mut rows = stmt.query([])?;
+        let mut dumped = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut values = Vec::with_capacity(columns.len());
+            for index in 0..columns.len() {
+                values.push(to_json(row.get_ref(index)?));
+            }
+            dumped.push(values);
+        }
+        tables.insert(
+            table,
+            TableDump {
+                columns,
+                rows: dumped,
+            },
+        );
+    }
+
+    Ok(VaultPayload {
+        schema_version: conn.pragma_query_value(None, "user_version", |row| row.get(0))?,
+        exported_at: OffsetDateTime::now_utc().unix_timestamp(),
+        tables,
+    })
+}
+
+fn table_names(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+         ORDER BY name",
+    )?;
+    let names = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(names)
+}
+
+fn seal(payload: &VaultPayload, passphrase: &str, params: KdfParams) -> Result<Vec<u8>> {
+    if passphrase.is_empty() {
+        bail!("A passphrase is required to export the vault");
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let header = serde_json::to_vec(&BundleHeader {
+        format_version: FORMAT_VERSION,
+        kdf: KDF.into(),
+        memory_kib: params.memory_kib,
+        iterations: params.iterations,
+        parallelism: params.parallelism,
+        salt: hex::encode(salt),
+        cipher: CIPHER.into(),
+        nonce: hex::encode(nonce),
+    })?;
+    let plaintext = serde_json::to_vec(payload).context("Failed to serialise vault")?;
+
+    let key = derive_key(passphrase.as_bytes(), &salt, params)?;
+    let mut aad = MAGIC.to_vec();
+    aad.extend_from_slice(&header);
+    let ciphertext = XChaCha20Poly1305::new(key.as_bytes().into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| anyhow!("Vault encryption failed"))?;
+
+    let mut bundle = Vec::with_capacity(MAGIC.len() + 4 + header.len() + ciphertext.len());
+    bundle.extend_from_slice(MAGIC);
+    bundle.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    bundle.extend_from_slice(&header);
+    bundle.extend_from_slice(&ciphertext);
+    Ok(bundle)
+}
+
+fn open(bytes: &[u8], passphrase: &str) -> Result<VaultPayload> {
+    let body = bytes
+        .strip_prefix(MAGIC.as_slice())
+        .ok_or_else(|| anyhow!("Not a .wvault bundle"))?;
+    let (length, body) = body
+        .split_first_chunk::<4>()
+        .ok_or_else(|| anyhow!("Vault bundle is truncated"))?;
+    let header_len = u32::from_le_bytes(*length) as usize;
+    if header_len > MAX_HEADER_LEN || header_len > body.len() {
+        bail!("Vault bundle is truncated");
+    }
+    let (header_bytes, ciphertext) = body.split_at(header_len);
+
+    let header: BundleHeader =
+        serde_json::from_slice(header_bytes).context("Vault bundle header is corrupt")?;
+    if header.format_version != FORMAT_VERSION || header.kdf != KDF || header.cipher != CIPHER {
+        bail!(
+            "Unsupported vault bundle (format {}, {}, {})",
+            header.format_version,
+            header.kdf,
+            header.cipher
+        );
+    }
+    let params = KdfParams {
+        memory_kib: header.memory_kib,
+        iterations: header.iterations,
+        parallelism: header.parallelism,
+    }
+    .validate()
+    .context("Vault bundle has unsupported key derivation parameters")?;
+    let salt = hex::decode(&header.salt).context("Vault bundle header is corrupt")?;
+    let nonce = hex::decode(&header.nonce).context("Vault bundle header is corrupt")?;
+    if salt.len() != SALT_LEN || nonce.len() != NONCE_LEN {
+        bail!("Vault bundle header is corrupt");
+    }
+
+    let key = derive_key(passphrase.as_bytes(), &salt, params)?;
+    let mut aad = MAGIC.to_vec();
+    aad.extend_from_slice(header_bytes);
+    let plaintext = XChaCha20Poly1305::new(key.as_bytes().into())
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| {
+            anyhow!("Vault bundle failed integrity check (wrong passphrase or modified file)")
+        })?;
+
+    serde_json::from_slice(&plaintext).context("Vault bundle contents are corrupt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn sample_vault() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('currency', 'INR')",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn settings(conn: &Connection) -> Vec<(String, String)> {
+        let mut stmt = conn
+            .prepare("SELECT key, value FROM settings ORDER BY key")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips_and_rejects_tampering() {
+        let source = sample_vault();
+        let bundle = seal(&dump(&source).unwrap(), "correct horse", FAST).unwrap();
+
+        let target = Connection::open_in_memory().unwrap();
+        storage::migrate(&target).unwrap();
+        let info = restore(&target, &bundle, "correct horse").unwrap();
+        assert_eq!(info.schema_version, storage::SCHEMA_VERSION);
+        assert!(info
+            .tables
+            .iter()
+            .any(|table| table.name == "settings" && table.rows == 1));
+        assert_eq!(settings(&target), settings(&source));
+
+        assert!(open(&bundle, "wrong horse").is_err());
+
+        let mut body = bundle.clone();
+        *body.last_mut().unwrap() ^= 1;
+        assert!(open(&body, "correct horse").is_err());
+
+        // Lowering the KDF cost in the header must not go unnoticed.
+        let mut header = bundle.clone();
+        let at = header
+            .windows(b"\"iterations\":1".len())
+            .position(|window| window == b"\"iterations\":1")
+            .unwrap();
+        header[at + b"\"iterations\":".len()] = b'2';
+        assert!(open(&header, "correct horse").is_err());
+
+        assert!(open(&bundle[..bundle.len() / 2], "correct horse").is_err());
+        assert!(open(b"not a bundle", "correct horse").is_err());
+    }
+}
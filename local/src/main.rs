@@ -1,53 +1,122 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod keychain;
+mod session;
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 use artha_core::{
-    dto::{AppSettings, BudgetConfig, InboxItem, NetWorthPoint, SetCategoryResponse, SummaryResponse},
+    dto::{
+        AmortizationRow, AppSettings, AssetClass, AuditEntry, AuditQuery, AuditVerification,
+        BackupPolicy, BackupSnapshot, Broker, BrokerFunding, BrokerImportSummary, BudgetAlert,
+        BudgetConfig, BudgetProposal, CapitalGainsReport, CardMatchSummary, CardOutstanding,
+        CardStatement, CasImportSummary, CreditCard, ExportFilter, ExportFormat, ExportKind, Goal,
+        GoalContribution, GoalProgress, Holding, HoldingLot, HoldingValuation, InboxItem,
+        JournalEntry, LearnedToken, Loan, LoanPrepayment, LoanSummary, NetWorthPoint,
+        NewCreditCard, NewGoal, NewHolding, NewLoan, PerformanceReport, PricePoint, ReportGrouping,
+        ReportResponse, SetCategoryResponse, SummaryResponse, TaxReport, TaxRule, TaxSection,
+        TimeWeightedReturn, TokenProvenance, VaultBundleInfo,
+    },
+    key_provider::{self, KeyProvider, KeyProviderConfig},
     ArthaCore,
 };
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{api::notification::Notification, AppHandle, Manager, State};
+
+const BACKUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const BACKUP_POLL: Duration = Duration::from_secs(60 * 60);
+const LOCK_POLL: Duration = Duration::from_secs(15);
 
 struct AppState {
     core: RwLock<Option<Arc<ArthaCore>>>,
+    last_activity: Mutex<Instant>,
 }
 
 impl AppState {
     fn new() -> Self {
         Self {
             core: RwLock::new(None),
+            last_activity: Mutex::new(Instant::now()),
         }
     }
 
     fn set_core(&self, core: ArthaCore) -> Arc<ArthaCore> {
         let arc = Arc::new(core);
         *self.core.write() = Some(arc.clone());
+        *self.last_activity.lock() = Instant::now();
         arc
     }
 
-    fn require_core(&self) -> Result<Arc<ArthaCore>> {
-        self.core
-            .read()
-            .clone()
-            .context("Vault locked. Unlock before accessing data.")
+    /// The unlocked core. Every command goes through here, so it doubles as the
+    /// activity signal for the idle auto-lock.
+    fn require_core(&self) -> Result<Arc<ArthaCore>, CommandError> {
+        let core = self.core.read().clone().ok_or(CommandError::Locked)?;
+        *self.last_activity.lock() = Instant::now();
+        Ok(core)
+    }
+
+    /// Drops the core; its key is wiped once in-flight commands release it. Returns
+    /// whether the vault was unlocked.
+    fn lock(&self) -> bool {
+        self.core.write().take().is_some()
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_activity.lock().elapsed()
+    }
+}
+
+/// Error returned by commands, serialised as `{ kind, message }` so the UI can tell
+/// a locked vault (back to the unlock screen) from an ordinary failure.
+#[derive(Debug, thiserror::Error)]
+enum CommandError {
+    #[error("Vault locked. Unlock before accessing data.")]
+    Locked,
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Failed(message)
+    }
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let kind = match self {
+            CommandError::Locked => "locked",
+            CommandError::Failed(_) => "failed",
+        };
+        let mut error = serializer.serialize_struct("CommandError", 2)?;
+        error.serialize_field("kind", kind)?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
     }
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 struct UnlockVaultResponse {
     ok: bool,
     message: String,
+    /// Set only when a new vault was created; shown to the user once.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recovery_phrase: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct ImportCsvPayload {
     bytes: Vec<u8>,
     name: Option<String>,
+    account: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -68,6 +137,143 @@ struct SetBudgetPayload {
     cap: f64,
 }
 
+#[derive(Deserialize)]
+struct ReportPayload {
+    start: String,
+    end: String,
+    grouping: ReportGrouping,
+}
+
+#[derive(Deserialize)]
+struct TimeWeightedReturnPayload {
+    start: String,
+    end: String,
+    asset_class: Option<AssetClass>,
+}
+
+#[derive(Deserialize)]
+struct TagTaxTransactionPayload {
+    transaction_id: i64,
+    section: Option<TaxSection>,
+}
+
+#[derive(Deserialize)]
+struct ImportCasPayload {
+    bytes: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct ImportTradebookPayload {
+    bytes: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct ImportContractNotePayload {
+    broker: Broker,
+    bytes: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct UpdateLoanPayload {
+    id: i64,
+    loan: NewLoan,
+}
+
+#[derive(Deserialize)]
+struct UpdateCreditCardPayload {
+    id: i64,
+    card: NewCreditCard,
+}
+
+#[derive(Deserialize)]
+struct AssignCardTransactionsPayload {
+    card_id: i64,
+    transaction_ids: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+struct LinkCardPaymentPayload {
+    card_id: i64,
+    transaction_id: i64,
+}
+
+#[derive(Deserialize)]
+struct UpdateGoalPayload {
+    id: i64,
+    goal: NewGoal,
+}
+
+#[derive(Deserialize)]
+struct LinkGoalHoldingPayload {
+    goal_id: i64,
+    holding_id: i64,
+    share: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct GoalAccountPayload {
+    goal_id: i64,
+    account: String,
+}
+
+#[derive(Deserialize)]
+struct TagGoalTransactionPayload {
+    goal_id: i64,
+    transaction_id: i64,
+}
+
+#[derive(Deserialize)]
+struct ExportVaultPayload {
+    path: PathBuf,
+    passphrase: String,
+}
+
+#[derive(Deserialize)]
+struct VaultBundlePayload {
+    bundle_path: PathBuf,
+    passphrase: String,
+    /// Vault passphrase, when the passphrase key provider is configured.
+    key_passphrase: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RestoreVaultBackupPayload {
+    name: String,
+    /// Vault passphrase, when the passphrase key provider is configured.
+    key_passphrase: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExportDataPayload {
+    kind: ExportKind,
+    format: ExportFormat,
+    filter: ExportFilter,
+}
+
+#[derive(Deserialize)]
+struct SetHoldingPricePayload {
+    holding_id: i64,
+    date: String,
+    price: f64,
+}
+
+#[derive(Deserialize)]
+struct ImportHoldingPricesPayload {
+    holding_id: i64,
+    bytes: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct SeedBudgetsPayload {
+    budgets: Vec<BudgetConfig>,
+}
+
+#[derive(Deserialize)]
+struct SetBudgetThresholdsPayload {
+    category: String,
+    thresholds: Vec<f64>,
+}
+
 fn vault_path() -> Result<PathBuf> {
     let mut base = dirs::data_dir().context("Could not resolve application support directory")?;
     base.push("ArthaOS");
@@ -76,153 +282,1339 @@ fn vault_path() -> Result<PathBuf> {
     Ok(base)
 }
 
-#[tauri::command]
-async fn unlock_vault(state: State<'_, AppState>) -> Result<UnlockVaultResponse, String> {
-    let key = keychain::get_or_create_vault_key()
-        .map_err(|error| format!("Keychain failure: {error}"))?;
+/// Polls hourly and snapshots the vault once a day for as long as it stays unlocked.
+fn schedule_backups(core: Weak<ArthaCore>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let Some(core) = core.upgrade() else {
+                break;
+            };
+            if let Err(error) = core
+                .backup_if_due(BackupPolicy::default(), BACKUP_INTERVAL)
+                .await
+            {
+                log::error!("Scheduled backup failed: {error}");
+            }
+            drop(core);
+            tokio::time::sleep(BACKUP_POLL).await;
+        }
+    });
+}
+
+/// Locks the vault after `auto_lock_minutes` without a command, when the screen
+/// locks, and when the machine wakes from sleep. The UI gets a `vault-locked`
+/// event carrying the reason.
+fn watch_auto_lock(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut sleep = session::SleepDetector::new();
+        loop {
+            tokio::time::sleep(LOCK_POLL).await;
+            let woke_up = sleep.woke_up();
+
+            let state = app.state::<AppState>();
+            let Some(core) = state.core.read().clone() else {
+                continue;
+            };
+            let reason = if woke_up {
+                Some("sleep")
+            } else if session::screen_locked() {
+                Some("screenLock")
+            } else {
+                match core.get_app_settings().await {
+                    Ok(settings) => (settings.auto_lock_minutes > 0
+                        && state.idle_for()
+                            >= Duration::from_secs(u64::from(settings.auto_lock_minutes) * 60))
+                    .then_some("idle"),
+                    Err(error) => {
+                        log::warn!("Auto-lock settings unavailable: {error}");
+                        None
+                    }
+                }
+            };
+            drop(core);
+
+            if let Some(reason) = reason {
+                if state.lock() {
+                    if let Err(error) = app.emit_all("vault-locked", reason) {
+                        log::error!("Failed to announce vault lock: {error}");
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Key provider chosen by `ARTHA_KEY_PROVIDER` (see `KeyProviderConfig::parse`),
+/// falling back to the platform keystore.
+fn vault_key_provider(
+    vault: &Path,
+    passphrase: Option<&str>,
+) -> Result<Box<dyn KeyProvider>, String> {
+    let config = match std::env::var("ARTHA_KEY_PROVIDER") {
+        Ok(raw) => KeyProviderConfig::parse(&raw)
+            .map_err(|error| format!("Key provider misconfigured: {error}"))?,
+        Err(_) => KeyProviderConfig::platform_default(),
+    };
+    config
+        .build(vault, passphrase)
+        .map_err(|error| format!("Key provider unavailable: {error}"))
+}
 
+#[tauri::command]
+async fn unlock_vault(
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<UnlockVaultResponse, CommandError> {
     let db_path = vault_path().map_err(|error| format!("Storage failure: {error}"))?;
+    let provider = vault_key_provider(&db_path, passphrase.as_deref())?;
+    let created = !db_path.exists();
 
-    let core = ArthaCore::bootstrap(db_path, key)
+    let core = ArthaCore::unlock(db_path, provider.as_ref())
         .map_err(|error| format!("Vault bootstrap failed: {error}"))?;
+    let recovery_phrase = if created {
+        Some(
+            core.create_recovery_phrase()
+                .await
+                .map_err(|error| format!("Recovery phrase setup failed: {error}"))?,
+        )
+    } else {
+        None
+    };
 
-    state.set_core(core);
+    let core = state.set_core(core);
+    schedule_backups(Arc::downgrade(&core));
 
     Ok(UnlockVaultResponse {
         ok: true,
         message: "Vault unlocked".into(),
+        recovery_phrase,
     })
 }
 
 #[tauri::command]
-async fn import_csv(payload: ImportCsvPayload, state: State<'_, AppState>) -> Result<Vec<InboxItem>, String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+async fn import_csv(
+    payload: ImportCsvPayload,
+    state: State<'_, AppState>,
+) -> Result<Vec<InboxItem>, CommandError> {
+    let core = state.require_core()?;
 
-    core.import_csv(&payload.bytes)
+    core.import_csv(&payload.bytes, payload.account.as_deref())
         .await
-        .map_err(|error| format!("Import failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Import failed: {error}")))
 }
 
 #[tauri::command]
-async fn get_inbox(state: State<'_, AppState>) -> Result<Vec<InboxItem>, String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+async fn get_inbox(state: State<'_, AppState>) -> Result<Vec<InboxItem>, CommandError> {
+    let core = state.require_core()?;
 
     core.get_inbox()
         .await
-        .map_err(|error| format!("Inbox fetch failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Inbox fetch failed: {error}")))
 }
 
 #[tauri::command]
 async fn set_inbox_category(
     payload: SetInboxCategoryPayload,
     state: State<'_, AppState>,
-) -> Result<SetCategoryResponse, String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+) -> Result<SetCategoryResponse, CommandError> {
+    let core = state.require_core()?;
 
     core.set_inbox_category(&payload.temp_id, &payload.category)
         .await
-        .map_err(|error| format!("Update failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Update failed: {error}")))
 }
 
 #[tauri::command]
-async fn commit_inbox(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+async fn commit_inbox(state: State<'_, AppState>) -> Result<serde_json::Value, CommandError> {
+    let core = state.require_core()?;
 
     core.commit_inbox()
         .await
         .map(|count| serde_json::json!({ "committedCount": count }))
-        .map_err(|error| format!("Commit failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Commit failed: {error}")))
+}
+
+#[tauri::command]
+async fn recategorize_transactions(
+    ids: Vec<i64>,
+    category: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<usize, CommandError> {
+    let core = state.require_core()?;
+
+    core.recategorize_transactions(ids, category.as_deref())
+        .await
+        .map_err(|error| CommandError::Failed(format!("Recategorize failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_transactions(
+    ids: Vec<i64>,
+    state: State<'_, AppState>,
+) -> Result<usize, CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_transactions(ids)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Transaction delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_learned_tokens(state: State<'_, AppState>) -> Result<Vec<LearnedToken>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_learned_tokens()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Learned token fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_token_provenance(
+    token: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<TokenProvenance>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_token_provenance(&token)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Provenance fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn update_learned_token(
+    token: String,
+    category: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.update_learned_token(&token, &category)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Learned token update failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_learned_token(
+    token: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_learned_token(&token)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Learned token delete failed: {error}")))
 }
 
 #[tauri::command]
-async fn get_summary(month: String, state: State<'_, AppState>) -> Result<SummaryResponse, String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+async fn get_summary(
+    month: String,
+    state: State<'_, AppState>,
+) -> Result<SummaryResponse, CommandError> {
+    let core = state.require_core()?;
 
     core.get_summary(&month)
         .await
-        .map_err(|error| format!("Summary failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Summary failed: {error}")))
 }
 
 #[tauri::command]
-async fn get_networth_curve(state: State<'_, AppState>) -> Result<Vec<NetWorthPoint>, String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+async fn get_report(
+    payload: ReportPayload,
+    state: State<'_, AppState>,
+) -> Result<ReportResponse, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_report(&payload.start, &payload.end, payload.grouping)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Report failed: {error}")))
+}
 
+#[tauri::command]
+async fn get_networth_curve(
+    state: State<'_, AppState>,
+) -> Result<Vec<NetWorthPoint>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_networth_curve()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Net worth curve failed: {error}")))
 }
 
 #[tauri::command]
-async fn get_app_settings(state: State<'_, AppState>) -> Result<AppSettings, String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+async fn get_app_settings(state: State<'_, AppState>) -> Result<AppSettings, CommandError> {
+    let core = state.require_core()?;
 
     core.get_app_settings()
         .await
-        .map_err(|error| format!("Settings fetch failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Settings fetch failed: {error}")))
 }
 
 #[tauri::command]
-async fn update_setting(payload: SetSettingPayload, state: State<'_, AppState>) -> Result<(), String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+async fn update_setting(
+    payload: SetSettingPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
 
     core.update_setting(&payload.key, &payload.value)
         .await
-        .map_err(|error| format!("Settings update failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Settings update failed: {error}")))
 }
 
 #[tauri::command]
-async fn get_budget_configs(state: State<'_, AppState>) -> Result<Vec<BudgetConfig>, String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+async fn get_budget_configs(state: State<'_, AppState>) -> Result<Vec<BudgetConfig>, CommandError> {
+    let core = state.require_core()?;
 
     core.get_budget_configs()
         .await
-        .map_err(|error| format!("Budgets fetch failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Budgets fetch failed: {error}")))
 }
 
 #[tauri::command]
 async fn set_budget_config(
     payload: SetBudgetPayload,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let core = state
-        .require_core()
-        .map_err(|error| format!("Locked: {error}"))?;
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
 
     core.set_budget_config(&payload.category, payload.cap)
         .await
-        .map_err(|error| format!("Budget update failed: {error}"))
+        .map_err(|error| CommandError::Failed(format!("Budget update failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_budget_config(
+    category: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_budget_config(&category)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Budget delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn propose_budgets(
+    months: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<BudgetProposal>, CommandError> {
+    let core = state.require_core()?;
+
+    core.propose_budgets(months.unwrap_or(3))
+        .await
+        .map_err(|error| CommandError::Failed(format!("Budget proposal failed: {error}")))
+}
+
+#[tauri::command]
+async fn seed_budgets(
+    payload: SeedBudgetsPayload,
+    state: State<'_, AppState>,
+) -> Result<usize, CommandError> {
+    let core = state.require_core()?;
+
+    core.seed_budgets(payload.budgets)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Budget seed failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_budget_thresholds(
+    category: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<f64>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_budget_thresholds(&category)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Thresholds fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn set_budget_thresholds(
+    payload: SetBudgetThresholdsPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.set_budget_thresholds(&payload.category, payload.thresholds)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Thresholds update failed: {error}")))
+}
+
+#[tauri::command]
+async fn evaluate_budget_alerts(
+    month: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<BudgetAlert>, CommandError> {
+    let core = state.require_core()?;
+
+    let alerts = core
+        .evaluate_alerts(&month)
+        .await
+        .map_err(|error| format!("Alert evaluation failed: {error}"))?;
+
+    let identifier = app.config().tauri.bundle.identifier.clone();
+    for alert in &alerts {
+        let notification = Notification::new(&identifier)
+            .title(format!(
+                "{} budget at {:.0}%",
+                alert.category, alert.percent_used
+            ))
+            .body(format!(
+                "Spent {:.2} of {:.2} in {}",
+                alert.spent, alert.cap, alert.month
+            ));
+        if let Err(error) = notification.show() {
+            log::warn!("Failed to show budget notification: {error}");
+        }
+    }
+
+    Ok(alerts)
+}
+
+#[tauri::command]
+async fn get_tax_report(
+    financial_year: String,
+    state: State<'_, AppState>,
+) -> Result<TaxReport, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_tax_report(&financial_year)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Tax report failed: {error}")))
+}
+
+#[tauri::command]
+async fn export_tax_report_csv(
+    financial_year: String,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    let core = state.require_core()?;
+
+    core.export_tax_report_csv(&financial_year)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Tax export failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_capital_gains(
+    financial_year: String,
+    state: State<'_, AppState>,
+) -> Result<CapitalGainsReport, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_capital_gains(&financial_year)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Capital gains failed: {error}")))
+}
+
+#[tauri::command]
+async fn export_capital_gains_csv(
+    financial_year: String,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    let core = state.require_core()?;
+
+    core.export_capital_gains_csv(&financial_year)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Capital gains export failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_tax_rules(state: State<'_, AppState>) -> Result<Vec<TaxRule>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_tax_rules()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Tax rules fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn add_tax_rule(rule: TaxRule, state: State<'_, AppState>) -> Result<i64, CommandError> {
+    let core = state.require_core()?;
+
+    core.add_tax_rule(rule)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Tax rule insert failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_tax_rule(id: i64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_tax_rule(id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Tax rule delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn tag_tax_transaction(
+    payload: TagTaxTransactionPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.tag_tax_transaction(payload.transaction_id, payload.section)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Tax tag failed: {error}")))
+}
+
+#[tauri::command]
+async fn clear_tax_tag(
+    transaction_id: i64,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.clear_tax_tag(transaction_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Tax tag clear failed: {error}")))
+}
+
+#[tauri::command]
+async fn upsert_holding(
+    holding: NewHolding,
+    state: State<'_, AppState>,
+) -> Result<Holding, CommandError> {
+    let core = state.require_core()?;
+
+    core.upsert_holding(holding)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Holding update failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_holding(id: i64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_holding(id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Holding delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_holdings(
+    as_of: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<HoldingValuation>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_holdings(as_of.as_deref())
+        .await
+        .map_err(|error| CommandError::Failed(format!("Holdings fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_performance(
+    as_of: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<PerformanceReport, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_performance(as_of.as_deref())
+        .await
+        .map_err(|error| CommandError::Failed(format!("Performance failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_time_weighted_return(
+    payload: TimeWeightedReturnPayload,
+    state: State<'_, AppState>,
+) -> Result<TimeWeightedReturn, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_time_weighted_return(&payload.start, &payload.end, payload.asset_class)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Time-weighted return failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_holding_lots(
+    holding_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<HoldingLot>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_holding_lots(holding_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Lots fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn add_holding_lot(
+    lot: HoldingLot,
+    state: State<'_, AppState>,
+) -> Result<Option<i64>, CommandError> {
+    let core = state.require_core()?;
+
+    core.add_holding_lot(lot)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Lot insert failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_holding_lot(id: i64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_holding_lot(id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Lot delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_holding_prices(
+    holding_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<PricePoint>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_holding_prices(holding_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Price fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn set_holding_price(
+    payload: SetHoldingPricePayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.set_holding_price(payload.holding_id, &payload.date, payload.price)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Price update failed: {error}")))
+}
+
+#[tauri::command]
+async fn import_holding_prices(
+    payload: ImportHoldingPricesPayload,
+    state: State<'_, AppState>,
+) -> Result<usize, CommandError> {
+    let core = state.require_core()?;
+
+    core.import_holding_prices(payload.holding_id, &payload.bytes)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Price import failed: {error}")))
+}
+
+#[tauri::command]
+async fn import_cas(
+    payload: ImportCasPayload,
+    state: State<'_, AppState>,
+) -> Result<CasImportSummary, CommandError> {
+    let core = state.require_core()?;
+
+    let text = String::from_utf8_lossy(&payload.bytes);
+    core.import_cas(&text)
+        .await
+        .map_err(|error| CommandError::Failed(format!("CAS import failed: {error}")))
+}
+
+#[tauri::command]
+async fn import_tradebook(
+    payload: ImportTradebookPayload,
+    state: State<'_, AppState>,
+) -> Result<BrokerImportSummary, CommandError> {
+    let core = state.require_core()?;
+
+    core.import_tradebook(&payload.bytes)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Tradebook import failed: {error}")))
+}
+
+#[tauri::command]
+async fn import_contract_note(
+    payload: ImportContractNotePayload,
+    state: State<'_, AppState>,
+) -> Result<usize, CommandError> {
+    let core = state.require_core()?;
+
+    core.import_contract_note(payload.broker, &payload.bytes)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Contract note import failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_broker_fundings(
+    state: State<'_, AppState>,
+) -> Result<Vec<BrokerFunding>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_broker_fundings()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Failed to fetch broker fundings: {error}")))
+}
+
+#[tauri::command]
+async fn add_loan(loan: NewLoan, state: State<'_, AppState>) -> Result<Loan, CommandError> {
+    let core = state.require_core()?;
+
+    core.add_loan(loan)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Loan add failed: {error}")))
+}
+
+#[tauri::command]
+async fn update_loan(
+    payload: UpdateLoanPayload,
+    state: State<'_, AppState>,
+) -> Result<Loan, CommandError> {
+    let core = state.require_core()?;
+
+    core.update_loan(payload.id, payload.loan)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Loan update failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_loan(id: i64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_loan(id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Loan delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_loans(state: State<'_, AppState>) -> Result<Vec<LoanSummary>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_loans()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Loans fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_loan_schedule(
+    loan_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<AmortizationRow>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_loan_schedule(loan_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Loan schedule failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_loan_prepayments(
+    loan_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<LoanPrepayment>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_loan_prepayments(loan_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Prepayments fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn add_loan_prepayment(
+    prepayment: LoanPrepayment,
+    state: State<'_, AppState>,
+) -> Result<i64, CommandError> {
+    let core = state.require_core()?;
+
+    core.add_loan_prepayment(prepayment)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Prepayment add failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_loan_prepayment(id: i64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_loan_prepayment(id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Prepayment delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn match_loan_payments(state: State<'_, AppState>) -> Result<usize, CommandError> {
+    let core = state.require_core()?;
+
+    core.match_loan_payments()
+        .await
+        .map_err(|error| CommandError::Failed(format!("EMI matching failed: {error}")))
+}
+
+#[tauri::command]
+async fn add_credit_card(
+    card: NewCreditCard,
+    state: State<'_, AppState>,
+) -> Result<CreditCard, CommandError> {
+    let core = state.require_core()?;
+
+    core.add_credit_card(card)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Card add failed: {error}")))
+}
+
+#[tauri::command]
+async fn update_credit_card(
+    payload: UpdateCreditCardPayload,
+    state: State<'_, AppState>,
+) -> Result<CreditCard, CommandError> {
+    let core = state.require_core()?;
+
+    core.update_credit_card(payload.id, payload.card)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Card update failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_credit_card(id: i64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_credit_card(id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Card delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_credit_cards(
+    state: State<'_, AppState>,
+) -> Result<Vec<CardOutstanding>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_credit_cards()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Cards fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_card_statements(
+    card_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<CardStatement>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_card_statements(card_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Card statements failed: {error}")))
+}
+
+#[tauri::command]
+async fn assign_card_transactions(
+    payload: AssignCardTransactionsPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.assign_card_transactions(payload.card_id, payload.transaction_ids)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Card assignment failed: {error}")))
+}
+
+#[tauri::command]
+async fn link_card_payment(
+    payload: LinkCardPaymentPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.link_card_payment(payload.card_id, payload.transaction_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Card payment link failed: {error}")))
+}
+
+#[tauri::command]
+async fn unlink_card_transaction(
+    transaction_id: i64,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.unlink_card_transaction(transaction_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Card unlink failed: {error}")))
+}
+
+#[tauri::command]
+async fn match_card_transactions(
+    state: State<'_, AppState>,
+) -> Result<CardMatchSummary, CommandError> {
+    let core = state.require_core()?;
+
+    core.match_card_transactions()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Card matching failed: {error}")))
+}
+
+#[tauri::command]
+async fn add_goal(goal: NewGoal, state: State<'_, AppState>) -> Result<Goal, CommandError> {
+    let core = state.require_core()?;
+
+    core.add_goal(goal)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Goal add failed: {error}")))
+}
+
+#[tauri::command]
+async fn update_goal(
+    payload: UpdateGoalPayload,
+    state: State<'_, AppState>,
+) -> Result<Goal, CommandError> {
+    let core = state.require_core()?;
+
+    core.update_goal(payload.id, payload.goal)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Goal update failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_goal(id: i64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_goal(id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Goal delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_goals(state: State<'_, AppState>) -> Result<Vec<GoalProgress>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_goals()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Goals fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn link_goal_holding(
+    payload: LinkGoalHoldingPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.link_goal_holding(
+        payload.goal_id,
+        payload.holding_id,
+        payload.share.unwrap_or(1.0),
+    )
+    .await
+    .map_err(|error| CommandError::Failed(format!("Goal link failed: {error}")))
+}
+
+#[tauri::command]
+async fn unlink_goal_holding(
+    goal_id: i64,
+    holding_id: i64,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.unlink_goal_holding(goal_id, holding_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Goal unlink failed: {error}")))
+}
+
+#[tauri::command]
+async fn link_goal_account(
+    payload: GoalAccountPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.link_goal_account(payload.goal_id, &payload.account)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Goal account link failed: {error}")))
+}
+
+#[tauri::command]
+async fn unlink_goal_account(
+    payload: GoalAccountPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.unlink_goal_account(payload.goal_id, &payload.account)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Goal account unlink failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_goal_contributions(
+    goal_id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<GoalContribution>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_goal_contributions(goal_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Contributions fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn add_goal_contribution(
+    contribution: GoalContribution,
+    state: State<'_, AppState>,
+) -> Result<i64, CommandError> {
+    let core = state.require_core()?;
+
+    core.add_goal_contribution(contribution)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Contribution add failed: {error}")))
+}
+
+#[tauri::command]
+async fn tag_goal_transaction(
+    payload: TagGoalTransactionPayload,
+    state: State<'_, AppState>,
+) -> Result<i64, CommandError> {
+    let core = state.require_core()?;
+
+    core.tag_goal_transaction(payload.goal_id, payload.transaction_id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Goal tagging failed: {error}")))
+}
+
+#[tauri::command]
+async fn delete_goal_contribution(id: i64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    core.delete_goal_contribution(id)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Contribution delete failed: {error}")))
+}
+
+#[tauri::command]
+async fn export_vault(
+    payload: ExportVaultPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let core = state.require_core()?;
+
+    let bundle = core
+        .export_vault(&payload.passphrase)
+        .await
+        .map_err(|error| format!("Vault export failed: {error}"))?;
+    std::fs::write(&payload.path, bundle)
+        .map_err(|error| CommandError::Failed(format!("Vault export failed: {error}")))
+}
+
+#[tauri::command]
+async fn inspect_vault_bundle(
+    payload: VaultBundlePayload,
+) -> Result<VaultBundleInfo, CommandError> {
+    let bundle = std::fs::read(&payload.bundle_path)
+        .map_err(|error| format!("Bundle read failed: {error}"))?;
+
+    ArthaCore::inspect_vault_bundle(&bundle, &payload.passphrase)
+        .map_err(|error| CommandError::Failed(format!("Bundle verification failed: {error}")))
+}
+
+#[tauri::command]
+async fn restore_vault(
+    payload: VaultBundlePayload,
+    state: State<'_, AppState>,
+) -> Result<VaultBundleInfo, CommandError> {
+    let bundle = std::fs::read(&payload.bundle_path)
+        .map_err(|error| format!("Bundle read failed: {error}"))?;
+    let db_path = vault_path().map_err(|error| format!("Storage failure: {error}"))?;
+    let provider = vault_key_provider(&db_path, payload.key_passphrase.as_deref())?;
+    let key = key_provider::get_or_create_vault_key(provider.as_ref(), db_path.exists())
+        .map_err(|error| format!("Key provider failure: {error}"))?;
+
+    let (core, info) = ArthaCore::restore_vault(db_path, key, &bundle, &payload.passphrase)
+        .map_err(|error| format!("Vault restore failed: {error}"))?;
+    let core = state.set_core(core);
+    schedule_backups(Arc::downgrade(&core));
+
+    Ok(info)
+}
+
+#[tauri::command]
+async fn create_backup(
+    policy: Option<BackupPolicy>,
+    state: State<'_, AppState>,
+) -> Result<BackupSnapshot, CommandError> {
+    let core = state.require_core()?;
+
+    core.create_backup(policy.unwrap_or_default())
+        .await
+        .map_err(|error| CommandError::Failed(format!("Backup failed: {error}")))
+}
+
+/// Works while locked, so a vault that no longer opens can still be restored.
+#[tauri::command]
+async fn list_backups() -> Result<Vec<BackupSnapshot>, CommandError> {
+    let db_path = vault_path().map_err(|error| format!("Storage failure: {error}"))?;
+
+    ArthaCore::list_vault_backups(&db_path)
+        .map_err(|error| CommandError::Failed(format!("Backup listing failed: {error}")))
+}
+
+#[tauri::command]
+async fn restore_backup(
+    name: String,
+    state: State<'_, AppState>,
+) -> Result<BackupSnapshot, CommandError> {
+    let core = state.require_core()?;
+
+    core.restore_backup(&name)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Backup restore failed: {error}")))
+}
+
+/// Restores a snapshot over a locked vault and unlocks the result.
+#[tauri::command]
+async fn restore_vault_backup(
+    payload: RestoreVaultBackupPayload,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    if state.core.read().is_some() {
+        return Err(CommandError::Failed(
+            "Lock the vault before restoring over it, or use restore_backup".into(),
+        ));
+    }
+    let db_path = vault_path().map_err(|error| format!("Storage failure: {error}"))?;
+    let provider = vault_key_provider(&db_path, payload.key_passphrase.as_deref())?;
+    let key = key_provider::get_or_create_vault_key(provider.as_ref(), true)
+        .map_err(|error| format!("Key provider failure: {error}"))?;
+
+    let core = ArthaCore::restore_vault_backup(db_path, key, &payload.name)
+        .map_err(|error| format!("Backup restore failed: {error}"))?;
+    let core = state.set_core(core);
+    schedule_backups(Arc::downgrade(&core));
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn export_data(
+    payload: ExportDataPayload,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    let core = state.require_core()?;
+
+    core.export_data(payload.kind, payload.format, payload.filter)
+        .await
+        .map_err(|error| CommandError::Failed(format!("Export failed: {error}")))
+}
+
+/// Re-keys the vault under a fresh key from the configured provider. If the app
+/// dies midway, `unlock_vault` finishes the job on the next start.
+#[tauri::command]
+async fn rotate_vault_key(
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<UnlockVaultResponse, CommandError> {
+    let core = state.require_core()?;
+
+    let db_path = vault_path().map_err(|error| format!("Storage failure: {error}"))?;
+    let provider = vault_key_provider(&db_path, passphrase.as_deref())?;
+    let rotated = core
+        .rotate_key(provider.as_ref())
+        .await
+        .map_err(|error| format!("Key rotation failed: {error}"))?;
+
+    let rotated = state.set_core(rotated);
+    schedule_backups(Arc::downgrade(&rotated));
+
+    Ok(UnlockVaultResponse {
+        ok: true,
+        message: "Vault key rotated".into(),
+        recovery_phrase: None,
+    })
+}
+
+#[tauri::command]
+async fn get_audit_log(
+    query: Option<AuditQuery>,
+    state: State<'_, AppState>,
+) -> Result<Vec<AuditEntry>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_audit_log(query.unwrap_or_default())
+        .await
+        .map_err(|error| CommandError::Failed(format!("Audit log fetch failed: {error}")))
+}
+
+#[tauri::command]
+async fn verify_audit_log(state: State<'_, AppState>) -> Result<AuditVerification, CommandError> {
+    let core = state.require_core()?;
+
+    core.verify_audit_log()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Audit log verification failed: {error}")))
+}
+
+#[tauri::command]
+async fn undo(state: State<'_, AppState>) -> Result<Option<JournalEntry>, CommandError> {
+    let core = state.require_core()?;
+
+    core.undo()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Undo failed: {error}")))
+}
+
+#[tauri::command]
+async fn redo(state: State<'_, AppState>) -> Result<Option<JournalEntry>, CommandError> {
+    let core = state.require_core()?;
+
+    core.redo()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Redo failed: {error}")))
+}
+
+#[tauri::command]
+async fn get_undo_history(state: State<'_, AppState>) -> Result<Vec<JournalEntry>, CommandError> {
+    let core = state.require_core()?;
+
+    core.get_undo_history()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Undo history fetch failed: {error}")))
+}
+
+/// Locks the vault now. Commands fail with `CommandError::Locked` until the next
+/// unlock.
+#[tauri::command]
+async fn lock_vault(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.lock();
+    Ok(())
+}
+
+/// Unlocks the vault with its recovery phrase when the key provider has lost (or
+/// never had) the key, and stores a fresh key with the provider.
+#[tauri::command]
+async fn recover_vault(
+    phrase: String,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<UnlockVaultResponse, CommandError> {
+    let db_path = vault_path().map_err(|error| format!("Storage failure: {error}"))?;
+    let provider = vault_key_provider(&db_path, passphrase.as_deref())?;
+
+    let core = ArthaCore::recover(db_path, &phrase, provider.as_ref())
+        .await
+        .map_err(|error| format!("Vault recovery failed: {error}"))?;
+
+    let core = state.set_core(core);
+    schedule_backups(Arc::downgrade(&core));
+
+    Ok(UnlockVaultResponse {
+        ok: true,
+        message: "Vault recovered".into(),
+        recovery_phrase: None,
+    })
+}
+
+/// Issues a new recovery phrase, invalidating the previous one.
+#[tauri::command]
+async fn create_recovery_phrase(state: State<'_, AppState>) -> Result<String, CommandError> {
+    let core = state.require_core()?;
+
+    core.create_recovery_phrase()
+        .await
+        .map_err(|error| CommandError::Failed(format!("Recovery phrase setup failed: {error}")))
 }
 
 fn main() {
     tauri::Builder::default()
         .manage(AppState::new())
+        .setup(|app| {
+            watch_auto_lock(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             unlock_vault,
             import_csv,
             get_inbox,
             set_inbox_category,
             commit_inbox,
+            recategorize_transactions,
+            delete_transactions,
+            get_learned_tokens,
+            get_token_provenance,
+            update_learned_token,
+            delete_learned_token,
             get_summary,
+            get_report,
             get_networth_curve,
             get_app_settings,
             update_setting,
             get_budget_configs,
-            set_budget_config
+            set_budget_config,
+            delete_budget_config,
+            propose_budgets,
+            seed_budgets,
+            get_budget_thresholds,
+            set_budget_thresholds,
+            evaluate_budget_alerts,
+            get_tax_report,
+            export_tax_report_csv,
+            get_tax_rules,
+            add_tax_rule,
+            delete_tax_rule,
+            tag_tax_transaction,
+            clear_tax_tag,
+            upsert_holding,
+            delete_holding,
+            get_holdings,
+            get_holding_lots,
+            add_holding_lot,
+            delete_holding_lot,
+            get_holding_prices,
+            set_holding_price,
+            import_holding_prices,
+            import_cas,
+            import_tradebook,
+            import_contract_note,
+            get_broker_fundings,
+            get_capital_gains,
+            export_capital_gains_csv,
+            get_performance,
+            get_time_weighted_return,
+            add_loan,
+            update_loan,
+            delete_loan,
+            get_loans,
+            get_loan_schedule,
+            get_loan_prepayments,
+            add_loan_prepayment,
+            delete_loan_prepayment,
+            match_loan_payments,
+            add_credit_card,
+            update_credit_card,
+            delete_credit_card,
+            get_credit_cards,
+            get_card_statements,
+            assign_card_transactions,
+            link_card_payment,
+            unlink_card_transaction,
+            match_card_transactions,
+            add_goal,
+            update_goal,
+            delete_goal,
+            get_goals,
+            link_goal_holding,
+            unlink_goal_holding,
+            link_goal_account,
+            unlink_goal_account,
+            get_goal_contributions,
+            add_goal_contribution,
+            tag_goal_transaction,
+            delete_goal_contribution,
+            export_vault,
+            inspect_vault_bundle,
+            restore_vault,
+            create_backup,
+            list_backups,
+            restore_backup,
+            restore_vault_backup,
+            export_data,
+            rotate_vault_key,
+            recover_vault,
+            create_recovery_phrase,
+            lock_vault,
+            get_audit_log,
+            verify_audit_log,
+            undo,
+            redo,
+            get_undo_history
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,77 @@
+use std::time::{Duration, Instant, SystemTime};
+
+/// Wall-clock time that may pass beyond the monotonic clock between two polls
+/// before we call it a suspend.
+const SLEEP_GAP: Duration = Duration::from_secs(30);
+
+/// Detects suspend/resume by comparing clocks: the monotonic clock stops while the
+/// machine sleeps (macOS, Linux), the wall clock does not.
+pub struct SleepDetector {
+    monotonic: Instant,
+    wall: SystemTime,
+}
+
+impl SleepDetector {
+    pub fn new() -> Self {
+        Self {
+            monotonic: Instant::now(),
+            wall: SystemTime::now(),
+        }
+    }
+
+    /// Whether the machine slept since the previous call.
+    pub fn woke_up(&mut self) -> bool {
+        let (monotonic, wall) = (Instant::now(), SystemTime::now());
+        let awake = monotonic - self.monotonic;
+        let elapsed = wall.duration_since(self.wall).unwrap_or_default();
+        self.monotonic = monotonic;
+        self.wall = wall;
+        elapsed.saturating_sub(awake) > SLEEP_GAP
+    }
+}
+
+/// Whether the user's screen is locked.
+#[cfg(target_os = "macos")]
+pub fn screen_locked() -> bool {
+    use core_foundation::{
+        base::{CFType, TCFType},
+        boolean::CFBoolean,
+        dictionary::{CFDictionary, CFDictionaryRef},
+        string::CFString,
+    };
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSessionCopyCurrentDictionary() -> CFDictionaryRef;
+    }
+
+    // SAFETY: returns an owned dictionary (create rule) or null outside a GUI session.
+    let session = unsafe { CGSessionCopyCurrentDictionary() };
+    if session.is_null() {
+        return false;
+    }
+    let session: CFDictionary<CFString, CFType> =
+        unsafe { CFDictionary::wrap_under_create_rule(session) };
+    session
+        .find(CFString::from_static_string("CGSSessionScreenIsLocked"))
+        .and_then(|value| value.downcast::<CFBoolean>())
+        .map(bool::from)
+        .unwrap_or(false)
+}
+
+/// Whether the user's screen is locked, per logind's `LockedHint` (set by GNOME, KDE
+/// and most screen lockers).
+#[cfg(target_os = "linux")]
+pub fn screen_locked() -> bool {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".into());
+    std::process::Command::new("loginctl")
+        .args(["show-session", &session, "--property=LockedHint", "--value"])
+        .output()
+        .map(|output| output.status.success() && output.stdout.trim_ascii() == b"yes")
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn screen_locked() -> bool {
+    false
+}